@@ -2,11 +2,15 @@ use cosmwasm_std::{
     entry_point, to_binary, to_vec, ContractResult, CosmosMsg, Deps, DepsMut, Env, MessageInfo,
     QueryRequest, QueryResponse, Reply, Response, StdError, StdResult, SubMsg, SystemResult,
 };
-use osmo_bindings::{OsmosisMsg, OsmosisQuery};
+use osmo_bindings::{
+    DenomsByCreatorResponse, OsmosisMsg, OsmosisQuerier, OsmosisQuery, SwapResponse,
+};
 
 use crate::errors::ReflectError;
 use crate::msg::{ChainResponse, ExecuteMsg, InstantiateMsg, OwnerResponse, QueryMsg};
-use crate::state::{config, config_read, replies, replies_read, State};
+use crate::state::{
+    captures, captures_read, config, config_read, replies, replies_read, swaps, swaps_read, State,
+};
 
 #[entry_point]
 pub fn instantiate(
@@ -30,6 +34,9 @@ pub fn execute(
     match msg {
         ExecuteMsg::ReflectMsg { msgs } => execute_reflect(deps, env, info, msgs),
         ExecuteMsg::ReflectSubMsg { msgs } => execute_reflect_subcall(deps, env, info, msgs),
+        ExecuteMsg::ReflectSwapAndCapture { msgs } => {
+            execute_reflect_swap_and_capture(deps, env, info, msgs)
+        }
         ExecuteMsg::ChangeOwner { owner } => execute_change_owner(deps, env, info, owner),
     }
 }
@@ -81,6 +88,33 @@ pub fn execute_reflect_subcall(
         .add_submessages(msgs))
 }
 
+pub fn execute_reflect_swap_and_capture(
+    deps: DepsMut<OsmosisQuery>,
+    _env: Env,
+    info: MessageInfo,
+    msgs: Vec<SubMsg<OsmosisMsg>>,
+) -> Result<Response<OsmosisMsg>, ReflectError> {
+    let state = config(deps.storage).load()?;
+    if info.sender != state.owner {
+        return Err(ReflectError::NotCurrentOwner {
+            expected: state.owner.into(),
+            actual: info.sender.into(),
+        });
+    }
+
+    if msgs.is_empty() {
+        return Err(ReflectError::MessagesEmpty);
+    }
+
+    for msg in &msgs {
+        captures(deps.storage).save(&msg.id.to_be_bytes(), &true)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "reflect_swap_and_capture")
+        .add_submessages(msgs))
+}
+
 pub fn execute_change_owner(
     deps: DepsMut<OsmosisQuery>,
     _env: Env,
@@ -103,20 +137,40 @@ pub fn execute_change_owner(
         .add_attribute("owner", new_owner))
 }
 
-/// This just stores the result for future query
+/// This just stores the result for future query.
+/// For submessages sent by ReflectSwapAndCapture, it also decodes the swap result, if any.
+/// Any earlier swap captured under the same id is dropped, so it is never reported as current.
 #[entry_point]
 pub fn reply(deps: DepsMut<OsmosisQuery>, _env: Env, msg: Reply) -> Result<Response, ReflectError> {
     let key = msg.id.to_be_bytes();
     replies(deps.storage).save(&key, &msg)?;
+
+    // a capture only applies to the reply of the submessage it was requested for
+    let captured = captures_read(deps.storage).may_load(&key)?.is_some();
+    if captured {
+        captures(deps.storage).remove(&key);
+    }
+    // not every message returns a SwapResponse, those are simply skipped
+    let swap = match msg.result.into_result() {
+        Ok(response) if captured => SwapResponse::from_data(&response.data).ok(),
+        _ => None,
+    };
+    match swap {
+        Some(swap) => swaps(deps.storage).save(&key, &swap)?,
+        None => swaps(deps.storage).remove(&key),
+    }
     Ok(Response::default())
 }
 
 #[entry_point]
-pub fn query(deps: Deps<OsmosisQuery>, _env: Env, msg: QueryMsg) -> StdResult<QueryResponse> {
+pub fn query(deps: Deps<OsmosisQuery>, env: Env, msg: QueryMsg) -> StdResult<QueryResponse> {
     match msg {
         QueryMsg::Owner {} => to_binary(&query_owner(deps)?),
         QueryMsg::Chain { request } => to_binary(&query_chain(deps, &request)?),
+        QueryMsg::ChainBatch { requests } => to_binary(&query_chain_batch(deps, &requests)?),
         QueryMsg::SubMsgResult { id } => to_binary(&query_subcall(deps, id)?),
+        QueryMsg::CapturedSwap { id } => to_binary(&query_captured_swap(deps, id)?),
+        QueryMsg::MyDenoms {} => to_binary(&query_my_denoms(deps, env)?),
     }
 }
 
@@ -133,6 +187,16 @@ fn query_subcall(deps: Deps<OsmosisQuery>, id: u64) -> StdResult<Reply> {
     replies_read(deps.storage).load(&key)
 }
 
+fn query_captured_swap(deps: Deps<OsmosisQuery>, id: u64) -> StdResult<SwapResponse> {
+    let key = id.to_be_bytes();
+    swaps_read(deps.storage).load(&key)
+}
+
+fn query_my_denoms(deps: Deps<OsmosisQuery>, env: Env) -> StdResult<DenomsByCreatorResponse> {
+    let querier = OsmosisQuerier::new(&deps.querier);
+    querier.denoms_by_creator(env.contract.address.into())
+}
+
 fn query_chain(
     deps: Deps<OsmosisQuery>,
     request: &QueryRequest<OsmosisQuery>,
@@ -153,6 +217,16 @@ fn query_chain(
     }
 }
 
+fn query_chain_batch(
+    deps: Deps<OsmosisQuery>,
+    requests: &[QueryRequest<OsmosisQuery>],
+) -> StdResult<Vec<ChainResponse>> {
+    requests
+        .iter()
+        .map(|request| query_chain(deps, request))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -163,7 +237,11 @@ mod tests {
         coin, coins, from_binary, AllBalanceResponse, BankMsg, BankQuery, Binary, Coin, Event,
         StakingMsg, StdError, SubMsgResponse,
     };
-    use cosmwasm_std::{OwnedDeps, SubMsgResult, SystemError};
+    use cosmwasm_std::{Addr, OwnedDeps, SubMsgResult, SystemError};
+    use cosmwasm_std::{Decimal, Uint128};
+    use cw_multi_test::{ContractWrapper, Executor};
+    use osmo_bindings::{PoolStateResponse, SpotPriceResponse, SwapAmount, SwapAmountWithLimit};
+    use osmo_bindings_test::{OsmosisApp, Pool};
     use std::marker::PhantomData;
 
     pub fn mock_dependencies(
@@ -373,16 +451,222 @@ mod tests {
         let outer: ChainResponse = from_binary(&response).unwrap();
         let inner: AllBalanceResponse = from_binary(&outer.data).unwrap();
         assert_eq!(inner.amount, coins(123, "ucosm"));
+    }
+
+    #[test]
+    fn chain_query_custom_works() {
+        // custom queries need the osmosis mock, so this runs in multitest
+        let pool = Pool::new(coin(6_000_000, "osmo"), coin(1_500_000, "atom"));
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| router.custom.set_pool(storage, 1, &pool))
+            .unwrap();
+
+        let code_id = app.store_code(Box::new(ContractWrapper::new(execute, instantiate, query)));
+        let reflect = app
+            .instantiate_contract(
+                code_id,
+                Addr::unchecked("creator"),
+                &InstantiateMsg {},
+                &[],
+                "reflect",
+                None,
+            )
+            .unwrap();
 
-        // TODO? or better in multitest?
-        // // with custom query
-        // let msg = QueryMsg::Chain {
-        //     request: OsmosisQuery::Ping {}.into(),
-        // };
-        // let response = query(deps.as_ref(), mock_env(), msg).unwrap();
-        // let outer: ChainResponse = from_binary(&response).unwrap();
-        // let inner: SpecialResponse = from_binary(&outer.data).unwrap();
-        // assert_eq!(inner.msg, "pong");
+        let msg = QueryMsg::Chain {
+            request: OsmosisQuery::PoolState { id: 1 }.into(),
+        };
+        let outer: ChainResponse = app.wrap().query_wasm_smart(&reflect, &msg).unwrap();
+        let inner: PoolStateResponse = from_binary(&outer.data).unwrap();
+        assert_eq!(inner, pool.into_response(1));
+    }
+
+    #[test]
+    fn reflect_chain_batch() {
+        let pool = Pool::new(coin(6_000_000, "osmo"), coin(1_500_000, "atom"));
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| router.custom.set_pool(storage, 1, &pool))
+            .unwrap();
+
+        let code_id = app.store_code(Box::new(ContractWrapper::new(execute, instantiate, query)));
+        let reflect = app
+            .instantiate_contract(
+                code_id,
+                Addr::unchecked("creator"),
+                &InstantiateMsg {},
+                &[],
+                "reflect",
+                None,
+            )
+            .unwrap();
+
+        let msg = QueryMsg::ChainBatch {
+            requests: vec![
+                OsmosisQuery::PoolState { id: 1 }.into(),
+                OsmosisQuery::spot_price(1, "osmo", "atom").into(),
+            ],
+        };
+        let responses: Vec<ChainResponse> = app.wrap().query_wasm_smart(&reflect, &msg).unwrap();
+        assert_eq!(responses.len(), 2);
+        let state: PoolStateResponse = from_binary(&responses[0].data).unwrap();
+        assert_eq!(state, pool.into_response(1));
+        let SpotPriceResponse { price } = from_binary(&responses[1].data).unwrap();
+        assert_eq!(price, Decimal::percent(25));
+
+        // one bad query fails the batch
+        let msg = QueryMsg::ChainBatch {
+            requests: vec![
+                OsmosisQuery::PoolState { id: 1 }.into(),
+                OsmosisQuery::PoolState { id: 2 }.into(),
+            ],
+        };
+        let err = app
+            .wrap()
+            .query_wasm_smart::<Vec<ChainResponse>>(&reflect, &msg)
+            .unwrap_err();
+        assert!(err.to_string().contains("Pool 2 does not exist"), "{}", err);
+    }
+
+    #[test]
+    fn reflect_swap_and_capture() {
+        let pool = Pool::new(coin(6_000_000, "osmo"), coin(1_500_000, "atom"));
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| router.custom.set_pool(storage, 1, &pool))
+            .unwrap();
+
+        let code_id = app.store_code(Box::new(
+            ContractWrapper::new(execute, instantiate, query).with_reply_empty(reply),
+        ));
+        let reflect = app
+            .instantiate_contract(
+                code_id,
+                Addr::unchecked("creator"),
+                &InstantiateMsg {},
+                &[],
+                "reflect",
+                None,
+            )
+            .unwrap();
+        app.init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &reflect, coins(12_000, "osmo"))
+        })
+        .unwrap();
+
+        let swap = OsmosisMsg::simple_swap(
+            1,
+            "osmo",
+            "atom",
+            SwapAmountWithLimit::ExactIn {
+                input: Uint128::new(12_000),
+                min_output: Uint128::zero(),
+            },
+        );
+        let expected = pool
+            .clone()
+            .swap("osmo", "atom", SwapAmount::In(Uint128::new(12_000)))
+            .unwrap();
+        let msg = ExecuteMsg::ReflectSwapAndCapture {
+            msgs: vec![SubMsg::reply_on_success(swap, 7)],
+        };
+        app.execute_contract(Addr::unchecked("creator"), reflect.clone(), &msg, &[])
+            .unwrap();
+
+        let captured: SwapResponse = app
+            .wrap()
+            .query_wasm_smart(&reflect, &QueryMsg::CapturedSwap { id: 7 })
+            .unwrap();
+        assert_eq!(captured.amount, expected);
+
+        // nothing is captured for other ids
+        let err = app
+            .wrap()
+            .query_wasm_smart::<SwapResponse>(&reflect, &QueryMsg::CapturedSwap { id: 8 })
+            .unwrap_err();
+        assert!(err.to_string().contains("not found"), "{}", err);
+    }
+
+    #[test]
+    fn captured_swap_is_not_reused() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), info.clone(), InstantiateMsg {}).unwrap();
+
+        let id = 7u64;
+        let send = SubMsg::reply_always(
+            BankMsg::Send {
+                to_address: String::from("friend"),
+                amount: coins(1, "token"),
+            },
+            id,
+        );
+        let swap_reply = |amount: u128| Reply {
+            id,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: Some(
+                    to_binary(&SwapResponse {
+                        amount: SwapAmount::Out(Uint128::new(amount)),
+                    })
+                    .unwrap(),
+                ),
+            }),
+        };
+        let captured_swap = |deps: &OwnedDeps<_, _, _, OsmosisQuery>| {
+            query(deps.as_ref(), mock_env(), QueryMsg::CapturedSwap { id })
+                .map(|raw| from_binary::<SwapResponse>(&raw).unwrap().amount)
+        };
+        let capture = |deps: &mut OwnedDeps<_, _, _, OsmosisQuery>| {
+            let msg = ExecuteMsg::ReflectSwapAndCapture {
+                msgs: vec![send.clone()],
+            };
+            execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        };
+
+        capture(&mut deps);
+        reply(deps.as_mut(), mock_env(), swap_reply(100)).unwrap();
+        assert_eq!(
+            captured_swap(&deps).unwrap(),
+            SwapAmount::Out(Uint128::new(100))
+        );
+
+        // a plain submessage reusing the id is not captured and drops the old swap
+        let msg = ExecuteMsg::ReflectSubMsg {
+            msgs: vec![send.clone()],
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        reply(deps.as_mut(), mock_env(), swap_reply(200)).unwrap();
+        captured_swap(&deps).unwrap_err();
+
+        // a failed capture under the same id drops the old swap as well
+        capture(&mut deps);
+        reply(deps.as_mut(), mock_env(), swap_reply(300)).unwrap();
+        assert_eq!(
+            captured_swap(&deps).unwrap(),
+            SwapAmount::Out(Uint128::new(300))
+        );
+        capture(&mut deps);
+        let failed = Reply {
+            id,
+            result: SubMsgResult::Err(String::from("swap failed")),
+        };
+        reply(deps.as_mut(), mock_env(), failed).unwrap();
+        captured_swap(&deps).unwrap_err();
+
+        // as does a capture without any swap data
+        capture(&mut deps);
+        reply(deps.as_mut(), mock_env(), swap_reply(400)).unwrap();
+        capture(&mut deps);
+        let no_swap = Reply {
+            id,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: None,
+            }),
+        };
+        reply(deps.as_mut(), mock_env(), no_swap).unwrap();
+        captured_swap(&deps).unwrap_err();
     }
 
     #[test]
@@ -448,4 +732,55 @@ mod tests {
         assert_eq!(result.data, Some(data));
         assert_eq!(result.events, events);
     }
+
+    #[test]
+    fn query_my_denoms() {
+        let mut app = OsmosisApp::new();
+        let code_id = app.store_code(Box::new(ContractWrapper::new(execute, instantiate, query)));
+        let reflect = app
+            .instantiate_contract(
+                code_id,
+                Addr::unchecked("creator"),
+                &InstantiateMsg {},
+                &[],
+                "reflect",
+                None,
+            )
+            .unwrap();
+
+        // nothing created yet
+        let DenomsByCreatorResponse { denoms } = app
+            .wrap()
+            .query_wasm_smart(&reflect, &QueryMsg::MyDenoms {})
+            .unwrap();
+        assert_eq!(denoms, Vec::<String>::new());
+
+        let msg = ExecuteMsg::ReflectMsg {
+            msgs: vec![
+                OsmosisMsg::create_denom("one").into(),
+                OsmosisMsg::create_denom("two").into(),
+            ],
+        };
+        app.execute_contract(Addr::unchecked("creator"), reflect.clone(), &msg, &[])
+            .unwrap();
+
+        // a denom of another creator is not listed
+        app.execute(
+            Addr::unchecked("other"),
+            OsmosisMsg::create_denom("three").into(),
+        )
+        .unwrap();
+
+        let DenomsByCreatorResponse { denoms } = app
+            .wrap()
+            .query_wasm_smart(&reflect, &QueryMsg::MyDenoms {})
+            .unwrap();
+        assert_eq!(
+            denoms,
+            vec![
+                format!("factory/{}/one", reflect),
+                format!("factory/{}/two", reflect),
+            ]
+        );
+    }
 }
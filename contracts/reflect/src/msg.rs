@@ -8,9 +8,20 @@ pub struct InstantiateMsg {}
 
 #[cw_serde]
 pub enum ExecuteMsg {
-    ReflectMsg { msgs: Vec<CosmosMsg<OsmosisMsg>> },
-    ReflectSubMsg { msgs: Vec<SubMsg<OsmosisMsg>> },
-    ChangeOwner { owner: String },
+    ReflectMsg {
+        msgs: Vec<CosmosMsg<OsmosisMsg>>,
+    },
+    ReflectSubMsg {
+        msgs: Vec<SubMsg<OsmosisMsg>>,
+    },
+    /// Like ReflectSubMsg, but the `SwapResponse` in the data of every successful reply is
+    /// decoded and stored, see QueryMsg::CapturedSwap. The messages should reply on success.
+    ReflectSwapAndCapture {
+        msgs: Vec<SubMsg<OsmosisMsg>>,
+    },
+    ChangeOwner {
+        owner: String,
+    },
 }
 
 #[cw_serde]
@@ -21,9 +32,21 @@ pub enum QueryMsg {
     /// Queries the blockchain and returns the result untouched
     #[returns(ChainResponse)]
     Chain { request: QueryRequest<OsmosisQuery> },
+    /// Like Chain, but for several queries at once. Results are in the same order as the
+    /// requests, the first failing query fails the whole batch.
+    #[returns(Vec<ChainResponse>)]
+    ChainBatch {
+        requests: Vec<QueryRequest<OsmosisQuery>>,
+    },
     /// If there was a previous ReflectSubMsg with this ID, returns cosmwasm_std::Reply
     #[returns(cosmwasm_std::Reply)]
     SubMsgResult { id: u64 },
+    /// If there was a previous ReflectSwapAndCapture with this ID, returns the decoded swap result
+    #[returns(osmo_bindings::SwapResponse)]
+    CapturedSwap { id: u64 },
+    /// Returns the factory denoms created by this contract
+    #[returns(osmo_bindings::DenomsByCreatorResponse)]
+    MyDenoms {},
 }
 
 // We define a custom struct for each query response
@@ -2,6 +2,8 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use cosmwasm_std::{Addr, Reply, Storage};
+use osmo_bindings::SwapResponse;
+
 use cosmwasm_storage::{
     bucket, bucket_read, singleton, singleton_read, Bucket, ReadonlyBucket, ReadonlySingleton,
     Singleton,
@@ -9,24 +11,43 @@ use cosmwasm_storage::{
 
 const CONFIG_KEY: &[u8] = b"config";
 const RESULT_PREFIX: &[u8] = b"result";
+const CAPTURE_PREFIX: &[u8] = b"capture";
+const SWAP_PREFIX: &[u8] = b"swap";
 
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
 pub struct State {
     pub owner: Addr,
 }
 
-pub fn config(storage: &mut dyn Storage) -> Singleton<State> {
+pub fn config(storage: &mut dyn Storage) -> Singleton<'_, State> {
     singleton(storage, CONFIG_KEY)
 }
 
-pub fn config_read(storage: &dyn Storage) -> ReadonlySingleton<State> {
+pub fn config_read(storage: &dyn Storage) -> ReadonlySingleton<'_, State> {
     singleton_read(storage, CONFIG_KEY)
 }
 
-pub fn replies(storage: &mut dyn Storage) -> Bucket<Reply> {
+pub fn replies(storage: &mut dyn Storage) -> Bucket<'_, Reply> {
     bucket(storage, RESULT_PREFIX)
 }
 
-pub fn replies_read(storage: &dyn Storage) -> ReadonlyBucket<Reply> {
+pub fn replies_read(storage: &dyn Storage) -> ReadonlyBucket<'_, Reply> {
     bucket_read(storage, RESULT_PREFIX)
 }
+
+/// The submessage IDs whose swap results should be captured on reply
+pub fn captures(storage: &mut dyn Storage) -> Bucket<'_, bool> {
+    bucket(storage, CAPTURE_PREFIX)
+}
+
+pub fn captures_read(storage: &dyn Storage) -> ReadonlyBucket<'_, bool> {
+    bucket_read(storage, CAPTURE_PREFIX)
+}
+
+pub fn swaps(storage: &mut dyn Storage) -> Bucket<'_, SwapResponse> {
+    bucket(storage, SWAP_PREFIX)
+}
+
+pub fn swaps_read(storage: &dyn Storage) -> ReadonlyBucket<'_, SwapResponse> {
+    bucket_read(storage, SWAP_PREFIX)
+}
@@ -1,26 +1,31 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult, Uint128,
+    to_binary, Addr, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult, Uint128,
 };
 use cw2::set_contract_version;
 
 use crate::error::TokenFactoryError;
 use crate::msg::{ExecuteMsg, GetDenomResponse, InstantiateMsg, QueryMsg};
 use crate::state::{State, STATE};
-use osmo_bindings::{OsmosisMsg, OsmosisQuerier, OsmosisQuery};
+use osmo_bindings::{split_factory_denom, OsmosisMsg, OsmosisQuerier, OsmosisQuery};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:tokenfactory-demo";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+// limits of x/tokenfactory denoms
+const MAX_CREATOR_LENGTH: usize = 75;
+const MAX_SUBDENOM_LENGTH: usize = 44;
+const MAX_DENOM_LENGTH: usize = 128;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut<OsmosisQuery>,
     _env: Env,
     info: MessageInfo,
     _msg: InstantiateMsg,
-) -> Result<Response, TokenFactoryError> {
+) -> Result<Response<OsmosisMsg>, TokenFactoryError> {
     let state = State {
         owner: info.sender.clone(),
     };
@@ -55,11 +60,17 @@ pub fn execute(
             amount,
             burn_from_address,
         } => burn_tokens(deps, denom, amount, burn_from_address),
+        ExecuteMsg::ForceTransfer {
+            denom,
+            amount,
+            from_address,
+            to_address,
+        } => force_transfer(deps, denom, amount, from_address, to_address),
     }
 }
 
 pub fn create_denom(subdenom: String) -> Result<Response<OsmosisMsg>, TokenFactoryError> {
-    if subdenom.eq("") {
+    if subdenom.is_empty() {
         return Err(TokenFactoryError::InvalidSubdenom { subdenom });
     }
 
@@ -79,7 +90,7 @@ pub fn change_admin(
 ) -> Result<Response<OsmosisMsg>, TokenFactoryError> {
     deps.api.addr_validate(&new_admin_address)?;
 
-    validate_denom(deps, denom.clone())?;
+    validate_denom(deps, denom.clone(), false)?;
 
     let change_admin_msg = OsmosisMsg::ChangeAdmin {
         denom,
@@ -105,7 +116,7 @@ pub fn mint_tokens(
         return Result::Err(TokenFactoryError::ZeroAmount {});
     }
 
-    validate_denom(deps, denom.clone())?;
+    validate_denom(deps, denom.clone(), false)?;
 
     let mint_tokens_msg = OsmosisMsg::mint_contract_tokens(denom, amount, mint_to_address);
 
@@ -132,7 +143,7 @@ pub fn burn_tokens(
         return Result::Err(TokenFactoryError::ZeroAmount {});
     }
 
-    validate_denom(deps, denom.clone())?;
+    validate_denom(deps, denom.clone(), false)?;
 
     let burn_token_msg = OsmosisMsg::burn_contract_tokens(denom, amount, burn_from_address);
 
@@ -143,6 +154,36 @@ pub fn burn_tokens(
     Ok(res)
 }
 
+pub fn force_transfer(
+    deps: DepsMut<OsmosisQuery>,
+    denom: String,
+    amount: Uint128,
+    from_address: String,
+    to_address: String,
+) -> Result<Response<OsmosisMsg>, TokenFactoryError> {
+    deps.api.addr_validate(&from_address)?;
+    deps.api.addr_validate(&to_address)?;
+
+    if amount.is_zero() {
+        return Result::Err(TokenFactoryError::ZeroAmount {});
+    }
+
+    validate_denom(deps, denom.clone(), false)?;
+
+    let force_transfer_msg = OsmosisMsg::ForceTransfer {
+        denom,
+        amount,
+        from_address,
+        to_address,
+    };
+
+    let res = Response::new()
+        .add_attribute("method", "force_transfer")
+        .add_message(force_transfer_msg);
+
+    Ok(res)
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn query(deps: Deps<OsmosisQuery>, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
@@ -162,42 +203,61 @@ fn get_denom(deps: Deps<OsmosisQuery>, creator_addr: String, subdenom: String) -
     }
 }
 
-fn validate_denom(deps: DepsMut<OsmosisQuery>, denom: String) -> Result<(), TokenFactoryError> {
-    let denom_to_split = denom.clone();
-    let tokenfactory_denom_parts: Vec<&str> = denom_to_split.split('/').collect();
+/// Splits a `factory/{creator}/{subdenom}` denom into its creator and subdenom, following
+/// `osmo_bindings::split_factory_denom`, so nested subdenoms are accepted. This applies the chain's length limits locally, so no query is needed.
+pub fn parse_full_denom(denom: &str) -> Result<(Addr, String), TokenFactoryError> {
+    let invalid = |message: String| TokenFactoryError::InvalidDenom {
+        denom: denom.to_string(),
+        message,
+    };
 
-    if tokenfactory_denom_parts.len() != 3 {
-        return Result::Err(TokenFactoryError::InvalidDenom {
-            denom,
-            message: std::format!(
-                "denom must have 3 parts separated by /, had {}",
-                tokenfactory_denom_parts.len()
-            ),
-        });
+    let (creator_address, subdenom) = split_factory_denom(denom).ok_or_else(|| {
+        invalid(String::from(
+            "denom must have the form factory/{creator}/{subdenom}",
+        ))
+    })?;
+
+    // https://github.com/osmosis-labs/osmosis/blob/main/x/tokenfactory/types/denoms.go
+    if creator_address.len() > MAX_CREATOR_LENGTH {
+        return Err(invalid(format!(
+            "invalid creator address: longer than {} characters",
+            MAX_CREATOR_LENGTH
+        )));
     }
-
-    let prefix = tokenfactory_denom_parts[0];
-    let creator_address = tokenfactory_denom_parts[1];
-    let subdenom = tokenfactory_denom_parts[2];
-
-    if !prefix.eq_ignore_ascii_case("factory") {
-        return Result::Err(TokenFactoryError::InvalidDenom {
-            denom,
-            message: std::format!("prefix must be 'factory', was {}", prefix),
-        });
+    if subdenom.len() > MAX_SUBDENOM_LENGTH {
+        return Err(invalid(format!(
+            "subdenom longer than {} characters",
+            MAX_SUBDENOM_LENGTH
+        )));
+    }
+    if denom.len() > MAX_DENOM_LENGTH {
+        return Err(invalid(format!(
+            "denom longer than {} characters",
+            MAX_DENOM_LENGTH
+        )));
     }
 
-    // Validate denom by attempting to query for full denom
-    let response = OsmosisQuerier::new(&deps.querier)
-        .full_denom(String::from(creator_address), String::from(subdenom));
-    if response.is_err() {
-        return Result::Err(TokenFactoryError::InvalidDenom {
-            denom,
-            message: response.err().unwrap().to_string(),
-        });
+    Ok((Addr::unchecked(creator_address), subdenom.to_string()))
+}
+
+/// Checks that `denom` is a well formed factory denom.
+/// With `check_existence` this also queries the chain to make sure the denom was created.
+fn validate_denom(
+    deps: DepsMut<OsmosisQuery>,
+    denom: String,
+    check_existence: bool,
+) -> Result<(), TokenFactoryError> {
+    parse_full_denom(&denom)?;
+
+    if check_existence
+        && OsmosisQuerier::new(&deps.querier)
+            .denom_admin(denom.clone())
+            .is_err()
+    {
+        return Err(TokenFactoryError::DenomDoesNotExist { denom });
     }
 
-    Result::Ok(())
+    Ok(())
 }
 
 #[cfg(test)]
@@ -210,6 +270,7 @@ mod tests {
         coins, from_binary, Attribute, ContractResult, CosmosMsg, OwnedDeps, Querier, StdError,
         SystemError, SystemResult,
     };
+    use cw_multi_test::{ContractWrapper, Executor};
     use osmo_bindings::OsmosisQuery;
     use osmo_bindings_test::OsmosisApp;
     use std::marker::PhantomData;
@@ -238,13 +299,13 @@ mod tests {
                 } => {
                     let binary_request = to_binary(a).unwrap();
 
-                    if creator_addr.eq("") {
+                    if creator_addr.is_empty() {
                         return SystemResult::Err(SystemError::InvalidRequest {
                             error: String::from("invalid creator address"),
                             request: binary_request,
                         });
                     }
-                    if subdenom.eq("") {
+                    if subdenom.is_empty() {
                         return SystemResult::Err(SystemError::InvalidRequest {
                             error: String::from("invalid subdenom"),
                             request: binary_request,
@@ -303,13 +364,13 @@ mod tests {
         let expected_message = CosmosMsg::from(OsmosisMsg::CreateDenom {
             subdenom: String::from(DENOM_NAME),
         });
-        let actual_message = res.messages.get(0).unwrap();
+        let actual_message = res.messages.first().unwrap();
         assert_eq!(expected_message, actual_message.msg);
 
         assert_eq!(1, res.attributes.len());
 
         let expected_attribute = Attribute::new("method", "create_denom");
-        let actual_attribute = res.attributes.get(0).unwrap();
+        let actual_attribute = res.attributes.first().unwrap();
         assert_eq!(expected_attribute, actual_attribute);
 
         assert_eq!(res.data.ok_or(0), Err(0));
@@ -355,13 +416,13 @@ mod tests {
             denom: String::from(full_denom_name),
             new_admin_address: String::from(NEW_ADMIN_ADDR),
         });
-        let actual_message = res.messages.get(0).unwrap();
+        let actual_message = res.messages.first().unwrap();
         assert_eq!(expected_message, actual_message.msg);
 
         assert_eq!(1, res.attributes.len());
 
         let expected_attribute = Attribute::new("method", "change_admin");
-        let actual_attribute = res.attributes.get(0).unwrap();
+        let actual_attribute = res.attributes.first().unwrap();
         assert_eq!(expected_attribute, actual_attribute);
 
         assert_eq!(res.data.ok_or(0), Err(0));
@@ -396,7 +457,7 @@ mod tests {
         let full_denom_name: &str =
             &format!("{}/{}/{}", DENOM_PREFIX, MOCK_CONTRACT_ADDR, DENOM_NAME)[..];
 
-        validate_denom(deps.as_mut(), String::from(full_denom_name)).unwrap()
+        validate_denom(deps.as_mut(), String::from(full_denom_name), false).unwrap()
     }
 
     #[test]
@@ -407,11 +468,9 @@ mod tests {
 
         let info = mock_info("creator", &coins(2, "token"));
 
-        // too many parts in denom
-        let full_denom_name: &str = &format!(
-            "{}/{}/{}/invalid",
-            DENOM_PREFIX, MOCK_CONTRACT_ADDR, DENOM_NAME
-        )[..];
+        // wrong prefix
+        let full_denom_name: &str =
+            &format!("{}/{}/{}", "invalid", MOCK_CONTRACT_ADDR, DENOM_NAME)[..];
 
         let msg = ExecuteMsg::ChangeAdmin {
             denom: String::from(full_denom_name),
@@ -421,7 +480,7 @@ mod tests {
 
         let expected_error = TokenFactoryError::InvalidDenom {
             denom: String::from(full_denom_name),
-            message: String::from("denom must have 3 parts separated by /, had 4"),
+            message: String::from("denom must have the form factory/{creator}/{subdenom}"),
         };
 
         assert_eq!(expected_error, err);
@@ -454,13 +513,13 @@ mod tests {
             amount: mint_amount,
             mint_to_address: String::from(NEW_ADMIN_ADDR),
         });
-        let actual_message = res.messages.get(0).unwrap();
+        let actual_message = res.messages.first().unwrap();
         assert_eq!(expected_message, actual_message.msg);
 
         assert_eq!(1, res.attributes.len());
 
         let expected_attribute = Attribute::new("method", "mint_tokens");
-        let actual_attribute = res.attributes.get(0).unwrap();
+        let actual_attribute = res.attributes.first().unwrap();
         assert_eq!(expected_attribute, actual_attribute);
 
         assert_eq!(res.data.ok_or(0), Err(0));
@@ -485,7 +544,7 @@ mod tests {
         let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
         let expected_error = TokenFactoryError::InvalidDenom {
             denom: String::from(full_denom_name),
-            message: String::from("denom must have 3 parts separated by /, had 2"),
+            message: String::from("denom must have the form factory/{creator}/{subdenom}"),
         };
 
         assert_eq!(expected_error, err);
@@ -514,13 +573,13 @@ mod tests {
             amount: mint_amount,
             burn_from_address: String::from(""),
         });
-        let actual_message = res.messages.get(0).unwrap();
+        let actual_message = res.messages.first().unwrap();
         assert_eq!(expected_message, actual_message.msg);
 
         assert_eq!(1, res.attributes.len());
 
         let expected_attribute = Attribute::new("method", "burn_tokens");
-        let actual_attribute = res.attributes.get(0).unwrap();
+        let actual_attribute = res.attributes.first().unwrap();
         assert_eq!(expected_attribute, actual_attribute);
 
         assert_eq!(res.data.ok_or(0), Err(0))
@@ -552,23 +611,18 @@ mod tests {
     }
 
     #[test]
-    fn msg_validate_denom_too_many_parts_invalid() {
+    fn msg_validate_denom_nested_subdenom_valid() {
         let mut deps = mock_dependencies();
 
-        // too many parts in denom
+        // the subdenom may contain further /
         let full_denom_name: &str = &format!(
-            "{}/{}/{}/invalid",
+            "{}/{}/{}/nested",
             DENOM_PREFIX, MOCK_CONTRACT_ADDR, DENOM_NAME
         )[..];
 
-        let err = validate_denom(deps.as_mut(), String::from(full_denom_name)).unwrap_err();
-
-        let expected_error = TokenFactoryError::InvalidDenom {
-            denom: String::from(full_denom_name),
-            message: String::from("denom must have 3 parts separated by /, had 4"),
-        };
-
-        assert_eq!(expected_error, err);
+        validate_denom(deps.as_mut(), String::from(full_denom_name), false).unwrap();
+        let (_, subdenom) = parse_full_denom(full_denom_name).unwrap();
+        assert_eq!(subdenom, format!("{}/nested", DENOM_NAME));
     }
 
     #[test]
@@ -578,11 +632,11 @@ mod tests {
         // too little parts in denom
         let full_denom_name: &str = &format!("{}/{}", DENOM_PREFIX, MOCK_CONTRACT_ADDR)[..];
 
-        let err = validate_denom(deps.as_mut(), String::from(full_denom_name)).unwrap_err();
+        let err = validate_denom(deps.as_mut(), String::from(full_denom_name), false).unwrap_err();
 
         let expected_error = TokenFactoryError::InvalidDenom {
             denom: String::from(full_denom_name),
-            message: String::from("denom must have 3 parts separated by /, had 2"),
+            message: String::from("denom must have the form factory/{creator}/{subdenom}"),
         };
 
         assert_eq!(expected_error, err);
@@ -592,18 +646,19 @@ mod tests {
     fn msg_validate_denom_denom_prefix_invalid() {
         let mut deps = mock_dependencies();
 
-        // invalid denom prefix
-        let full_denom_name: &str =
-            &format!("{}/{}/{}", "invalid", MOCK_CONTRACT_ADDR, DENOM_NAME)[..];
+        // invalid denom prefix, which is case sensitive
+        for prefix in ["invalid", "FACTORY"] {
+            let full_denom_name = format!("{}/{}/{}", prefix, MOCK_CONTRACT_ADDR, DENOM_NAME);
 
-        let err = validate_denom(deps.as_mut(), String::from(full_denom_name)).unwrap_err();
+            let err = validate_denom(deps.as_mut(), full_denom_name.clone(), false).unwrap_err();
 
-        let expected_error = TokenFactoryError::InvalidDenom {
-            denom: String::from(full_denom_name),
-            message: String::from("prefix must be 'factory', was invalid"),
-        };
+            let expected_error = TokenFactoryError::InvalidDenom {
+                denom: full_denom_name,
+                message: String::from("denom must have the form factory/{creator}/{subdenom}"),
+            };
 
-        assert_eq!(expected_error, err);
+            assert_eq!(expected_error, err);
+        }
     }
 
     #[test]
@@ -612,14 +667,139 @@ mod tests {
 
         let full_denom_name: &str = &format!("{}/{}/{}", DENOM_PREFIX, "", DENOM_NAME)[..]; // empty contract address
 
-        let err = validate_denom(deps.as_mut(), String::from(full_denom_name)).unwrap_err();
+        let err = validate_denom(deps.as_mut(), String::from(full_denom_name), true).unwrap_err();
 
         match err {
             TokenFactoryError::InvalidDenom { denom, message } => {
                 assert_eq!(String::from(full_denom_name), denom);
-                assert!(message.contains("invalid creator address"))
+                assert_eq!(
+                    message,
+                    "denom must have the form factory/{creator}/{subdenom}"
+                )
             }
             err => panic!("Unexpected error: {:?}", err),
         }
     }
+
+    #[test]
+    fn parse_full_denom_valid() {
+        let full_denom_name = format!("{}/{}/{}", DENOM_PREFIX, MOCK_CONTRACT_ADDR, DENOM_NAME);
+        let (creator, subdenom) = parse_full_denom(&full_denom_name).unwrap();
+        assert_eq!(creator, Addr::unchecked(MOCK_CONTRACT_ADDR));
+        assert_eq!(subdenom, DENOM_NAME);
+
+        // empty subdenoms are valid
+        let full_denom_name = format!("{}/{}/", DENOM_PREFIX, MOCK_CONTRACT_ADDR);
+        let (_, subdenom) = parse_full_denom(&full_denom_name).unwrap();
+        assert_eq!(subdenom, "");
+    }
+
+    #[test]
+    fn parse_full_denom_too_long() {
+        let long_subdenom = "a".repeat(45);
+        let full_denom_name = format!("{}/{}/{}", DENOM_PREFIX, MOCK_CONTRACT_ADDR, long_subdenom);
+        let err = parse_full_denom(&full_denom_name).unwrap_err();
+        assert_eq!(
+            err,
+            TokenFactoryError::InvalidDenom {
+                denom: full_denom_name,
+                message: String::from("subdenom longer than 44 characters"),
+            }
+        );
+
+        let long_creator = "a".repeat(76);
+        let full_denom_name = format!("{}/{}/{}", DENOM_PREFIX, long_creator, DENOM_NAME);
+        let err = parse_full_denom(&full_denom_name).unwrap_err();
+        assert_eq!(
+            err,
+            TokenFactoryError::InvalidDenom {
+                denom: full_denom_name,
+                message: String::from("invalid creator address: longer than 75 characters"),
+            }
+        );
+    }
+
+    #[test]
+    fn msg_validate_denom_check_existence() {
+        let mut deps = mock_dependencies();
+
+        let full_denom_name = format!("{}/{}/{}", DENOM_PREFIX, MOCK_CONTRACT_ADDR, DENOM_NAME);
+
+        // well formed is enough without the existence check
+        validate_denom(deps.as_mut(), full_denom_name.clone(), false).unwrap();
+        let err = validate_denom(deps.as_mut(), full_denom_name.clone(), true).unwrap_err();
+        assert_eq!(
+            err,
+            TokenFactoryError::DenomDoesNotExist {
+                denom: full_denom_name.clone()
+            }
+        );
+
+        // once created, it passes both
+        let create = OsmosisMsg::CreateDenom {
+            subdenom: String::from(DENOM_NAME),
+        };
+        deps.querier
+            .execute(Addr::unchecked(MOCK_CONTRACT_ADDR), create.into())
+            .unwrap();
+        validate_denom(deps.as_mut(), full_denom_name, true).unwrap();
+    }
+
+    #[test]
+    fn force_transfer_claws_back_tokens() {
+        let owner = Addr::unchecked("owner");
+        let user = Addr::unchecked("user");
+
+        let mut app = OsmosisApp::new();
+        let code_id = app.store_code(Box::new(ContractWrapper::new(execute, instantiate, query)));
+        let mut instantiate_contract = |label| {
+            app.instantiate_contract(code_id, owner.clone(), &InstantiateMsg {}, &[], label, None)
+                .unwrap()
+        };
+        let admin = instantiate_contract("admin");
+        let other = instantiate_contract("other");
+
+        let create = ExecuteMsg::CreateDenom {
+            subdenom: String::from(DENOM_NAME),
+        };
+        app.execute_contract(owner.clone(), admin.clone(), &create, &[])
+            .unwrap();
+        let denom = format!("{}/{}/{}", DENOM_PREFIX, admin, DENOM_NAME);
+        let mint = ExecuteMsg::MintTokens {
+            denom: denom.clone(),
+            amount: Uint128::new(1_000),
+            mint_to_address: user.to_string(),
+        };
+        app.execute_contract(owner.clone(), admin.clone(), &mint, &[])
+            .unwrap();
+
+        let balance =
+            |app: &OsmosisApp, addr: &Addr| app.wrap().query_balance(addr, &denom).unwrap().amount;
+        assert_eq!(balance(&app, &user), Uint128::new(1_000));
+        assert_eq!(balance(&app, &admin), Uint128::zero());
+
+        // only the denom admin may move the user's tokens
+        let claw_back = ExecuteMsg::ForceTransfer {
+            denom: denom.clone(),
+            amount: Uint128::new(400),
+            from_address: user.to_string(),
+            to_address: other.to_string(),
+        };
+        let err = app
+            .execute_contract(owner.clone(), other.clone(), &claw_back, &[])
+            .unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "Unauthorized");
+        assert_eq!(balance(&app, &user), Uint128::new(1_000));
+
+        let claw_back = ExecuteMsg::ForceTransfer {
+            denom: denom.clone(),
+            amount: Uint128::new(400),
+            from_address: user.to_string(),
+            to_address: admin.to_string(),
+        };
+        app.execute_contract(owner, admin.clone(), &claw_back, &[])
+            .unwrap();
+        assert_eq!(balance(&app, &user), Uint128::new(600));
+        assert_eq!(balance(&app, &admin), Uint128::new(400));
+    }
 }
@@ -24,3 +24,51 @@ pub enum TokenFactoryError {
     #[error("amount was zero, must be positive")]
     ZeroAmount {},
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_messages() {
+        let cases = vec![
+            (
+                TokenFactoryError::Std(StdError::generic_err("boom")),
+                "Generic error: boom",
+            ),
+            (TokenFactoryError::Unauthorized {}, "Unauthorized"),
+            (
+                TokenFactoryError::InvalidSubdenom {
+                    subdenom: String::from(""),
+                },
+                "Invalid subdenom: \"\"",
+            ),
+            (
+                TokenFactoryError::InvalidDenom {
+                    denom: String::from("factory/creator"),
+                    message: String::from("denom must have the form factory/{creator}/{subdenom}"),
+                },
+                "Invalid denom: \"factory/creator\" \"denom must have the form factory/{creator}/{subdenom}\"",
+            ),
+            (
+                TokenFactoryError::DenomDoesNotExist {
+                    denom: String::from("factory/creator/fundz"),
+                },
+                "denom does not exist: \"factory/creator/fundz\"",
+            ),
+            (
+                TokenFactoryError::BurnFromAddressNotSupported {
+                    address: String::from("burnfrom"),
+                },
+                "address is not supported yet, was: \"burnfrom\"",
+            ),
+            (
+                TokenFactoryError::ZeroAmount {},
+                "amount was zero, must be positive",
+            ),
+        ];
+        for (err, expected) in cases {
+            assert_eq!(err.to_string(), expected);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{
+    to_binary, Addr, CosmosMsg, CustomQuery, QuerierWrapper, StdResult, WasmMsg, WasmQuery,
+};
+
+use crate::msg::{ExecuteMsg, GetDenomResponse, QueryMsg};
+
+/// CwTemplateContract is a wrapper around Addr that provides a lot of helpers
+/// for working with this.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct CwTemplateContract(pub Addr);
+
+impl CwTemplateContract {
+    pub fn addr(&self) -> Addr {
+        self.0.clone()
+    }
+
+    pub fn call<T: Into<ExecuteMsg>>(&self, msg: T) -> StdResult<CosmosMsg> {
+        let msg = to_binary(&msg.into())?;
+        Ok(WasmMsg::Execute {
+            contract_addr: self.addr().into(),
+            msg,
+            funds: vec![],
+        }
+        .into())
+    }
+
+    /// Get the full denom `subdenom` created by `creator_address` would have
+    pub fn get_denom<Q: CustomQuery>(
+        &self,
+        querier: &QuerierWrapper<Q>,
+        creator_address: String,
+        subdenom: String,
+    ) -> StdResult<GetDenomResponse> {
+        let msg = QueryMsg::GetDenom {
+            creator_address,
+            subdenom,
+        };
+        let query = WasmQuery::Smart {
+            contract_addr: self.addr().into(),
+            msg: to_binary(&msg)?,
+        }
+        .into();
+        querier.query(&query)
+    }
+
+    #[deprecated(note = "use get_denom instead")]
+    #[allow(non_snake_case)]
+    pub fn getDenom<Q: CustomQuery>(
+        &self,
+        querier: &QuerierWrapper<Q>,
+        creator_address: String,
+        subdenom: String,
+    ) -> StdResult<GetDenomResponse> {
+        self.get_denom(querier, creator_address, subdenom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::{execute, instantiate, query};
+    use crate::msg::InstantiateMsg;
+    use cw_multi_test::{ContractWrapper, Executor};
+    use osmo_bindings::OsmosisQuery;
+    use osmo_bindings_test::OsmosisApp;
+
+    #[test]
+    fn get_denom_queries_contract() {
+        let mut app = OsmosisApp::new();
+        let code_id = app.store_code(Box::new(ContractWrapper::new(execute, instantiate, query)));
+        let addr = app
+            .instantiate_contract(
+                code_id,
+                Addr::unchecked("owner"),
+                &InstantiateMsg {},
+                &[],
+                "tokenfactory",
+                None,
+            )
+            .unwrap();
+        let contract = CwTemplateContract(addr.clone());
+
+        let querier = QuerierWrapper::<OsmosisQuery>::new(&*app);
+        let GetDenomResponse { denom } = contract
+            .get_denom(&querier, addr.to_string(), "fundz".to_string())
+            .unwrap();
+        assert_eq!(denom, format!("factory/{}/fundz", addr));
+    }
+}
@@ -5,7 +5,8 @@ use cw2::set_contract_version;
 
 use crate::error::TwapError;
 use crate::msg::{
-    GetArithmeticTwapResponse, GetArithmeticTwapToNowResponse, InstantiateMsg, QueryMsg,
+    GetArithmeticTwapResponse, GetArithmeticTwapToNowResponse, GetSpotPriceResponse,
+    InstantiateMsg, QueryMsg,
 };
 use crate::state::{State, STATE};
 use osmo_bindings::{OsmosisQuerier, OsmosisQuery};
@@ -62,6 +63,19 @@ pub fn query(deps: Deps<OsmosisQuery>, _env: Env, msg: QueryMsg) -> StdResult<Bi
             base_asset_denom,
             start_time,
         )),
+
+        QueryMsg::GetSpotPrice {
+            id,
+            denom_in,
+            denom_out,
+            with_swap_fee,
+        } => to_binary(&get_spot_price(
+            deps,
+            id,
+            denom_in,
+            denom_out,
+            with_swap_fee,
+        )?),
     }
 }
 
@@ -105,3 +119,91 @@ fn get_arithmetic_twap_to_now(
         twap: response.twap,
     }
 }
+
+fn get_spot_price(
+    deps: Deps<OsmosisQuery>,
+    id: u64,
+    denom_in: String,
+    denom_out: String,
+    with_swap_fee: bool,
+) -> StdResult<GetSpotPriceResponse> {
+    let querier = OsmosisQuerier::new(&deps.querier);
+    let response = if with_swap_fee {
+        querier.spot_price_with_fee(id, denom_in, denom_out)?
+    } else {
+        querier.spot_price(id, denom_in, denom_out)?
+    };
+
+    Ok(GetSpotPriceResponse {
+        price: response.price,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::{coin, Addr, Decimal, Empty};
+    use cw_multi_test::{ContractWrapper, Executor};
+    use osmo_bindings::OsmosisMsg;
+    use osmo_bindings_test::{OsmosisApp, Pool};
+
+    // OsmosisApp runs contracts emitting OsmosisMsg, this one never emits any messages
+    fn execute(
+        _deps: DepsMut<OsmosisQuery>,
+        _env: Env,
+        _info: MessageInfo,
+        _msg: Empty,
+    ) -> StdResult<Response<OsmosisMsg>> {
+        Ok(Response::new())
+    }
+
+    fn instantiate_osmosis(
+        deps: DepsMut<OsmosisQuery>,
+        env: Env,
+        info: MessageInfo,
+        msg: InstantiateMsg,
+    ) -> Result<Response<OsmosisMsg>, TwapError> {
+        let res = instantiate(deps, env, info, msg)?;
+        Ok(Response::new().add_attributes(res.attributes))
+    }
+
+    #[test]
+    fn spot_price_through_querier() {
+        let pool = Pool::new(coin(6_000_000, "osmo"), coin(1_500_000, "atom"));
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| router.custom.set_pool(storage, 1, &pool))
+            .unwrap();
+
+        let code_id = app.store_code(Box::new(ContractWrapper::new(
+            execute,
+            instantiate_osmosis,
+            query,
+        )));
+        let twap = app
+            .instantiate_contract(
+                code_id,
+                Addr::unchecked("owner"),
+                &InstantiateMsg {},
+                &[],
+                "twap",
+                None,
+            )
+            .unwrap();
+
+        let spot_price = |with_swap_fee| {
+            let msg = QueryMsg::GetSpotPrice {
+                id: 1,
+                denom_in: "atom".to_string(),
+                denom_out: "osmo".to_string(),
+                with_swap_fee,
+            };
+            let GetSpotPriceResponse { price } = app.wrap().query_wasm_smart(&twap, &msg).unwrap();
+            price
+        };
+        assert_eq!(spot_price(false), Decimal::percent(400));
+        assert_eq!(
+            spot_price(true),
+            Decimal::percent(400) * (Decimal::one() - pool.fee)
+        );
+    }
+}
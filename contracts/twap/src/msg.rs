@@ -22,6 +22,14 @@ pub enum QueryMsg {
         base_asset_denom: String,
         start_time: i64,
     },
+    /// The current spot price of `denom_in` in `denom_out`, for comparison with the TWAPs
+    #[returns(GetSpotPriceResponse)]
+    GetSpotPrice {
+        id: u64,
+        denom_in: String,
+        denom_out: String,
+        with_swap_fee: bool,
+    },
 }
 
 // We define a custom struct for each query response
@@ -34,3 +42,8 @@ pub struct GetArithmeticTwapResponse {
 pub struct GetArithmeticTwapToNowResponse {
     pub twap: Decimal,
 }
+
+#[cw_serde]
+pub struct GetSpotPriceResponse {
+    pub price: Decimal,
+}
@@ -8,4 +8,7 @@ pub enum ContractError {
 
     #[error("Invalid full denom '{full_denom}'")]
     InvalidFullDenom { full_denom: String },
+
+    #[error("Invalid subdenom '{subdenom}', only [0-9a-zA-Z./] are allowed")]
+    InvalidSubdenom { subdenom: String },
 }
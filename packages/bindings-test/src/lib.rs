@@ -1,4 +1,7 @@
 pub mod error;
 mod multitest;
 
-pub use multitest::{OsmosisApp, OsmosisAppWrapped, OsmosisError, OsmosisModule, Pool};
+pub use multitest::{
+    Epoch, Gauge, Lock, OsmosisApp, OsmosisAppWrapped, OsmosisError, OsmosisModule, OsmosisSudo,
+    Pool, PoolKind, Position, DEFAULT_EPOCH_DURATION, DEFAULT_EPOCH_PROVISIONS, INIT_POOL_SHARES,
+};
@@ -1,49 +1,220 @@
-use anyhow::{bail, Result as AnyResult};
+use anyhow::Result as AnyResult;
 use itertools::Itertools;
 use schemars::JsonSchema;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::cmp::max;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
 use std::fmt::Debug;
 use std::iter;
 use std::ops::{Deref, DerefMut};
+use std::str::FromStr;
 use thiserror::Error;
 
 use cosmwasm_std::testing::{MockApi, MockStorage};
 use cosmwasm_std::{
-    coins, to_binary, Addr, Api, BankMsg, Binary, BlockInfo, Coin, CustomQuery, Decimal, Empty,
-    Fraction, Isqrt, Querier, QuerierResult, StdError, StdResult, Storage, Uint128,
+    coin, coins, from_slice, to_binary, Addr, Api, BankMsg, Binary, BlockInfo, Coin, CustomQuery,
+    Decimal, Decimal256, Event, Fraction, Isqrt, Order, Querier, QuerierResult, StdError,
+    StdResult, Storage, Timestamp, Uint128, Uint256,
 };
 use cw_multi_test::{
     App, AppResponse, BankKeeper, BankSudo, BasicAppBuilder, CosmosRouter, Module, WasmKeeper,
 };
-use cw_storage_plus::Map;
+use cw_storage_plus::{Bound, Item, Map};
 
 use crate::error::ContractError;
 use osmo_bindings::{
-    ArithmeticTwapResponse, ArithmeticTwapToNowResponse, FullDenomResponse, OsmosisMsg,
-    OsmosisQuery, PoolStateResponse, SpotPriceResponse, Step, Swap, SwapAmount,
-    SwapAmountWithLimit, SwapResponse,
+    is_factory_denom, pool_id_from_lp_denom, AccountLockedCoinsResponse, AccountLocksResponse,
+    ArithmeticTwapResponse, ArithmeticTwapToNowResponse, BeforeSendHookResponse, CalcExitResponse,
+    CalcJoinPoolSharesResponse, CollectIncentivesResponse, CollectSpreadRewardsResponse,
+    CreateGaugeResponse, CreatePoolResponse, CreatePositionResponse, DenomAdminResponse,
+    DenomSupplyResponse, DenomsByCreatorResponse, EpochInfoResponse, EpochProvisionsResponse,
+    EstimateSwapInResponse, EstimateSwapOutResponse, ExitPoolResponse, FullDenomResponse,
+    GaugeResponse, GeometricTwapResponse, IsFactoryDenomResponse, JoinPoolResponse, LockedResponse,
+    LpSpotPriceResponse, OsmosisMsg, OsmosisQuery, PoolAsset, PoolInfo, PoolLiquidityResponse,
+    PoolParams, PoolParamsResponse, PoolStateResponse, PoolTypeResponse, PoolsResponse,
+    PositionResponse, PriceImpactResponse, SpotPriceBothResponse, SpotPriceKind, SpotPriceResponse,
+    SpotPriceWithFeeResponse, Step, SuperfluidDelegationResponse, Swap, SwapAmount,
+    SwapAmountWithLimit, SwapResponse, SwapRoute, TotalLiquidityResponse,
+    TotalPoolLiquidityResponse, WithdrawPositionResponse, MAX_TICK, MIN_TICK,
 };
 
 pub const POOLS: Map<u64, Pool> = Map::new("pools");
+pub const LOCKS: Map<u64, Lock> = Map::new("locks");
+/// Before send hook contract of each factory denom that has one
+pub const BEFORE_SEND_HOOKS: Map<&str, Addr> = Map::new("before_send_hooks");
+/// Validator each superfluid delegated lock is delegated to
+pub const SUPERFLUID: Map<u64, String> = Map::new("superfluid");
+/// Admin of every factory denom created so far, empty if the admin was cleared
+pub const DENOM_ADMINS: Map<&str, String> = Map::new("denom_admins");
+pub const DENOM_MAX_SUPPLIES: Map<&str, Uint128> = Map::new("denom_max_supplies");
+pub const GAUGES: Map<u64, Gauge> = Map::new("gauges");
+pub const POSITIONS: Map<u64, Position> = Map::new("positions");
+/// Price observations as (Unix time millisecond, price), sorted by time
+pub type PriceHistory = Vec<(i64, Decimal)>;
+/// Price observations keyed by pool ID, quote denom and base denom. Each series also answers TWAP
+/// queries for the reversed pair.
+pub const TWAP_HISTORY: Map<(u64, &str, &str), PriceHistory> = Map::new("twap_history");
+/// Page size of `OsmosisQuery::Pools` when no limit is given, and the largest one allowed
+pub const MAX_POOLS_LIMIT: u32 = 100;
+/// Page size of `OsmosisQuery::AccountLocks` when no limit is given, and the largest one allowed
+pub const MAX_LOCKS_LIMIT: u32 = 100;
+/// Timing of the synthetic "day" epoch
+pub const EPOCH: Item<Epoch> = Item::new("epoch");
+
+/// Length of the "day" epoch unless configured otherwise, see `OsmosisApp::new_with_epoch_duration`
+pub const DEFAULT_EPOCH_DURATION: u64 = 24 * 60 * 60;
+/// Answer of `OsmosisQuery::EpochProvisions`
+pub const EPOCH_PROVISIONS: Item<Decimal> = Item::new("epoch_provisions");
+/// Epoch provisions unless configured otherwise, see `OsmosisApp::with_epoch_provisions`.
+/// 821917808219.178082191780 uosmo, the 300M OSMO of the first year spread over its daily epochs.
+pub const DEFAULT_EPOCH_PROVISIONS: Decimal = Decimal::raw(821_917_808_219_178_082_191_780_000_000);
 
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema, Debug)]
 pub struct Pool {
     pub assets: Vec<Coin>,
+    /// Balancer weight of each asset, in the same order as `assets`
+    pub weights: Vec<Uint128>,
     pub shares: Uint128,
     pub fee: Decimal,
+    /// Fraction of the shares of every exit that stays in the pool
+    pub exit_fee: Decimal,
+    pub kind: PoolKind,
+}
+
+/// The curve a pool trades along
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolKind {
+    /// Weighted constant product over the reserves, see `Pool::weights`
+    Balancer,
+    /// The x/gamm stableswap curve `x * y * (x^2 + y^2 + w) = k`, where `w` is the sum of the
+    /// squares of all other reserves. Every reserve is divided by its scaling factor (in the same
+    /// order as `Pool::assets`) first.
+    /// Near the peg this is close to constant sum, so swaps have very little slippage.
+    Stableswap { scaling_factors: Vec<u64> },
+    /// A simplified x/concentratedliquidity pool of two assets. It trades as constant product over
+    /// the total reserves of all positions, whose liquidity is counted in `Pool::shares`.
+    /// The price never leaves `current_tick`, which only decides which positions are in range.
+    Concentrated { current_tick: i64 },
+}
+
+impl PoolKind {
+    /// The name reported by `OsmosisQuery::PoolType`
+    pub fn name(&self) -> &'static str {
+        match self {
+            PoolKind::Balancer => "balancer",
+            PoolKind::Stableswap { .. } => "stableswap",
+            PoolKind::Concentrated { .. } => "concentrated",
+        }
+    }
 }
 
+/// LP shares minted on pool creation, the same as x/gamm uses (100 * 10^18)
+pub const INIT_POOL_SHARES: u128 = 100_000_000_000_000_000_000;
+
 impl Pool {
     // make an equal-weighted uniswap-like pool with 0.3% fees
     pub fn new(a: Coin, b: Coin) -> Self {
-        let shares = (a.amount * b.amount).isqrt();
+        // the square root of a product of two Uint128 always fits back into one
+        let shares = Uint128::try_from(a.amount.full_mul(b.amount).isqrt()).unwrap();
         Pool {
             assets: vec![a, b],
+            weights: vec![Uint128::new(1), Uint128::new(1)],
             shares,
             fee: Decimal::permille(3),
+            exit_fee: Decimal::zero(),
+            kind: PoolKind::Balancer,
+        }
+    }
+
+    /// An empty concentrated liquidity pool of `denom0` and `denom1`, filled by
+    /// `OsmosisMsg::CreatePosition`. Swaps pay `spread_factor`.
+    pub fn new_concentrated(
+        denom0: impl Into<String>,
+        denom1: impl Into<String>,
+        spread_factor: Decimal,
+    ) -> Self {
+        Pool {
+            assets: vec![coin(0, denom0), coin(0, denom1)],
+            weights: vec![Uint128::new(1), Uint128::new(1)],
+            shares: Uint128::zero(),
+            fee: spread_factor,
+            exit_fee: Decimal::zero(),
+            kind: PoolKind::Concentrated { current_tick: 0 },
+        }
+    }
+
+    /// Builds a balancer pool as `OsmosisMsg::CreateBalancerPool` does
+    pub fn new_balancer(
+        pool_params: &PoolParams,
+        pool_assets: Vec<PoolAsset>,
+    ) -> Result<Self, OsmosisError> {
+        if pool_assets.len() < 2 {
+            return Err(OsmosisError::TooFewPoolAssets);
+        }
+        let mut pool = Pool {
+            assets: Vec::with_capacity(pool_assets.len()),
+            weights: Vec::with_capacity(pool_assets.len()),
+            shares: Uint128::new(INIT_POOL_SHARES),
+            fee: pool_params.swap_fee,
+            exit_fee: pool_params.exit_fee,
+            kind: PoolKind::Balancer,
+        };
+        for PoolAsset { token, weight } in pool_assets {
+            if weight.is_zero() {
+                return Err(OsmosisError::ZeroPoolWeight(token.denom));
+            }
+            if pool.has_denom(&token.denom) {
+                return Err(OsmosisError::DuplicatePoolAsset(token.denom));
+            }
+            pool.assets.push(token);
+            pool.weights.push(weight);
+        }
+        Ok(pool)
+    }
+
+    /// Builds a stableswap pool as `OsmosisMsg::CreateStableswapPool` does
+    pub fn new_stableswap(
+        initial_pool_liquidity: Vec<Coin>,
+        scaling_factors: Vec<u64>,
+        swap_fee: Decimal,
+    ) -> Result<Self, OsmosisError> {
+        if initial_pool_liquidity.len() < 2 {
+            return Err(OsmosisError::TooFewPoolAssets);
+        }
+        let scaling_factors = if scaling_factors.is_empty() {
+            vec![1; initial_pool_liquidity.len()]
+        } else {
+            scaling_factors
+        };
+        if scaling_factors.len() != initial_pool_liquidity.len() {
+            return Err(OsmosisError::ScalingFactorsMismatch {
+                expected: initial_pool_liquidity.len(),
+                got: scaling_factors.len(),
+            });
+        }
+        let mut pool = Pool {
+            assets: Vec::with_capacity(initial_pool_liquidity.len()),
+            weights: vec![Uint128::new(1); initial_pool_liquidity.len()],
+            shares: Uint128::new(INIT_POOL_SHARES),
+            fee: swap_fee,
+            exit_fee: Decimal::zero(),
+            kind: PoolKind::Stableswap {
+                scaling_factors: scaling_factors.clone(),
+            },
+        };
+        for (token, factor) in initial_pool_liquidity.into_iter().zip(&scaling_factors) {
+            if *factor == 0 {
+                return Err(OsmosisError::ZeroScalingFactor(token.denom));
+            }
+            if pool.has_denom(&token.denom) {
+                return Err(OsmosisError::DuplicatePoolAsset(token.denom));
+            }
+            pool.assets.push(token);
         }
+        Ok(pool)
     }
 
     pub fn has_denom(&self, denom: &str) -> bool {
@@ -57,6 +228,13 @@ impl Pool {
             .map(|c| c.amount)
     }
 
+    pub fn get_weight(&self, denom: &str) -> Option<Uint128> {
+        self.assets
+            .iter()
+            .position(|c| c.denom == denom)
+            .and_then(|pos| self.weights.get(pos).copied())
+    }
+
     pub fn set_amount(&mut self, denom: &str, amount: Uint128) -> Result<(), OsmosisError> {
         let pos = self
             .assets
@@ -67,24 +245,94 @@ impl Pool {
         Ok(())
     }
 
+    /// Returns how much `denom_out` one `denom_in` buys, see `SpotPriceKind` for the variants.
+    /// Passing a bool selects the kind the same way `OsmosisQuery::SpotPrice::with_swap_fee` does.
     pub fn spot_price(
         &self,
         denom_in: &str,
         denom_out: &str,
-        with_swap_fee: bool,
+        kind: impl Into<SpotPriceKind>,
     ) -> Result<Decimal, OsmosisError> {
         // ensure they have both assets
         let (bal_in, bal_out) = match (self.get_amount(denom_in), self.get_amount(denom_out)) {
             (Some(a), Some(b)) => (a, b),
             _ => return Err(OsmosisError::AssetNotInPool),
         };
-        let mult = if with_swap_fee {
-            Decimal::one() - self.fee
-        } else {
-            Decimal::one()
+        if bal_in.is_zero() || bal_out.is_zero() {
+            return Err(OsmosisError::EmptyPool);
+        }
+        let (weight_in, weight_out) = self.get_weights(denom_in, denom_out)?;
+        let mult = match kind.into() {
+            SpotPriceKind::Marginal => Decimal::one(),
+            SpotPriceKind::EffectiveWithFee => Decimal::one() - self.fee,
         };
-        let price = Decimal::from_ratio(bal_out * mult, bal_in);
-        Ok(price)
+        let price = match self.kind {
+            PoolKind::Balancer | PoolKind::Concentrated { .. } => {
+                // (bal_out / weight_out) / (bal_in / weight_in)
+                Decimal::checked_from_ratio(
+                    bal_out.checked_mul(weight_in)?,
+                    bal_in.checked_mul(weight_out)?,
+                )?
+            }
+            PoolKind::Stableswap { .. } => {
+                // the slope of the curve, -dy/dx = (df/dx) / (df/dy), converted back from the
+                // scaled reserves
+                let curve = self.stableswap_curve(denom_in, denom_out)?;
+                let (x, y, w) = (curve.scale_in(bal_in)?, curve.scale_out(bal_out)?, curve.w);
+                let three = Uint256::from(3u8);
+                let (xx, yy) = (x.checked_mul(x)?, y.checked_mul(y)?);
+                let df_dx = y
+                    .checked_mul(three.checked_mul(xx)?.checked_add(yy)?.checked_add(w)?)?
+                    .checked_mul(Uint256::from(curve.out_factor))?;
+                let df_dy = x
+                    .checked_mul(xx.checked_add(three.checked_mul(yy)?)?.checked_add(w)?)?
+                    .checked_mul(Uint256::from(curve.in_factor))?;
+                let price = Decimal256::checked_from_ratio(df_dx, df_dy)?;
+                Decimal::new(Uint128::try_from(price.atomics())?)
+            }
+        };
+        Ok(price * mult)
+    }
+
+    /// The stableswap curve between `denom_in` and `denom_out`. The reserves of all other assets
+    /// only enter it through the sum of their scaled squares.
+    fn stableswap_curve(
+        &self,
+        denom_in: &str,
+        denom_out: &str,
+    ) -> Result<StableswapCurve, OsmosisError> {
+        let scaling_factors = match &self.kind {
+            PoolKind::Stableswap { scaling_factors } => scaling_factors,
+            _ => return Err(OsmosisError::Unimplemented),
+        };
+        let mut curve = StableswapCurve {
+            in_factor: Uint128::zero(),
+            out_factor: Uint128::zero(),
+            w: Uint256::zero(),
+        };
+        for (asset, factor) in self.assets.iter().zip(scaling_factors) {
+            let factor = Uint128::from(*factor);
+            if asset.denom == denom_in {
+                curve.in_factor = factor;
+            } else if asset.denom == denom_out {
+                curve.out_factor = factor;
+            } else {
+                let scaled = Uint256::from(asset.amount / factor);
+                curve.w = curve.w.checked_add(scaled.checked_mul(scaled)?)?;
+            }
+        }
+        Ok(curve)
+    }
+
+    fn get_weights(
+        &self,
+        denom_in: &str,
+        denom_out: &str,
+    ) -> Result<(Uint128, Uint128), OsmosisError> {
+        match (self.get_weight(denom_in), self.get_weight(denom_out)) {
+            (Some(a), Some(b)) => Ok((a, b)),
+            _ => Err(OsmosisError::AssetNotInPool),
+        }
     }
 
     pub fn swap(
@@ -98,26 +346,65 @@ impl Pool {
             (Some(a), Some(b)) => (a, b),
             _ => return Err(OsmosisError::AssetNotInPool),
         };
-        // do calculations (in * out = k) equation
+        let (weight_in, weight_out) = self.get_weights(denom_in, denom_out)?;
+        // do calculations (in * out = k) equation, or its weighted form
+        // (in ^ weight_in * out ^ weight_out = k) if the weights differ,
+        // or the stableswap curve for such pools.
+        // k is taken as a Uint256, so pools with mainnet-scale reserves don't overflow
         let (final_in, final_out, payout) = match amount {
             SwapAmount::In(input) => {
                 let input_minus_fee = input * (Decimal::one() - self.fee);
-                let final_out =
-                    bal_in.checked_mul(bal_out)? / (bal_in.checked_add(input_minus_fee)?);
+                let final_out = if let PoolKind::Stableswap { .. } = self.kind {
+                    let curve = self.stableswap_curve(denom_in, denom_out)?;
+                    let k = curve.invariant(bal_in, bal_out)?;
+                    curve.solve_out(k, bal_in.checked_add(input_minus_fee)?, bal_out)?
+                } else if weight_in == weight_out {
+                    let k = bal_in.full_mul(bal_out);
+                    let new_in = Uint256::from(bal_in.checked_add(input_minus_fee)?);
+                    Uint128::try_from(k.checked_div(new_in)?)?
+                } else {
+                    // bal_out * (bal_in / (bal_in + input)) ^ (weight_in / weight_out), rounded up
+                    let ratio =
+                        Decimal::checked_from_ratio(bal_in, bal_in.checked_add(input_minus_fee)?)?;
+                    let exp = Decimal::checked_from_ratio(weight_in, weight_out)?;
+                    let remaining = pow_approx(ratio, exp)?;
+                    bal_out.checked_sub(bal_out * (Decimal::one() - remaining))?
+                };
                 let payout = SwapAmount::Out(bal_out.checked_sub(final_out)?);
                 let final_in = bal_in.checked_add(input)?;
                 (final_in, final_out, payout)
             }
             SwapAmount::Out(output) => {
-                let in_without_fee = bal_in.checked_mul(bal_out)? / bal_out.checked_sub(output)?;
-                // add one to handle rounding (final_in - old_in) / (1 - fee)
-                let mult = Decimal::one() - self.fee;
+                // Find the smallest input for which the `In` branch pays out at least `output`.
+                // It rounds the new out balance down, so we need
+                // bal_in + in_without_fee > bal_in * bal_out / (bal_out - output + 1)
+                let remaining = bal_out.checked_sub(output)?;
+                let in_without_fee = if let PoolKind::Stableswap { .. } = self.kind {
+                    let curve = self.stableswap_curve(denom_in, denom_out)?;
+                    let k = curve.invariant(bal_in, bal_out)?;
+                    curve.solve_in(k, remaining, bal_in)? - bal_in
+                } else if weight_in == weight_out {
+                    let k = bal_in.full_mul(bal_out);
+                    let new_in =
+                        k.checked_div(Uint256::from(remaining) + Uint256::one())? + Uint256::one();
+                    Uint128::try_from(new_in)?.saturating_sub(bal_in)
+                } else {
+                    // bal_in * ((bal_out / remaining) ^ (weight_out / weight_in) - 1), rounded up.
+                    // We raise the inverse ratio, which is below one, to keep pow_approx precise.
+                    let ratio = Decimal::checked_from_ratio(remaining, bal_out)?;
+                    let exp = Decimal::checked_from_ratio(weight_out, weight_in)?;
+                    let inverse = pow_approx(ratio, exp)?;
+                    ceil_ratio(
+                        bal_in,
+                        (Decimal::one() - inverse).atomics(),
+                        inverse.atomics(),
+                    )?
+                };
+                // The `In` branch rounds input * (1 - fee) down, so round the inverse up.
                 // Use this as Uint128 / Decimal is not implemented in cosmwasm_std
-                let pay_incl_fee = in_without_fee
-                    .checked_sub(bal_in)?
-                    .checked_mul(mult.denominator())?
-                    / mult.numerator()
-                    + Uint128::new(1);
+                let mult = Decimal::one() - self.fee;
+                let pay_incl_fee =
+                    ceil_ratio(in_without_fee, mult.denominator(), mult.numerator())?;
 
                 let payin = SwapAmount::In(pay_incl_fee);
                 let final_in = bal_in.checked_add(pay_incl_fee)?;
@@ -142,7 +429,10 @@ impl Pool {
                 let payout = self.swap(denom_in, denom_out, SwapAmount::In(input))?;
                 let payout_as = payout.as_out();
                 if payout_as < min_output {
-                    Err(OsmosisError::PriceTooLowExactIn(payout_as, min_output))
+                    Err(OsmosisError::MinOutputNotMet {
+                        got: payout_as,
+                        min: min_output,
+                    })
                 } else {
                     Ok(payout)
                 }
@@ -151,7 +441,10 @@ impl Pool {
                 let payin = self.swap(denom_in, denom_out, SwapAmount::Out(output))?;
                 let payin_as = payin.as_in();
                 if payin_as > max_input {
-                    Err(OsmosisError::PriceTooLowExactOut(payin_as, max_input))
+                    Err(OsmosisError::MaxInputExceeded {
+                        got: payin_as,
+                        max: max_input,
+                    })
                 } else {
                     Ok(payin)
                 }
@@ -159,6 +452,158 @@ impl Pool {
         }
     }
 
+    /// Adds liquidity without swapping, returning the assets taken and the shares minted.
+    /// If `token_in_maxs` is given, this mints as many shares as the scarcest asset allows, which
+    /// must be at least `share_out_amount`. Otherwise exactly `share_out_amount` are minted.
+    pub fn join(
+        &mut self,
+        share_out_amount: Uint128,
+        token_in_maxs: &[Coin],
+    ) -> Result<(Vec<Coin>, Uint128), OsmosisError> {
+        let max_of = |denom: &str| {
+            token_in_maxs
+                .iter()
+                .find(|c| c.denom == denom)
+                .map(|c| c.amount)
+                .unwrap_or_default()
+        };
+
+        let shares_out = if token_in_maxs.is_empty() {
+            share_out_amount
+        } else {
+            // the limiting ratio, rounded down so no asset needs more than was offered.
+            // Taken as a Uint256, as huge offers can be worth more shares than fit a Uint128.
+            let mut shares_out: Option<Uint256> = None;
+            for asset in self.assets.iter().filter(|a| !a.amount.is_zero()) {
+                let shares =
+                    max_of(&asset.denom).full_mul(self.shares) / Uint256::from(asset.amount);
+                shares_out = Some(shares_out.map_or(shares, |s| s.min(shares)));
+            }
+            let shares_out = match shares_out {
+                Some(shares) => Uint128::try_from(shares)?,
+                None => share_out_amount,
+            };
+            if shares_out < share_out_amount {
+                // report the first asset that cannot cover the requested shares
+                for asset in &self.assets {
+                    let required = ceil_ratio(asset.amount, share_out_amount, self.shares)?;
+                    let max = max_of(&asset.denom);
+                    if required > max {
+                        return Err(OsmosisError::JoinPoolMaxInputExceeded {
+                            required: Coin::new(required.u128(), &asset.denom),
+                            max,
+                        });
+                    }
+                }
+            }
+            shares_out
+        };
+
+        let mut tokens_consumed = Vec::with_capacity(self.assets.len());
+        for asset in self.assets.iter_mut() {
+            let required = ceil_ratio(asset.amount, shares_out, self.shares)?;
+            asset.amount = asset.amount.checked_add(required)?;
+            tokens_consumed.push(Coin::new(required.u128(), &asset.denom));
+        }
+        self.shares = self.shares.checked_add(shares_out)?;
+        Ok((tokens_consumed, shares_out))
+    }
+
+    /// Deposits a new concentrated liquidity position, returning the assets taken and the
+    /// liquidity created. The first position sets the price, later ones join at the limiting
+    /// ratio like `join` does.
+    pub fn add_position(
+        &mut self,
+        tokens_provided: &[Coin],
+    ) -> Result<(Vec<Coin>, Uint128), OsmosisError> {
+        if tokens_provided
+            .iter()
+            .any(|c| self.get_amount(&c.denom).is_none())
+        {
+            return Err(OsmosisError::AssetNotInPool);
+        }
+        let (consumed, liquidity) = if self.shares.is_zero() {
+            let provided = |denom: &str| {
+                tokens_provided
+                    .iter()
+                    .find(|c| c.denom == denom)
+                    .map(|c| c.amount)
+                    .unwrap_or_default()
+            };
+            let consumed: Vec<_> = self
+                .assets
+                .iter()
+                .map(|a| coin(provided(&a.denom).u128(), &a.denom))
+                .collect();
+            let liquidity = consumed[0].amount.full_mul(consumed[1].amount).isqrt();
+            let liquidity = Uint128::try_from(liquidity)?;
+            self.assets = consumed.clone();
+            self.shares = liquidity;
+            (consumed, liquidity)
+        } else {
+            self.join(Uint128::zero(), tokens_provided)?
+        };
+        if liquidity.is_zero() {
+            return Err(OsmosisError::ZeroAmount);
+        }
+        Ok((consumed, liquidity))
+    }
+
+    /// Burns `share_in_amount` shares, returning the assets paid out for them.
+    /// The exit fee is skimmed off the shares first (rounding up in favour of the pool) and only
+    /// the remainder is paid out. All `share_in_amount` shares are burnt though, so the assets
+    /// backing the skimmed ones stay in the pool and raise the value of every remaining share.
+    pub fn exit(
+        &mut self,
+        share_in_amount: Uint128,
+        token_out_mins: &[Coin],
+    ) -> Result<Vec<Coin>, OsmosisError> {
+        if self.shares.is_zero() {
+            return Err(OsmosisError::EmptyPool);
+        }
+        if share_in_amount > self.shares {
+            return Err(OsmosisError::InsufficientPoolShares {
+                requested: share_in_amount,
+                available: self.shares,
+            });
+        }
+        let skimmed = ceil_ratio(
+            share_in_amount,
+            self.exit_fee.numerator(),
+            self.exit_fee.denominator(),
+        )?;
+        let redeemed = share_in_amount.checked_sub(skimmed)?;
+
+        let mut tokens_out = Vec::with_capacity(self.assets.len());
+        for asset in self.assets.iter_mut() {
+            let output = asset.amount.multiply_ratio(redeemed, self.shares);
+            if !token_out_mins.is_empty() {
+                let min = token_out_mins
+                    .iter()
+                    .find(|c| c.denom == asset.denom)
+                    .map(|c| c.amount)
+                    .unwrap_or_default();
+                if output < min {
+                    return Err(OsmosisError::ExitPoolMinOutputNotMet {
+                        output: Coin::new(output.u128(), &asset.denom),
+                        min,
+                    });
+                }
+            }
+            asset.amount = asset.amount.checked_sub(output)?;
+            tokens_out.push(Coin::new(output.u128(), &asset.denom));
+        }
+        self.shares = self.shares.checked_sub(share_in_amount)?;
+        Ok(tokens_out)
+    }
+
+    pub fn params(&self) -> PoolParams {
+        PoolParams {
+            swap_fee: self.fee,
+            exit_fee: self.exit_fee,
+        }
+    }
+
     // returns spot price as place holders, not the arithmetic twap value
     pub fn arithmetic_twap(
         &self,
@@ -172,6 +617,9 @@ impl Pool {
             (Some(a), Some(b)) => (a, b),
             _ => return Err(OsmosisError::AssetNotInPool),
         };
+        if bal_in.is_zero() {
+            return Err(OsmosisError::EmptyPool);
+        }
         let mult = Decimal::one();
         let price = Decimal::from_ratio(bal_out * mult, bal_in);
         Ok(price)
@@ -190,6 +638,9 @@ impl Pool {
             (Some(a), Some(b)) => (a, b),
             _ => return Err(OsmosisError::AssetNotInPool),
         };
+        if bal_in.is_zero() {
+            return Err(OsmosisError::EmptyPool);
+        }
         let mult = Decimal::one();
         let price = Decimal::from_ratio(bal_out * mult, bal_in);
         Ok(price)
@@ -212,14 +663,182 @@ impl Pool {
     }
 }
 
+/// A x/lockup lock, as returned by `OsmosisQuery::LockedById`
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema, Debug)]
+pub struct Lock {
+    pub owner: Addr,
+    /// Unbonding duration in seconds
+    pub duration: u64,
+    /// Unix time millisecond when unlocking completes, None if not yet unlocking
+    pub end_time: Option<i64>,
+    pub coins: Vec<Coin>,
+}
+
+impl Lock {
+    pub fn new(owner: impl Into<String>, duration: u64, coins: Vec<Coin>) -> Self {
+        Lock {
+            owner: Addr::unchecked(owner),
+            duration,
+            end_time: None,
+            coins,
+        }
+    }
+
+    pub fn into_response(self, lock_id: u64) -> LockedResponse {
+        LockedResponse {
+            lock_id,
+            owner: self.owner.into(),
+            duration: self.duration,
+            end_time: self.end_time,
+            coins: self.coins,
+        }
+    }
+}
+
+/// A concentrated liquidity position, as created by `OsmosisMsg::CreatePosition`
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema, Debug)]
+pub struct Position {
+    pub pool_id: u64,
+    pub owner: Addr,
+    pub lower_tick: i64,
+    pub upper_tick: i64,
+    /// Share of the pool's liquidity, see `PoolKind::Concentrated`
+    pub liquidity: Uint128,
+    /// Swap fees earned while in range and not yet collected
+    pub spread_rewards: Vec<Coin>,
+    /// Incentives not yet collected, see `OsmosisModule::add_incentives`
+    pub incentives: Vec<Coin>,
+}
+
+impl Position {
+    /// A position earns spread rewards while `lower_tick <= current_tick < upper_tick`
+    pub fn is_in_range(&self, current_tick: i64) -> bool {
+        self.lower_tick <= current_tick && current_tick < self.upper_tick
+    }
+
+    pub fn into_response(self, pool: &Pool) -> PositionResponse {
+        let coins = pool
+            .assets
+            .iter()
+            .map(|a| {
+                // an emptied position is kept while it has rewards, even once the pool is empty
+                let amount = if pool.shares.is_zero() {
+                    Uint128::zero()
+                } else {
+                    a.amount.multiply_ratio(self.liquidity, pool.shares)
+                };
+                coin(amount.u128(), &a.denom)
+            })
+            .collect();
+        PositionResponse {
+            pool_id: self.pool_id,
+            owner: self.owner.into(),
+            lower_tick: self.lower_tick,
+            upper_tick: self.upper_tick,
+            liquidity: self.liquidity.to_string(),
+            coins,
+        }
+    }
+}
+
+/// A x/incentives gauge, as created by `OsmosisMsg::CreateGauge`
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema, Debug)]
+pub struct Gauge {
+    pub owner: Addr,
+    pub is_perpetual: bool,
+    /// The locked denom that qualifies for rewards
+    pub denom: String,
+    /// All coins ever put into the gauge
+    pub coins: Vec<Coin>,
+    /// Unix time millisecond
+    pub start_time: i64,
+    pub num_epochs_paid_over: u64,
+    pub filled_epochs: u64,
+    pub distributed_coins: Vec<Coin>,
+}
+
+impl Gauge {
+    /// Adds `coins` to the gauge's total, merging denoms it already holds
+    pub fn add_coins(&mut self, coins: Vec<Coin>) -> Result<(), OsmosisError> {
+        for coin in coins {
+            match self.coins.iter_mut().find(|c| c.denom == coin.denom) {
+                Some(existing) => existing.amount = existing.amount.checked_add(coin.amount)?,
+                None => self.coins.push(coin),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn into_response(self, id: u64) -> GaugeResponse {
+        GaugeResponse {
+            id,
+            is_perpetual: self.is_perpetual,
+            denom: self.denom,
+            coins: self.coins,
+            start_time: self.start_time,
+            num_epochs_paid_over: self.num_epochs_paid_over,
+            filled_epochs: self.filled_epochs,
+            distributed_coins: self.distributed_coins,
+        }
+    }
+}
+
+/// Privileged messages of the mock, sent with `OsmosisApp::custom_sudo`
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum OsmosisSudo {
+    /// Simulates the x/epochs end of epoch hook: every active gauge pays out its share for this
+    /// epoch to the owners of locks holding its denom, pro rata to the amount locked.
+    /// `epoch_number` is only reported back in the event.
+    EpochEnd {
+        identifier: String,
+        epoch_number: i64,
+    },
+}
+
+/// The mock's only epoch, "day", which repeats every `duration` seconds from `start_time`
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema, Debug)]
+pub struct Epoch {
+    pub start_time: Timestamp,
+    pub duration: u64,
+}
+
+impl Epoch {
+    pub const IDENTIFIER: &'static str = "day";
+
+    pub fn info(&self, now: Timestamp) -> EpochInfoResponse {
+        let start_time = self.start_time.seconds();
+        // epochs are counted from 1, nothing is running before the start
+        let current_epoch = match now.seconds().checked_sub(start_time) {
+            Some(elapsed) => elapsed / self.duration + 1,
+            None => 0,
+        };
+        let current_epoch_start_time = start_time + current_epoch.saturating_sub(1) * self.duration;
+        EpochInfoResponse {
+            current_epoch: current_epoch as i64,
+            start_time: start_time as i64,
+            duration: self.duration,
+            current_epoch_start_time: current_epoch_start_time as i64,
+        }
+    }
+}
+
 pub struct OsmosisModule {}
 
-/// How many seconds per block
+/// How many seconds per block by default, see `OsmosisApp::with_block_time`
 /// (when we increment block.height, use this multiplier for block.time)
 pub const BLOCK_TIME: u64 = 5;
 
 impl OsmosisModule {
     fn build_denom(&self, creator: &Addr, subdenom: &str) -> Result<String, ContractError> {
+        if !subdenom
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '/')
+        {
+            return Err(ContractError::InvalidSubdenom {
+                subdenom: subdenom.to_string(),
+            });
+        }
         // Minimum validation checks on the full denom.
         // https://github.com/cosmos/cosmos-sdk/blob/2646b474c7beb0c93d4fafd395ef345f41afc251/types/coin.go#L706-L711
         // https://github.com/cosmos/cosmos-sdk/blob/2646b474c7beb0c93d4fafd395ef345f41afc251/types/coin.go#L677
@@ -239,844 +858,6510 @@ impl OsmosisModule {
     pub fn set_pool(&self, storage: &mut dyn Storage, pool_id: u64, pool: &Pool) -> StdResult<()> {
         POOLS.save(storage, pool_id, pool)
     }
-}
-
-fn complex_swap(
-    storage: &dyn Storage,
-    first: Swap,
-    route: Vec<Step>,
-    amount: SwapAmount,
-) -> AnyResult<(SwapAmount, Vec<(u64, Pool)>)> {
-    // all the `Swap`s we need to execute in order
-    let swaps: Vec<_> = {
-        let frst = iter::once(first.clone());
-        let rest = iter::once((first.pool_id, first.denom_out))
-            .chain(route.into_iter().map(|step| (step.pool_id, step.denom_out)))
-            .tuple_windows()
-            .map(|((_, denom_in), (pool_id, denom_out))| Swap {
-                pool_id,
-                denom_in,
-                denom_out,
-            });
-        frst.chain(rest).collect()
-    };
-
-    let mut updated_pools = vec![];
-
-    match amount {
-        SwapAmount::In(mut input) => {
-            for swap in &swaps {
-                let mut pool = POOLS.load(storage, swap.pool_id)?;
-                let payout = pool.swap(&swap.denom_in, &swap.denom_out, SwapAmount::In(input))?;
-                updated_pools.push((swap.pool_id, pool));
-
-                input = payout.as_out();
-            }
 
-            Ok((SwapAmount::Out(input), updated_pools))
-        }
-        SwapAmount::Out(mut output) => {
-            for swap in swaps.iter().rev() {
-                let mut pool = POOLS.load(storage, swap.pool_id)?;
-                let payout = pool.swap(&swap.denom_in, &swap.denom_out, SwapAmount::Out(output))?;
-                updated_pools.push((swap.pool_id, pool));
-
-                output = payout.as_in();
-            }
-
-            Ok((SwapAmount::In(output), updated_pools))
+    /// Changes the swap fee of an existing pool, e.g. to test behaviour across a fee change
+    pub fn set_pool_fee(
+        &self,
+        storage: &mut dyn Storage,
+        pool_id: u64,
+        fee: Decimal,
+    ) -> AnyResult<()> {
+        if fee >= Decimal::one() {
+            return Err(OsmosisError::InvalidSwapFee(fee).into());
         }
+        let mut pool = load_pool(storage, pool_id)?;
+        pool.fee = fee;
+        POOLS.save(storage, pool_id, &pool)?;
+        Ok(())
     }
-}
 
-impl Module for OsmosisModule {
-    type ExecT = OsmosisMsg;
-    type QueryT = OsmosisQuery;
-    type SudoT = Empty;
+    /// Used to mock out superfluid delegations of existing locks
+    pub fn set_superfluid_delegation(
+        &self,
+        storage: &mut dyn Storage,
+        lock_id: u64,
+        validator: impl Into<String>,
+    ) -> StdResult<()> {
+        SUPERFLUID.save(storage, lock_id, &validator.into())
+    }
 
-    // Builds a mock rust implementation of the expected osmosis functionality for testing
-    fn execute<ExecC, QueryC>(
+    /// Saves a new pool under the next free id, paid for by and with all shares going to `sender`
+    fn create_pool<ExecC, QueryC>(
         &self,
         api: &dyn Api,
         storage: &mut dyn Storage,
         router: &dyn CosmosRouter<ExecC = ExecC, QueryC = QueryC>,
         block: &BlockInfo,
         sender: Addr,
-        msg: OsmosisMsg,
+        pool: Pool,
     ) -> AnyResult<AppResponse>
     where
         ExecC: Debug + Clone + PartialEq + JsonSchema + DeserializeOwned + 'static,
         QueryC: CustomQuery + DeserializeOwned + 'static,
     {
-        match msg {
-            OsmosisMsg::CreateDenom { subdenom } => {
-                // TODO: Simulate denom creation, and add existence checks in MintTokens
-                let denom = self.build_denom(&sender, &subdenom)?;
-                let data = Some(to_binary(&FullDenomResponse { denom })?);
-                Ok(AppResponse {
-                    data,
-                    events: vec![],
-                })
-            }
-            OsmosisMsg::MintTokens {
-                denom,
-                amount,
-                mint_to_address,
-            } => {
-                // TODO: This currently incorrectly simulates the Osmosis functionality, as it does not
-                // check admin functionality on the denom / that the denom was actually created
-                let mint = BankSudo::Mint {
-                    to_address: mint_to_address,
-                    amount: coins(amount.u128(), &denom),
-                };
-                router.sudo(api, storage, block, mint.into())?;
+        let pool_id = next_id(storage, &POOLS)?;
+        POOLS.save(storage, pool_id, &pool)?;
 
-                let data = Some(to_binary(&FullDenomResponse { denom })?);
-                Ok(AppResponse {
-                    data,
-                    events: vec![],
-                })
-            }
-            OsmosisMsg::BurnTokens {
-                denom: _,
-                amount: _,
-                burn_from_address: _,
-            } => Ok(AppResponse {
-                data: None,
-                events: vec![],
-            }),
-            OsmosisMsg::ChangeAdmin {
-                denom: _denom,
-                new_admin_address: _new_admin_address,
-            } => Ok(AppResponse {
-                data: None,
-                events: vec![],
-            }),
-            OsmosisMsg::Swap {
-                first,
-                route,
-                amount,
-            } => {
-                let denom_in = first.denom_in.clone();
-                let denom_out = route
-                    .iter()
-                    .last()
-                    .map(|step| step.denom_out.clone())
-                    .unwrap_or_else(|| first.denom_out.clone());
+        // the initial liquidity is burnt like any other deposit
+        let burn = BankMsg::Burn {
+            amount: pool.assets.clone(),
+        };
+        router.execute(api, storage, block, sender.clone(), burn.into())?;
 
-                let (swap_result, updated_pools) =
-                    complex_swap(storage, first, route, amount.clone().discard_limit())?;
+        // and the creator gets all lp shares
+        let mint = BankSudo::Mint {
+            to_address: sender.to_string(),
+            amount: coins(pool.shares.u128(), pool.gamm_denom(pool_id)),
+        };
+        router.sudo(api, storage, block, mint.into())?;
 
-                match amount {
-                    SwapAmountWithLimit::ExactIn { min_output, .. } => {
-                        let swap_result_as_out = swap_result.as_out();
-                        if swap_result_as_out < min_output {
-                            return Err(OsmosisError::PriceTooLowExactIn(
-                                swap_result_as_out,
-                                min_output,
-                            )
-                            .into());
-                        }
-                    }
-                    SwapAmountWithLimit::ExactOut { max_input, .. } => {
-                        let swap_result_as_in = swap_result.as_in();
-                        if swap_result_as_in > max_input {
-                            return Err(OsmosisError::PriceTooLowExactOut(
-                                swap_result_as_in,
-                                max_input,
-                            )
-                            .into());
-                        }
-                    }
-                }
-
-                for (pool_id, pool) in updated_pools {
-                    POOLS.save(storage, pool_id, &pool)?;
-                }
+        let data = Some(to_binary(&CreatePoolResponse { pool_id })?);
+        Ok(AppResponse {
+            data,
+            events: vec![],
+        })
+    }
 
-                let (pay_in, get_out) = match amount {
-                    SwapAmountWithLimit::ExactIn { input, .. } => (input, swap_result.as_out()),
-                    SwapAmountWithLimit::ExactOut { output, .. } => (swap_result.as_in(), output),
-                };
+    /// Used to mock out locks for the x/lockup queries
+    pub fn set_lock(&self, storage: &mut dyn Storage, lock_id: u64, lock: &Lock) -> StdResult<()> {
+        LOCKS.save(storage, lock_id, lock)
+    }
 
-                // Note: to make testing easier, we just mint and burn - no balance for AMM
-                // burn pay_in tokens from sender
-                let burn = BankMsg::Burn {
-                    amount: coins(pay_in.u128(), &denom_in),
-                };
-                router.execute(api, storage, block, sender.clone(), burn.into())?;
+    /// Used to mock out the price observations TWAP queries average over. The prices are in the
+    /// orientation TWAP queries for `quote_asset_denom` and `base_asset_denom` return.
+    pub fn set_twap_history(
+        &self,
+        storage: &mut dyn Storage,
+        pool_id: u64,
+        quote_asset_denom: &str,
+        base_asset_denom: &str,
+        mut history: Vec<(i64, Decimal)>,
+    ) -> StdResult<()> {
+        history.sort_by_key(|(time, _)| *time);
+        TWAP_HISTORY.save(
+            storage,
+            (pool_id, quote_asset_denom, base_asset_denom),
+            &history,
+        )
+    }
 
-                // mint get_out tokens to sender
-                let mint = BankSudo::Mint {
-                    to_address: sender.to_string(),
-                    amount: coins(get_out.u128(), denom_out),
-                };
-                router.sudo(api, storage, block, mint.into())?;
+    /// Stands in for the chain's incentive distribution, crediting `coins` to a concentrated
+    /// liquidity position for `OsmosisMsg::CollectIncentives` to pay out
+    pub fn add_incentives(
+        &self,
+        storage: &mut dyn Storage,
+        position_id: u64,
+        coins: &[Coin],
+    ) -> AnyResult<()> {
+        let mut position = POSITIONS
+            .may_load(storage, position_id)?
+            .ok_or(OsmosisError::PositionNotFound(position_id))?;
+        let incentives = position.incentives.drain(..);
+        position.incentives = merge_coins(incentives.chain(coins.iter().cloned()))?;
+        POSITIONS.save(storage, position_id, &position)?;
+        Ok(())
+    }
 
-                let output = match amount {
-                    SwapAmountWithLimit::ExactIn { .. } => SwapAmount::Out(get_out),
-                    SwapAmountWithLimit::ExactOut { .. } => SwapAmount::In(pay_in),
-                };
-                let data = Some(to_binary(&SwapResponse { amount: output })?);
-                Ok(AppResponse {
-                    data,
-                    events: vec![],
-                })
-            }
-        }
+    /// Used to configure the "day" epoch for `OsmosisQuery::EpochInfo`
+    pub fn set_epoch(&self, storage: &mut dyn Storage, epoch: &Epoch) -> StdResult<()> {
+        EPOCH.save(storage, epoch)
     }
 
-    fn sudo<ExecC, QueryC>(
+    /// Used to mock out the x/mint emission rate for `OsmosisQuery::EpochProvisions`
+    pub fn set_epoch_provisions(
         &self,
-        _api: &dyn Api,
-        _storage: &mut dyn Storage,
-        _router: &dyn CosmosRouter<ExecC = ExecC, QueryC = QueryC>,
-        _block: &BlockInfo,
-        _msg: Self::SudoT,
-    ) -> AnyResult<AppResponse>
-    where
-        ExecC: Debug + Clone + PartialEq + JsonSchema + DeserializeOwned + 'static,
-        QueryC: CustomQuery + DeserializeOwned + 'static,
-    {
-        bail!("sudo not implemented for OsmosisModule")
+        storage: &mut dyn Storage,
+        epoch_provisions: Decimal,
+    ) -> StdResult<()> {
+        EPOCH_PROVISIONS.save(storage, &epoch_provisions)
     }
+}
 
-    fn query(
+/// The stableswap curve for one pair of a pool, see `Pool::stableswap_curve`
+/// Reserves are divided by their scaling factor (rounding down) before applying the curve,
+/// so amounts below the factor do not move the curve, like on chain.
+struct StableswapCurve {
+    in_factor: Uint128,
+    out_factor: Uint128,
+    w: Uint256,
+}
+
+impl StableswapCurve {
+    fn scale_in(&self, amount: Uint128) -> Result<Uint256, OsmosisError> {
+        Ok(Uint256::from(amount.checked_div(self.in_factor)?))
+    }
+
+    fn scale_out(&self, amount: Uint128) -> Result<Uint256, OsmosisError> {
+        Ok(Uint256::from(amount.checked_div(self.out_factor)?))
+    }
+
+    /// x * y * (x^2 + y^2 + w) for the given (unscaled) reserves
+    fn invariant(&self, bal_in: Uint128, bal_out: Uint128) -> Result<Uint256, OsmosisError> {
+        let x = self.scale_in(bal_in)?;
+        let y = self.scale_out(bal_out)?;
+        let squares = x
+            .checked_mul(x)?
+            .checked_add(y.checked_mul(y)?)?
+            .checked_add(self.w)?;
+        Ok(x.checked_mul(y)?.checked_mul(squares)?)
+    }
+
+    /// The smallest out reserve that keeps the invariant at `k` once the in reserve is `bal_in`.
+    /// Rounding up leaves any dust in the pool.
+    fn solve_out(
         &self,
-        api: &dyn Api,
-        storage: &dyn Storage,
-        _querier: &dyn Querier,
-        _block: &BlockInfo,
-        request: OsmosisQuery,
-    ) -> anyhow::Result<Binary> {
-        match request {
-            OsmosisQuery::FullDenom {
-                creator_addr,
-                subdenom,
-            } => {
-                let contract = api.addr_validate(&creator_addr)?;
-                let denom = self.build_denom(&contract, &subdenom)?;
-                let res = FullDenomResponse { denom };
-                Ok(to_binary(&res)?)
-            }
-            OsmosisQuery::PoolState { id } => {
-                let pool = POOLS.load(storage, id)?;
-                let res = pool.into_response(id);
-                Ok(to_binary(&res)?)
-            }
-            OsmosisQuery::SpotPrice {
-                swap,
-                with_swap_fee,
-            } => {
-                let pool = POOLS.load(storage, swap.pool_id)?;
-                let price = pool.spot_price(&swap.denom_in, &swap.denom_out, with_swap_fee)?;
-                Ok(to_binary(&SpotPriceResponse { price })?)
+        k: Uint256,
+        bal_in: Uint128,
+        max: Uint128,
+    ) -> Result<Uint128, OsmosisError> {
+        let (mut lo, mut hi) = (Uint128::zero(), max);
+        while lo < hi {
+            let mid = lo + (hi - lo) / Uint128::new(2);
+            if self.invariant(bal_in, mid)? >= k {
+                hi = mid;
+            } else {
+                lo = mid + Uint128::new(1);
             }
-            OsmosisQuery::EstimateSwap {
-                sender: _sender,
-                first,
-                route,
-                amount,
-            } => {
-                let (amount, _) = complex_swap(storage, first, route, amount)?;
+        }
+        Ok(lo)
+    }
 
-                Ok(to_binary(&SwapResponse { amount })?)
-            }
-            // ArithmeticTwap returns spot price for the multitest.
-            #[allow(unused_variables)]
-            OsmosisQuery::ArithmeticTwap {
-                id,
-                quote_asset_denom,
-                base_asset_denom,
-                start_time,
-                end_time,
-            } => {
-                let pool = POOLS.load(storage, id)?;
-                let twap = pool.arithmetic_twap(&quote_asset_denom, &base_asset_denom)?;
-                Ok(to_binary(&ArithmeticTwapResponse { twap })?)
-            }
-            // ArithmeticTwapToNow returns spot price for the multitest.
-            #[allow(unused_variables)]
-            OsmosisQuery::ArithmeticTwapToNow {
-                id,
-                quote_asset_denom,
-                base_asset_denom,
-                start_time,
-            } => {
-                let pool = POOLS.load(storage, id)?;
-                let twap = pool.arithmetic_twap_to_now(&quote_asset_denom, &base_asset_denom)?;
-                Ok(to_binary(&ArithmeticTwapToNowResponse { twap })?)
+    /// The smallest in reserve that keeps the invariant at `k` once the out reserve is `bal_out`
+    fn solve_in(
+        &self,
+        k: Uint256,
+        bal_out: Uint128,
+        min: Uint128,
+    ) -> Result<Uint128, OsmosisError> {
+        let mut lo = min;
+        let mut hi = max(min, Uint128::new(1));
+        while self.invariant(hi, bal_out)? < k {
+            lo = hi;
+            hi = hi.checked_mul(Uint128::new(2))?;
+        }
+        while lo < hi {
+            let mid = lo + (hi - lo) / Uint128::new(2);
+            if self.invariant(mid, bal_out)? >= k {
+                hi = mid;
+            } else {
+                lo = mid + Uint128::new(1);
             }
         }
+        Ok(lo)
     }
 }
 
-#[derive(Error, Debug, PartialEq)]
-pub enum OsmosisError {
-    #[error("{0}")]
-    Std(#[from] StdError),
+/// Errors unless `sender` is the admin of the factory denom
+fn ensure_denom_admin(
+    storage: &dyn Storage,
+    denom: &str,
+    sender: &Addr,
+) -> Result<(), OsmosisError> {
+    let admin = DENOM_ADMINS
+        .may_load(storage, denom)?
+        .ok_or_else(|| OsmosisError::DenomDoesNotExist(denom.to_string()))?;
+    if admin != sender.as_str() {
+        return Err(OsmosisError::Unauthorized);
+    }
+    Ok(())
+}
 
-    #[error("{0}")]
-    Overflow(#[from] cosmwasm_std::OverflowError),
+/// Errors if minting `amount` more of `denom` would take it above its max supply, if it has one
+fn ensure_within_max_supply(
+    storage: &dyn Storage,
+    denom: &str,
+    amount: Uint128,
+) -> Result<(), OsmosisError> {
+    if let Some(max_supply) = DENOM_MAX_SUPPLIES.may_load(storage, denom)? {
+        let supply = bank_supply(storage, denom)?.checked_add(amount)?;
+        if supply > max_supply {
+            return Err(OsmosisError::MaxSupplyExceeded {
+                denom: denom.to_string(),
+                supply,
+                max_supply,
+            });
+        }
+    }
+    Ok(())
+}
 
-    #[error("Asset not in pool")]
-    AssetNotInPool,
+fn load_pool(storage: &dyn Storage, id: u64) -> Result<Pool, OsmosisError> {
+    POOLS
+        .may_load(storage, id)?
+        .ok_or(OsmosisError::PoolNotFound { id })
+}
 
-    #[error("Aborting swap - payout: {0} is smaller then minimal output: {1}")]
-    PriceTooLowExactIn(Uint128, Uint128),
+/// Pool and gauge ids are assigned sequentially, starting from 1
+fn next_id<T>(storage: &dyn Storage, map: &Map<u64, T>) -> StdResult<u64>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let last = map
+        .keys(storage, None, None, Order::Descending)
+        .next()
+        .transpose()?;
+    Ok(last.map_or(1, |id| id + 1))
+}
 
-    #[error("Aborting swap - payin: {0} is bigger then maximum input: {1}")]
-    PriceTooLowExactOut(Uint128, Uint128),
+/// Splits the window `[start_time, end_time)` into the prices in effect and how long each lasted.
+/// Each observation holds until the next one, times before the first observation use its price.
+fn twap_segments(
+    history: &[(i64, Decimal)],
+    start_time: i64,
+    end_time: i64,
+) -> Result<Vec<(Decimal, u64)>, OsmosisError> {
+    if start_time >= end_time {
+        return Err(OsmosisError::InvalidTwapWindow {
+            start_time,
+            end_time,
+        });
+    }
+    let mut segments = vec![];
+    for (i, (time, price)) in history.iter().enumerate() {
+        let from = if i == 0 {
+            start_time
+        } else {
+            max(*time, start_time)
+        };
+        let to = match history.get(i + 1) {
+            Some((next, _)) => (*next).min(end_time),
+            None => end_time,
+        };
+        if to > from {
+            segments.push((*price, (to - from) as u64));
+        }
+    }
+    if segments.is_empty() {
+        return Err(OsmosisError::EmptyTwapWindow);
+    }
+    Ok(segments)
+}
 
-    /// Remove this to let the compiler find all TODOs
-    #[error("Not yet implemented (TODO)")]
-    Unimplemented,
+/// The mocked price history between two assets of `pool`, see `TWAP_HISTORY`.
+/// A series seeded for the reversed pair is inverted.
+fn twap_history(
+    storage: &dyn Storage,
+    pool: &Pool,
+    pool_id: u64,
+    quote_asset_denom: &str,
+    base_asset_denom: &str,
+) -> Result<Option<PriceHistory>, OsmosisError> {
+    if !pool.has_denom(quote_asset_denom) || !pool.has_denom(base_asset_denom) {
+        return Err(OsmosisError::AssetNotInPool);
+    }
+    let key = (pool_id, quote_asset_denom, base_asset_denom);
+    if let Some(history) = TWAP_HISTORY.may_load(storage, key)? {
+        return Ok(Some(history));
+    }
+    let reversed = (pool_id, base_asset_denom, quote_asset_denom);
+    match TWAP_HISTORY.may_load(storage, reversed)? {
+        Some(history) => {
+            let inverted = history
+                .into_iter()
+                .map(|(time, price)| Ok((time, price.inv().ok_or(OsmosisError::EmptyPool)?)))
+                .collect::<Result<_, OsmosisError>>()?;
+            Ok(Some(inverted))
+        }
+        None => Ok(None),
+    }
 }
 
-pub type OsmosisAppWrapped =
-    App<BankKeeper, MockApi, MockStorage, OsmosisModule, WasmKeeper<OsmosisMsg, OsmosisQuery>>;
+/// The time-weighted arithmetic mean of the prices
+fn arithmetic_mean(segments: &[(Decimal, u64)]) -> Result<Decimal, OsmosisError> {
+    let total: u64 = segments.iter().map(|(_, duration)| duration).sum();
+    let mut sum = Decimal::zero();
+    for (price, duration) in segments {
+        sum = sum.checked_add(*price * Decimal::from_ratio(*duration, total))?;
+    }
+    Ok(sum)
+}
 
-pub struct OsmosisApp(OsmosisAppWrapped);
+/// The time-weighted geometric mean of the prices, `exp(sum(w * ln(p)))`.
+/// As `pow_approx` needs bases up to 1, this computes the same as `max * prod((p / max) ^ w)`,
+/// which also keeps a constant price exact.
+fn geometric_mean(segments: &[(Decimal, u64)]) -> Result<Decimal, OsmosisError> {
+    let total: u64 = segments.iter().map(|(_, duration)| duration).sum();
+    let max_price = match segments.iter().map(|(price, _)| *price).max() {
+        Some(price) if !price.is_zero() => price,
+        _ => return Ok(Decimal::zero()),
+    };
+    let mut product = Decimal::one();
+    for (price, duration) in segments {
+        let base = Decimal::checked_from_ratio(price.atomics(), max_price.atomics())?;
+        if base.is_zero() {
+            return Ok(Decimal::zero());
+        }
+        product *= pow_approx(base, Decimal::from_ratio(*duration, total))?;
+    }
+    Ok(max_price * product)
+}
 
-impl Deref for OsmosisApp {
-    type Target = OsmosisAppWrapped;
+/// Computes `base ^ exp` for `base` in (0, 1], approximating like x/gamm does for balancer pools:
+/// the integer part of `exp` is applied exactly and the fractional part through the binomial
+/// series of `(1 - x) ^ frac` with `x = 1 - base`.
+/// This converges quickly for the usual swap sizes, but slowly as `base` approaches zero, which is
+/// why we stop after a fixed number of terms.
+fn pow_approx(base: Decimal, exp: Decimal) -> Result<Decimal, OsmosisError> {
+    const PRECISION: Decimal = Decimal::raw(100_000_000); // 10^-10
+    const MAX_TERMS: u64 = 10_000;
 
-    fn deref(&self) -> &Self::Target {
-        &self.0
+    let whole = exp.floor();
+    let frac = exp - whole;
+    let whole = (whole.atomics() / Decimal::one().atomics()).u128();
+    let whole_pow = base.checked_pow(u32::try_from(whole).unwrap_or(u32::MAX))?;
+    if frac.is_zero() {
+        return Ok(whole_pow);
     }
-}
 
-impl DerefMut for OsmosisApp {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+    // sum over k of binom(frac, k) * (-x) ^ k, tracking the sign of each term separately
+    let x = Decimal::one() - base;
+    let mut sum = Decimal::one();
+    let mut term = Decimal::one();
+    let mut negative = false;
+    for k in 1..=MAX_TERMS {
+        let k_minus_one = Decimal::from_ratio(k - 1, 1u128);
+        // binom(frac, k) = binom(frac, k - 1) * (frac - (k - 1)) / k
+        let factor = if frac > k_minus_one {
+            frac - k_minus_one
+        } else {
+            negative = !negative;
+            k_minus_one - frac
+        };
+        // the (-x) part flips the sign every term
+        negative = !negative;
+        term = term * factor * x / Uint128::from(k);
+        if term < PRECISION {
+            break;
+        }
+        sum = if negative {
+            sum.checked_sub(term)?
+        } else {
+            sum.checked_add(term)?
+        };
     }
+    Ok(whole_pow * sum)
 }
 
-impl Querier for OsmosisApp {
-    fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
-        self.0.raw_query(bin_request)
+/// Computes `value * numerator / denominator`, rounding up
+fn ceil_ratio(
+    value: Uint128,
+    numerator: Uint128,
+    denominator: Uint128,
+) -> Result<Uint128, OsmosisError> {
+    let product = value.full_mul(numerator);
+    let denominator = Uint256::from(denominator);
+    let mut result = product.checked_div(denominator)?;
+    if !product.checked_rem(denominator)?.is_zero() {
+        result += Uint256::one();
     }
+    Ok(Uint128::try_from(result)?)
 }
 
-impl Default for OsmosisApp {
-    fn default() -> Self {
-        Self::new()
+/// Sums up coins by denom, sorted by denom and without zero amounts
+fn merge_coins(coins: impl IntoIterator<Item = Coin>) -> Result<Vec<Coin>, OsmosisError> {
+    let mut totals: BTreeMap<String, Uint128> = BTreeMap::new();
+    for coin in coins {
+        let total = totals.entry(coin.denom).or_default();
+        *total = total.checked_add(coin.amount)?;
     }
+    Ok(totals
+        .into_iter()
+        .filter(|(_, amount)| !amount.is_zero())
+        .map(|(denom, amount)| Coin { denom, amount })
+        .collect())
 }
 
-impl OsmosisApp {
-    pub fn new() -> Self {
-        Self(
-            BasicAppBuilder::<OsmosisMsg, OsmosisQuery>::new_custom()
-                .with_custom(OsmosisModule {})
-                .build(|_router, _, _storage| {
-                    // router.custom.set_owner(storage, &owner).unwrap();
-                }),
-        )
-    }
+/// Storage prefix of the balances map of cw-multi-test's `BankKeeper`: its "bank" namespace
+/// followed by the "balances" map namespace, both length prefixed.
+/// This is private to cw-multi-test, which is why Cargo.toml pins its exact version;
+/// `bank_balances_layout` fails if an upgrade moves the balances elsewhere.
+const BANK_BALANCES_PREFIX: &[u8] = b"\x00\x04bank\x00\x08balances";
 
-    pub fn block_info(&self) -> BlockInfo {
-        self.0.block_info()
+/// Sums the balance of `denom` over all accounts.
+/// cw-multi-test 0.15 cannot answer `BankQuery::Supply`, so this reads the bank's storage directly.
+/// Everything minted and not yet burnt is held by some account, so this is the total supply.
+fn bank_supply(storage: &dyn Storage, denom: &str) -> Result<Uint128, OsmosisError> {
+    let mut supply = Uint128::zero();
+    for (key, value) in storage.range(Some(BANK_BALANCES_PREFIX), None, Order::Ascending) {
+        if !key.starts_with(BANK_BALANCES_PREFIX) {
+            break;
+        }
+        let balance: Vec<Coin> = from_slice(&value)?;
+        if let Some(coin) = balance.iter().find(|c| c.denom == denom) {
+            supply = supply.checked_add(coin.amount)?;
+        }
     }
+    Ok(supply)
+}
 
-    /// This advances BlockInfo by given number of blocks.
-    /// It does not do any callbacks, but keeps the ratio of seconds/block
-    pub fn advance_blocks(&mut self, blocks: u64) {
-        self.update_block(|block| {
-            block.time = block.time.plus_seconds(BLOCK_TIME * blocks);
-            block.height += blocks;
+/// All the `Swap`s we need to execute in order
+fn route_hops(first: Swap, route: Vec<Step>) -> Vec<Swap> {
+    let frst = iter::once(first.clone());
+    let rest = iter::once((first.pool_id, first.denom_out))
+        .chain(route.into_iter().map(|step| (step.pool_id, step.denom_out)))
+        .tuple_windows()
+        .map(|((_, denom_in), (pool_id, denom_out))| Swap {
+            pool_id,
+            denom_in,
+            denom_out,
         });
+    frst.chain(rest).collect()
+}
+
+/// Moves the spread factor's cut of `token_in` out of a concentrated pool's reserves and onto its
+/// in-range positions, pro rata to their liquidity. Whatever does not split evenly, or all of it
+/// if no position is in range, stays in the pool.
+fn credit_spread_rewards(
+    storage: &mut dyn Storage,
+    pool_id: u64,
+    current_tick: i64,
+    pool: &mut Pool,
+    token_in: &Coin,
+) -> Result<(), OsmosisError> {
+    // the same rounding as `Pool::swap`
+    let fee = token_in.amount - token_in.amount * (Decimal::one() - pool.fee);
+    let in_range = POSITIONS
+        .range(storage, None, None, Order::Ascending)
+        .filter(|item| match item {
+            Ok((_, position)) => position.pool_id == pool_id && position.is_in_range(current_tick),
+            Err(_) => true,
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    let total_liquidity: Uint128 = in_range.iter().map(|(_, p)| p.liquidity).sum();
+    if fee.is_zero() || total_liquidity.is_zero() {
+        return Ok(());
     }
 
-    /// This advances BlockInfo by given number of seconds.
-    /// It does not do any callbacks, but keeps the ratio of seconds/block
-    pub fn advance_seconds(&mut self, seconds: u64) {
-        self.update_block(|block| {
-            block.time = block.time.plus_seconds(seconds);
-            block.height += max(1, seconds / BLOCK_TIME);
-        });
+    let mut credited = Uint128::zero();
+    for (position_id, mut position) in in_range {
+        let reward = fee.multiply_ratio(position.liquidity, total_liquidity);
+        credited += reward;
+        let rewards = position.spread_rewards.drain(..);
+        position.spread_rewards =
+            merge_coins(rewards.chain(iter::once(coin(reward.u128(), &token_in.denom))))?;
+        POSITIONS.save(storage, position_id, &position)?;
     }
+    let reserve = pool.get_amount(&token_in.denom).unwrap_or_default();
+    pool.set_amount(&token_in.denom, reserve.checked_sub(credited)?)
+}
 
-    /// Simple iterator when you don't care too much about the details and just want to
-    /// simulate forward motion.
-    pub fn next_block(&mut self) {
-        self.advance_blocks(1)
+/// Empties the rewards `field` picks out of every position in `position_ids`, returning them merged.
+/// All the positions must belong to `owner`.
+fn collect_rewards(
+    storage: &mut dyn Storage,
+    owner: &Addr,
+    position_ids: &[u64],
+    field: impl Fn(&mut Position) -> &mut Vec<Coin>,
+) -> Result<Vec<Coin>, OsmosisError> {
+    let mut collected = vec![];
+    for &position_id in position_ids {
+        let mut position = POSITIONS
+            .may_load(storage, position_id)?
+            .ok_or(OsmosisError::PositionNotFound(position_id))?;
+        if &position.owner != owner {
+            return Err(OsmosisError::Unauthorized);
+        }
+        collected.append(field(&mut position));
+        POSITIONS.save(storage, position_id, &position)?;
     }
+    merge_coins(collected)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use cosmwasm_std::testing::MOCK_CONTRACT_ADDR;
-    use cosmwasm_std::{coin, from_slice, Uint128};
-    use cw_multi_test::Executor;
-    use osmo_bindings::{Step, Swap};
+/// A pool touched by a swap, with its state afterwards and the amount that went into it
+type SwapHop = (u64, Pool, Coin);
 
-    #[test]
-    fn mint_token() {
-        let contract = Addr::unchecked("govner");
-        let rcpt = Addr::unchecked("townies");
-        let subdenom = "fundz";
+fn complex_swap(
+    storage: &dyn Storage,
+    first: Swap,
+    route: Vec<Step>,
+    amount: SwapAmount,
+) -> AnyResult<(SwapAmount, Vec<SwapHop>)> {
+    let swaps = route_hops(first, route);
+
+    // Estimates may follow a route that ends on the denom it started with (e.g. an arbitrage
+    // A -> B -> A), only executing rejects those. But every single hop must trade two different
+    // assets
+    if swaps
+        .iter()
+        .any(|swap| swap.denom_in.is_empty() || swap.denom_out.is_empty())
+    {
+        return Err(OsmosisError::EmptyDenom.into());
+    }
+    if let Some(swap) = swaps.iter().find(|swap| swap.denom_in == swap.denom_out) {
+        return Err(OsmosisError::SameDenomSwap(swap.denom_in.clone()).into());
+    }
+
+    // Validate the whole route before swapping anything: every hop's pool must hold both the
+    // denom coming in from the previous hop and the one going out. Errors name the offending
+    // denom and pool, so contracts get a stable message through the querier.
+    for swap in &swaps {
+        let pool = load_pool(storage, swap.pool_id)?;
+        for denom in [&swap.denom_in, &swap.denom_out] {
+            if pool.get_amount(denom).is_none() {
+                return Err(OsmosisError::DenomNotInPool {
+                    denom: denom.clone(),
+                    pool_id: swap.pool_id,
+                }
+                .into());
+            }
+        }
+    }
+
+    let mut updated_pools = vec![];
+
+    match amount {
+        SwapAmount::In(mut input) => {
+            for swap in &swaps {
+                let mut pool = load_pool(storage, swap.pool_id)?;
+                let payout = pool.swap(&swap.denom_in, &swap.denom_out, SwapAmount::In(input))?;
+                updated_pools.push((swap.pool_id, pool, coin(input.u128(), &swap.denom_in)));
+
+                input = payout.as_out();
+            }
+
+            Ok((SwapAmount::Out(input), updated_pools))
+        }
+        SwapAmount::Out(mut output) => {
+            for swap in swaps.iter().rev() {
+                let mut pool = load_pool(storage, swap.pool_id)?;
+                // the pool can never pay out all of its reserve
+                if output >= pool.get_amount(&swap.denom_out).unwrap_or_default() {
+                    return Err(OsmosisError::InsufficientPoolLiquidity {
+                        pool_id: swap.pool_id,
+                    }
+                    .into());
+                }
+                let payout = pool.swap(&swap.denom_in, &swap.denom_out, SwapAmount::Out(output))?;
+                output = payout.as_in();
+                updated_pools.push((swap.pool_id, pool, coin(output.u128(), &swap.denom_in)));
+            }
+
+            Ok((SwapAmount::In(output), updated_pools))
+        }
+    }
+}
+
+/// Saves the pools a swap went through, crediting spread rewards on concentrated ones
+fn save_swap_hops(storage: &mut dyn Storage, hops: Vec<SwapHop>) -> Result<(), OsmosisError> {
+    for (pool_id, mut pool, token_in) in hops {
+        if let PoolKind::Concentrated { current_tick } = pool.kind {
+            credit_spread_rewards(storage, pool_id, current_tick, &mut pool, &token_in)?;
+        }
+        POOLS.save(storage, pool_id, &pool)?;
+    }
+    Ok(())
+}
+
+impl Module for OsmosisModule {
+    type ExecT = OsmosisMsg;
+    type QueryT = OsmosisQuery;
+    type SudoT = OsmosisSudo;
+
+    // Builds a mock rust implementation of the expected osmosis functionality for testing
+    fn execute<ExecC, QueryC>(
+        &self,
+        api: &dyn Api,
+        storage: &mut dyn Storage,
+        router: &dyn CosmosRouter<ExecC = ExecC, QueryC = QueryC>,
+        block: &BlockInfo,
+        sender: Addr,
+        msg: OsmosisMsg,
+    ) -> AnyResult<AppResponse>
+    where
+        ExecC: Debug + Clone + PartialEq + JsonSchema + DeserializeOwned + 'static,
+        QueryC: CustomQuery + DeserializeOwned + 'static,
+    {
+        match msg {
+            OsmosisMsg::CreateDenom { subdenom } => {
+                let denom = self.build_denom(&sender, &subdenom)?;
+                if DENOM_ADMINS.has(storage, &denom) {
+                    return Err(OsmosisError::DenomAlreadyExists(denom).into());
+                }
+                DENOM_ADMINS.save(storage, &denom, &sender.to_string())?;
+                let data = Some(to_binary(&FullDenomResponse { denom })?);
+                Ok(AppResponse {
+                    data,
+                    events: vec![],
+                })
+            }
+            OsmosisMsg::MintTokens {
+                denom,
+                amount,
+                mint_to_address,
+            } => {
+                if amount.is_zero() {
+                    return Err(OsmosisError::ZeroAmount.into());
+                }
+                ensure_denom_admin(storage, &denom, &sender)?;
+                ensure_within_max_supply(storage, &denom, amount)?;
+                let mint = BankSudo::Mint {
+                    to_address: mint_to_address,
+                    amount: coins(amount.u128(), &denom),
+                };
+                router.sudo(api, storage, block, mint.into())?;
+
+                let data = Some(to_binary(&FullDenomResponse { denom })?);
+                Ok(AppResponse {
+                    data,
+                    events: vec![],
+                })
+            }
+            OsmosisMsg::MintTokensBatch { denom, mints } => {
+                ensure_denom_admin(storage, &denom, &sender)?;
+                if mints.is_empty() {
+                    return Err(OsmosisError::EmptyMintBatch.into());
+                }
+                // check every recipient before the batch as a whole is held against the cap
+                let mut total = Uint128::zero();
+                let mut recipients = Vec::with_capacity(mints.len());
+                for (mint_to_address, amount) in mints {
+                    if amount.is_zero() {
+                        return Err(OsmosisError::ZeroAmount.into());
+                    }
+                    total = total.checked_add(amount)?;
+                    recipients.push((api.addr_validate(&mint_to_address)?, amount));
+                }
+                ensure_within_max_supply(storage, &denom, total)?;
+                for (mint_to_address, amount) in recipients {
+                    let mint = BankSudo::Mint {
+                        to_address: mint_to_address.into(),
+                        amount: coins(amount.u128(), &denom),
+                    };
+                    router.sudo(api, storage, block, mint.into())?;
+                }
+
+                let data = Some(to_binary(&FullDenomResponse { denom })?);
+                Ok(AppResponse {
+                    data,
+                    events: vec![],
+                })
+            }
+            OsmosisMsg::SetDenomMaxSupply { denom, max_supply } => {
+                ensure_denom_admin(storage, &denom, &sender)?;
+                DENOM_MAX_SUPPLIES.save(storage, &denom, &max_supply)?;
+                Ok(AppResponse {
+                    data: None,
+                    events: vec![],
+                })
+            }
+            OsmosisMsg::BurnTokens {
+                denom: _,
+                amount: _,
+                burn_from_address: _,
+            } => Ok(AppResponse {
+                data: None,
+                events: vec![],
+            }),
+            OsmosisMsg::ChangeAdmin {
+                denom,
+                new_admin_address,
+            } => {
+                ensure_denom_admin(storage, &denom, &sender)?;
+                // an empty address leaves the denom without admin
+                let new_admin = if new_admin_address.is_empty() {
+                    new_admin_address
+                } else {
+                    api.addr_validate(&new_admin_address)?.into()
+                };
+                DENOM_ADMINS.save(storage, &denom, &new_admin)?;
+                Ok(AppResponse {
+                    data: None,
+                    events: vec![],
+                })
+            }
+            OsmosisMsg::SetBeforeSendHook {
+                denom,
+                cosmwasm_address,
+            } => {
+                ensure_denom_admin(storage, &denom, &sender)?;
+                if cosmwasm_address.is_empty() {
+                    BEFORE_SEND_HOOKS.remove(storage, &denom);
+                } else {
+                    let hook = api.addr_validate(&cosmwasm_address)?;
+                    BEFORE_SEND_HOOKS.save(storage, &denom, &hook)?;
+                }
+                Ok(AppResponse {
+                    data: None,
+                    events: vec![],
+                })
+            }
+            OsmosisMsg::ForceTransfer {
+                denom,
+                amount,
+                from_address,
+                to_address,
+            } => {
+                ensure_denom_admin(storage, &denom, &sender)?;
+                let from = api.addr_validate(&from_address)?;
+                let to = api.addr_validate(&to_address)?;
+                // send on behalf of the holder, the bank fails on insufficient funds
+                let send = BankMsg::Send {
+                    to_address: to.into(),
+                    amount: coins(amount.u128(), denom),
+                };
+                router.execute(api, storage, block, from, send.into())?;
+                Ok(AppResponse {
+                    data: None,
+                    events: vec![],
+                })
+            }
+            OsmosisMsg::Swap {
+                first,
+                route,
+                amount,
+            } => {
+                let denom_in = first.denom_in.clone();
+                let denom_out = route
+                    .iter()
+                    .last()
+                    .map(|step| step.denom_out.clone())
+                    .unwrap_or_else(|| first.denom_out.clone());
+                // a single hop swapping a denom for itself is reported by complex_swap
+                if !route.is_empty() && denom_in == denom_out {
+                    return Err(OsmosisError::InvalidRoute(denom_in).into());
+                }
+
+                let (swap_result, updated_pools) =
+                    complex_swap(storage, first, route, amount.clone().discard_limit())?;
+
+                match amount {
+                    SwapAmountWithLimit::ExactIn { min_output, .. } => {
+                        let swap_result_as_out = swap_result.as_out();
+                        if swap_result_as_out < min_output {
+                            return Err(OsmosisError::MinOutputNotMet {
+                                got: swap_result_as_out,
+                                min: min_output,
+                            }
+                            .into());
+                        }
+                    }
+                    SwapAmountWithLimit::ExactOut { max_input, .. } => {
+                        let swap_result_as_in = swap_result.as_in();
+                        if swap_result_as_in > max_input {
+                            return Err(OsmosisError::MaxInputExceeded {
+                                got: swap_result_as_in,
+                                max: max_input,
+                            }
+                            .into());
+                        }
+                    }
+                }
+
+                save_swap_hops(storage, updated_pools)?;
+
+                let (pay_in, get_out) = match amount {
+                    SwapAmountWithLimit::ExactIn { input, .. } => (input, swap_result.as_out()),
+                    SwapAmountWithLimit::ExactOut { output, .. } => (swap_result.as_in(), output),
+                };
+
+                // Note: to make testing easier, we just mint and burn - no balance for AMM
+                // burn pay_in tokens from sender
+                let burn = BankMsg::Burn {
+                    amount: coins(pay_in.u128(), &denom_in),
+                };
+                router.execute(api, storage, block, sender.clone(), burn.into())?;
+
+                // mint get_out tokens to sender
+                let mint = BankSudo::Mint {
+                    to_address: sender.to_string(),
+                    amount: coins(get_out.u128(), denom_out),
+                };
+                router.sudo(api, storage, block, mint.into())?;
+
+                let output = match amount {
+                    SwapAmountWithLimit::ExactIn { .. } => SwapAmount::Out(get_out),
+                    SwapAmountWithLimit::ExactOut { .. } => SwapAmount::In(pay_in),
+                };
+                let data = Some(to_binary(&SwapResponse { amount: output })?);
+                Ok(AppResponse {
+                    data,
+                    events: vec![],
+                })
+            }
+            OsmosisMsg::SwapWithDeadline {
+                first,
+                route,
+                amount,
+                deadline,
+            } => {
+                let now = (block.time.nanos() / 1_000_000) as i64;
+                if let Some(deadline) = deadline {
+                    if now > deadline {
+                        return Err(OsmosisError::Expired { deadline, now }.into());
+                    }
+                }
+                let swap = OsmosisMsg::Swap {
+                    first,
+                    route,
+                    amount,
+                };
+                self.execute(api, storage, router, block, sender, swap)
+            }
+            OsmosisMsg::SplitRouteSwapExactAmountIn {
+                routes,
+                token_in_denom,
+                token_out_min_amount,
+            } => {
+                let mut denom_out = None;
+                let mut total_in = Uint128::zero();
+                let mut total_out = Uint128::zero();
+                // Routes run one after another, so a later one sees the pools moved by the
+                // earlier ones
+                for SwapRoute {
+                    pools,
+                    token_in_amount,
+                } in routes
+                {
+                    let (first, rest) = pools.split_first().ok_or(OsmosisError::EmptyRoute)?;
+                    let route_out = rest.last().unwrap_or(first).denom_out.clone();
+                    if route_out == token_in_denom {
+                        return Err(OsmosisError::InvalidRoute(route_out).into());
+                    }
+                    match &denom_out {
+                        None => denom_out = Some(route_out),
+                        Some(expected) if *expected != route_out => {
+                            return Err(OsmosisError::SplitRouteDenomMismatch {
+                                expected: expected.clone(),
+                                got: route_out,
+                            }
+                            .into());
+                        }
+                        Some(_) => {}
+                    }
+
+                    let first = Swap::new(first.pool_id, &token_in_denom, &first.denom_out);
+                    let (payout, hops) = complex_swap(
+                        storage,
+                        first,
+                        rest.to_vec(),
+                        SwapAmount::In(token_in_amount),
+                    )?;
+                    save_swap_hops(storage, hops)?;
+                    total_in = total_in.checked_add(token_in_amount)?;
+                    total_out = total_out.checked_add(payout.as_out())?;
+                }
+                let denom_out = denom_out.ok_or(OsmosisError::EmptyRoute)?;
+                if total_out < token_out_min_amount {
+                    return Err(OsmosisError::MinOutputNotMet {
+                        got: total_out,
+                        min: token_out_min_amount,
+                    }
+                    .into());
+                }
+
+                // as with Swap, burn the input and mint the output
+                let burn = BankMsg::Burn {
+                    amount: coins(total_in.u128(), &token_in_denom),
+                };
+                router.execute(api, storage, block, sender.clone(), burn.into())?;
+                let mint = BankSudo::Mint {
+                    to_address: sender.to_string(),
+                    amount: coins(total_out.u128(), denom_out),
+                };
+                router.sudo(api, storage, block, mint.into())?;
+
+                let data = Some(to_binary(&SwapResponse {
+                    amount: SwapAmount::Out(total_out),
+                })?);
+                Ok(AppResponse {
+                    data,
+                    events: vec![],
+                })
+            }
+            OsmosisMsg::JoinPool {
+                pool_id,
+                share_out_amount,
+                token_in_maxs,
+            } => {
+                let mut pool = load_pool(storage, pool_id)?;
+                if matches!(pool.kind, PoolKind::Concentrated { .. }) {
+                    return Err(OsmosisError::ConcentratedPool(pool_id).into());
+                }
+                let (tokens_consumed, shares_out) = pool.join(share_out_amount, &token_in_maxs)?;
+                POOLS.save(storage, pool_id, &pool)?;
+
+                // Note: as with swaps, we burn the deposit rather than holding a balance for the AMM.
+                // Only the consumed amounts are taken, the rest of token_in_maxs stays with the sender
+                let burn = BankMsg::Burn {
+                    amount: tokens_consumed.clone(),
+                };
+                router.execute(api, storage, block, sender.clone(), burn.into())?;
+
+                // mint the lp shares to sender
+                let mint = BankSudo::Mint {
+                    to_address: sender.to_string(),
+                    amount: coins(shares_out.u128(), pool.gamm_denom(pool_id)),
+                };
+                router.sudo(api, storage, block, mint.into())?;
+
+                let data = Some(to_binary(&JoinPoolResponse {
+                    tokens_consumed,
+                    shares_out,
+                })?);
+                Ok(AppResponse {
+                    data,
+                    events: vec![],
+                })
+            }
+            OsmosisMsg::ExitPool {
+                pool_id,
+                share_in_amount,
+                token_out_mins,
+            } => {
+                let mut pool = load_pool(storage, pool_id)?;
+                if matches!(pool.kind, PoolKind::Concentrated { .. }) {
+                    return Err(OsmosisError::ConcentratedPool(pool_id).into());
+                }
+                let tokens_out = pool.exit(share_in_amount, &token_out_mins)?;
+                POOLS.save(storage, pool_id, &pool)?;
+
+                // burn the lp shares returned by the sender
+                let burn = BankMsg::Burn {
+                    amount: coins(share_in_amount.u128(), pool.gamm_denom(pool_id)),
+                };
+                router.execute(api, storage, block, sender.clone(), burn.into())?;
+
+                // and mint the withdrawn assets, as the deposits were burnt when joining
+                let payout: Vec<_> = tokens_out
+                    .iter()
+                    .filter(|c| !c.amount.is_zero())
+                    .cloned()
+                    .collect();
+                if !payout.is_empty() {
+                    let mint = BankSudo::Mint {
+                        to_address: sender.to_string(),
+                        amount: payout,
+                    };
+                    router.sudo(api, storage, block, mint.into())?;
+                }
+
+                let data = Some(to_binary(&ExitPoolResponse { tokens_out })?);
+                Ok(AppResponse {
+                    data,
+                    events: vec![],
+                })
+            }
+            OsmosisMsg::CreateBalancerPool {
+                pool_params,
+                pool_assets,
+            } => {
+                let pool = Pool::new_balancer(&pool_params, pool_assets)?;
+                self.create_pool(api, storage, router, block, sender, pool)
+            }
+            OsmosisMsg::CreateStableswapPool {
+                initial_pool_liquidity,
+                scaling_factors,
+                swap_fee,
+            } => {
+                let pool = Pool::new_stableswap(initial_pool_liquidity, scaling_factors, swap_fee)?;
+                self.create_pool(api, storage, router, block, sender, pool)
+            }
+            OsmosisMsg::CreateGauge {
+                is_perpetual,
+                denom,
+                coins,
+                start_time,
+                num_epochs_paid_over,
+            } => {
+                if coins.is_empty() {
+                    return Err(OsmosisError::EmptyGauge.into());
+                }
+                if coins.iter().any(|c| c.amount.is_zero()) {
+                    return Err(OsmosisError::ZeroAmount.into());
+                }
+                let now = (block.time.nanos() / 1_000_000) as i64;
+                if start_time < now {
+                    return Err(OsmosisError::GaugeStartInPast { start_time, now }.into());
+                }
+
+                // the coins are held in escrow until distributed, burn them like a deposit
+                let burn = BankMsg::Burn {
+                    amount: coins.clone(),
+                };
+                router.execute(api, storage, block, sender.clone(), burn.into())?;
+
+                let gauge_id = next_id(storage, &GAUGES)?;
+                let gauge = Gauge {
+                    owner: sender,
+                    is_perpetual,
+                    denom,
+                    coins,
+                    start_time,
+                    num_epochs_paid_over,
+                    filled_epochs: 0,
+                    distributed_coins: vec![],
+                };
+                GAUGES.save(storage, gauge_id, &gauge)?;
+
+                let data = Some(to_binary(&CreateGaugeResponse { gauge_id })?);
+                Ok(AppResponse {
+                    data,
+                    events: vec![],
+                })
+            }
+            OsmosisMsg::CreatePosition {
+                pool_id,
+                lower_tick,
+                upper_tick,
+                tokens_provided,
+                token_min_amount0,
+                token_min_amount1,
+            } => {
+                for tick in [lower_tick, upper_tick] {
+                    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+                        return Err(OsmosisError::InvalidTick(tick).into());
+                    }
+                }
+                if lower_tick >= upper_tick {
+                    return Err(OsmosisError::InvalidTickRange {
+                        lower_tick,
+                        upper_tick,
+                    }
+                    .into());
+                }
+                if tokens_provided.iter().all(|c| c.amount.is_zero()) {
+                    return Err(OsmosisError::ZeroAmount.into());
+                }
+                let mut pool = load_pool(storage, pool_id)?;
+                if !matches!(pool.kind, PoolKind::Concentrated { .. }) {
+                    return Err(OsmosisError::NotConcentratedPool(pool_id).into());
+                }
+                let (consumed, liquidity) = pool.add_position(&tokens_provided)?;
+                for (amount, min) in consumed.iter().zip([token_min_amount0, token_min_amount1]) {
+                    if amount.amount < min {
+                        return Err(OsmosisError::PositionMinAmountNotMet {
+                            amount: amount.clone(),
+                            min,
+                        }
+                        .into());
+                    }
+                }
+                POOLS.save(storage, pool_id, &pool)?;
+
+                let position_id = next_id(storage, &POSITIONS)?;
+                let position = Position {
+                    pool_id,
+                    owner: sender.clone(),
+                    lower_tick,
+                    upper_tick,
+                    liquidity,
+                    spread_rewards: vec![],
+                    incentives: vec![],
+                };
+                POSITIONS.save(storage, position_id, &position)?;
+
+                // deposits are burnt as for any other pool
+                let burn = BankMsg::Burn {
+                    amount: consumed
+                        .iter()
+                        .filter(|c| !c.amount.is_zero())
+                        .cloned()
+                        .collect(),
+                };
+                router.execute(api, storage, block, sender, burn.into())?;
+
+                let data = Some(to_binary(&CreatePositionResponse {
+                    position_id,
+                    amount0: consumed[0].amount,
+                    amount1: consumed[1].amount,
+                    liquidity_created: liquidity.to_string(),
+                })?);
+                Ok(AppResponse {
+                    data,
+                    events: vec![],
+                })
+            }
+            OsmosisMsg::WithdrawPosition {
+                position_id,
+                liquidity_amount,
+            } => {
+                let mut position = POSITIONS
+                    .may_load(storage, position_id)?
+                    .ok_or(OsmosisError::PositionNotFound(position_id))?;
+                if position.owner != sender {
+                    return Err(OsmosisError::Unauthorized.into());
+                }
+                // liquidity is tracked in whole units, any fraction stays in the position
+                let liquidity = Decimal256::from_str(&liquidity_amount)?;
+                let liquidity =
+                    Uint128::try_from(liquidity.atomics() / Decimal256::one().atomics())?;
+                if liquidity.is_zero() {
+                    return Err(OsmosisError::ZeroAmount.into());
+                }
+                if liquidity > position.liquidity {
+                    return Err(OsmosisError::InsufficientPositionLiquidity {
+                        requested: liquidity,
+                        available: position.liquidity,
+                    }
+                    .into());
+                }
+
+                let mut pool = load_pool(storage, position.pool_id)?;
+                let tokens_out = pool.exit(liquidity, &[])?;
+                POOLS.save(storage, position.pool_id, &pool)?;
+
+                position.liquidity -= liquidity;
+                // an emptied position is only kept around while it has rewards left to collect
+                if position.liquidity.is_zero()
+                    && position.spread_rewards.is_empty()
+                    && position.incentives.is_empty()
+                {
+                    POSITIONS.remove(storage, position_id);
+                } else {
+                    POSITIONS.save(storage, position_id, &position)?;
+                }
+
+                // mint the withdrawn assets, as the deposits were burnt when creating the position
+                let payout: Vec<_> = tokens_out
+                    .iter()
+                    .filter(|c| !c.amount.is_zero())
+                    .cloned()
+                    .collect();
+                if !payout.is_empty() {
+                    let mint = BankSudo::Mint {
+                        to_address: sender.to_string(),
+                        amount: payout,
+                    };
+                    router.sudo(api, storage, block, mint.into())?;
+                }
+
+                let data = Some(to_binary(&WithdrawPositionResponse {
+                    amount0: tokens_out[0].amount,
+                    amount1: tokens_out[1].amount,
+                })?);
+                Ok(AppResponse {
+                    data,
+                    events: vec![],
+                })
+            }
+            OsmosisMsg::CollectSpreadRewards { position_ids } => {
+                let collected =
+                    collect_rewards(storage, &sender, &position_ids, |p| &mut p.spread_rewards)?;
+                if !collected.is_empty() {
+                    let mint = BankSudo::Mint {
+                        to_address: sender.to_string(),
+                        amount: collected.clone(),
+                    };
+                    router.sudo(api, storage, block, mint.into())?;
+                }
+
+                let data = Some(to_binary(&CollectSpreadRewardsResponse {
+                    collected_spread_rewards: collected,
+                })?);
+                Ok(AppResponse {
+                    data,
+                    events: vec![],
+                })
+            }
+            OsmosisMsg::CollectIncentives { position_ids } => {
+                let collected =
+                    collect_rewards(storage, &sender, &position_ids, |p| &mut p.incentives)?;
+                if !collected.is_empty() {
+                    let mint = BankSudo::Mint {
+                        to_address: sender.to_string(),
+                        amount: collected.clone(),
+                    };
+                    router.sudo(api, storage, block, mint.into())?;
+                }
+
+                let data = Some(to_binary(&CollectIncentivesResponse {
+                    collected_incentives: collected,
+                })?);
+                Ok(AppResponse {
+                    data,
+                    events: vec![],
+                })
+            }
+            OsmosisMsg::AddToGauge { gauge_id, coins } => {
+                if coins.is_empty() {
+                    return Err(OsmosisError::EmptyGauge.into());
+                }
+                if coins.iter().any(|c| c.amount.is_zero()) {
+                    return Err(OsmosisError::ZeroAmount.into());
+                }
+                let mut gauge = GAUGES
+                    .may_load(storage, gauge_id)?
+                    .ok_or(OsmosisError::GaugeNotFound(gauge_id))?;
+                gauge.add_coins(coins.clone())?;
+                GAUGES.save(storage, gauge_id, &gauge)?;
+
+                // escrowed like the initial coins
+                let burn = BankMsg::Burn { amount: coins };
+                router.execute(api, storage, block, sender, burn.into())?;
+
+                Ok(AppResponse {
+                    data: None,
+                    events: vec![],
+                })
+            }
+        }
+    }
+
+    fn sudo<ExecC, QueryC>(
+        &self,
+        api: &dyn Api,
+        storage: &mut dyn Storage,
+        router: &dyn CosmosRouter<ExecC = ExecC, QueryC = QueryC>,
+        block: &BlockInfo,
+        msg: Self::SudoT,
+    ) -> AnyResult<AppResponse>
+    where
+        ExecC: Debug + Clone + PartialEq + JsonSchema + DeserializeOwned + 'static,
+        QueryC: CustomQuery + DeserializeOwned + 'static,
+    {
+        match msg {
+            OsmosisSudo::EpochEnd {
+                identifier,
+                epoch_number,
+            } => {
+                if identifier != Epoch::IDENTIFIER {
+                    return Err(OsmosisError::EpochNotFound(identifier).into());
+                }
+                let now = (block.time.nanos() / 1_000_000) as i64;
+
+                let gauges = GAUGES
+                    .range(storage, None, None, Order::Ascending)
+                    .collect::<StdResult<Vec<_>>>()?;
+                let mut rewards: BTreeMap<Addr, Vec<Coin>> = BTreeMap::new();
+                for (gauge_id, mut gauge) in gauges {
+                    let finished =
+                        !gauge.is_perpetual && gauge.filled_epochs >= gauge.num_epochs_paid_over;
+                    if gauge.start_time > now || finished {
+                        continue;
+                    }
+
+                    let mut locks = vec![];
+                    for item in LOCKS.range(storage, None, None, Order::Ascending) {
+                        let (_, lock) = item?;
+                        let locked = lock.coins.iter().find(|c| c.denom == gauge.denom);
+                        if let Some(locked) = locked.filter(|c| !c.amount.is_zero()) {
+                            locks.push((lock.owner.clone(), locked.amount));
+                        }
+                    }
+                    let total_locked: Uint128 = locks.iter().map(|(_, locked)| *locked).sum();
+
+                    // perpetual gauges pay out everything they hold, others an equal share of
+                    // what is left over their remaining epochs
+                    let remaining_epochs = if gauge.is_perpetual {
+                        1
+                    } else {
+                        gauge.num_epochs_paid_over - gauge.filled_epochs
+                    };
+                    // with nobody to pay, the epoch's share stays in the gauge
+                    let mut distributed = vec![];
+                    for total in gauge.coins.iter().filter(|_| !total_locked.is_zero()) {
+                        let paid = gauge
+                            .distributed_coins
+                            .iter()
+                            .find(|c| c.denom == total.denom)
+                            .map(|c| c.amount)
+                            .unwrap_or_default();
+                        let epoch_amount =
+                            total.amount.checked_sub(paid)? / Uint128::from(remaining_epochs);
+                        for (owner, locked) in &locks {
+                            let reward = epoch_amount.multiply_ratio(*locked, total_locked);
+                            let reward = coin(reward.u128(), &total.denom);
+                            rewards
+                                .entry(owner.clone())
+                                .or_default()
+                                .push(reward.clone());
+                            distributed.push(reward);
+                        }
+                    }
+
+                    let already = gauge.distributed_coins.drain(..);
+                    gauge.distributed_coins = merge_coins(already.chain(distributed))?;
+                    gauge.filled_epochs += 1;
+                    GAUGES.save(storage, gauge_id, &gauge)?;
+                }
+
+                // gauge coins were burnt on deposit, so payouts are minted
+                for (owner, coins) in rewards {
+                    let amount = merge_coins(coins)?;
+                    if !amount.is_empty() {
+                        let mint = BankSudo::Mint {
+                            to_address: owner.into(),
+                            amount,
+                        };
+                        router.sudo(api, storage, block, mint.into())?;
+                    }
+                }
+
+                let event = Event::new("epoch_end")
+                    .add_attribute("identifier", identifier)
+                    .add_attribute("epoch_number", epoch_number.to_string());
+                Ok(AppResponse {
+                    data: None,
+                    events: vec![event],
+                })
+            }
+        }
+    }
+
+    fn query(
+        &self,
+        api: &dyn Api,
+        storage: &dyn Storage,
+        _querier: &dyn Querier,
+        block: &BlockInfo,
+        request: OsmosisQuery,
+    ) -> anyhow::Result<Binary> {
+        match request {
+            OsmosisQuery::FullDenom {
+                creator_addr,
+                subdenom,
+            } => {
+                let contract = api.addr_validate(&creator_addr)?;
+                let denom = self.build_denom(&contract, &subdenom)?;
+                let res = FullDenomResponse { denom };
+                Ok(to_binary(&res)?)
+            }
+            OsmosisQuery::DenomAdmin { denom } => {
+                let admin = DENOM_ADMINS
+                    .may_load(storage, &denom)?
+                    .ok_or(OsmosisError::DenomDoesNotExist(denom))?;
+                Ok(to_binary(&DenomAdminResponse { admin })?)
+            }
+            OsmosisQuery::IsFactoryDenom { denom } => {
+                let is_factory = is_factory_denom(&denom) && DENOM_ADMINS.has(storage, &denom);
+                Ok(to_binary(&IsFactoryDenomResponse { is_factory })?)
+            }
+            OsmosisQuery::BeforeSendHook { denom } => {
+                if !DENOM_ADMINS.has(storage, &denom) {
+                    return Err(OsmosisError::DenomDoesNotExist(denom).into());
+                }
+                let cosmwasm_address = BEFORE_SEND_HOOKS
+                    .may_load(storage, &denom)?
+                    .map(String::from)
+                    .unwrap_or_default();
+                Ok(to_binary(&BeforeSendHookResponse { cosmwasm_address })?)
+            }
+            OsmosisQuery::DenomsByCreator { creator } => {
+                let creator = api.addr_validate(&creator)?;
+                let prefix = format!("factory/{}/", creator);
+                let denoms = DENOM_ADMINS
+                    .keys(storage, None, None, Order::Ascending)
+                    .filter(|denom| match denom {
+                        Ok(denom) => denom.starts_with(&prefix),
+                        Err(_) => true,
+                    })
+                    .collect::<StdResult<_>>()?;
+                Ok(to_binary(&DenomsByCreatorResponse { denoms })?)
+            }
+            OsmosisQuery::PoolState { id } => {
+                let pool = load_pool(storage, id)?;
+                let res = pool.into_response(id);
+                Ok(to_binary(&res)?)
+            }
+            OsmosisQuery::PoolStateByLpDenom { lp_denom } => {
+                let id = pool_id_from_lp_denom(&lp_denom)
+                    .ok_or(OsmosisError::InvalidLpDenom(lp_denom))?;
+                let pool = load_pool(storage, id)?;
+                let res = pool.into_response(id);
+                Ok(to_binary(&res)?)
+            }
+            OsmosisQuery::Pools { start_after, limit } => {
+                let limit = limit.unwrap_or(MAX_POOLS_LIMIT).min(MAX_POOLS_LIMIT) as usize;
+                let pools = POOLS
+                    .range(
+                        storage,
+                        start_after.map(Bound::exclusive),
+                        None,
+                        Order::Ascending,
+                    )
+                    .take(limit)
+                    .map(|item| {
+                        let (id, pool) = item?;
+                        Ok(PoolInfo {
+                            id,
+                            state: pool.into_response(id),
+                        })
+                    })
+                    .collect::<StdResult<_>>()?;
+                Ok(to_binary(&PoolsResponse { pools })?)
+            }
+            OsmosisQuery::TotalPoolLiquidity { pool_id } => {
+                let pool = load_pool(storage, pool_id)?;
+                Ok(to_binary(&TotalPoolLiquidityResponse {
+                    liquidity: pool.assets,
+                })?)
+            }
+            OsmosisQuery::PoolLiquidityOf { pool_id, denom } => {
+                let pool = load_pool(storage, pool_id)?;
+                let amount = pool
+                    .get_amount(&denom)
+                    .ok_or(OsmosisError::DenomNotInPool { denom, pool_id })?;
+                Ok(to_binary(&PoolLiquidityResponse { amount })?)
+            }
+            OsmosisQuery::PoolType { pool_id } => {
+                let pool = load_pool(storage, pool_id)?;
+                let pool_type = pool.kind.name().to_string();
+                Ok(to_binary(&PoolTypeResponse { pool_type })?)
+            }
+            OsmosisQuery::PoolParams { id } => {
+                let pool = load_pool(storage, id)?;
+                let params = pool.params();
+                Ok(to_binary(&PoolParamsResponse { params })?)
+            }
+            OsmosisQuery::CalcJoinPoolShares { pool_id, tokens_in } => {
+                let mut pool = load_pool(storage, pool_id)?;
+                if matches!(pool.kind, PoolKind::Concentrated { .. }) {
+                    return Err(OsmosisError::ConcentratedPool(pool_id).into());
+                }
+                if tokens_in
+                    .iter()
+                    .any(|c| pool.get_amount(&c.denom).is_none())
+                {
+                    return Err(OsmosisError::AssetNotInPool.into());
+                }
+                // join a copy of the pool, which is never saved
+                let (tokens_used, share_out_amount) = pool.join(Uint128::zero(), &tokens_in)?;
+                Ok(to_binary(&CalcJoinPoolSharesResponse {
+                    share_out_amount,
+                    tokens_used,
+                })?)
+            }
+            OsmosisQuery::CalcExitPoolCoinsFromShares {
+                pool_id,
+                share_in_amount,
+            } => {
+                // exit a copy of the pool, which is never saved
+                let mut pool = load_pool(storage, pool_id)?;
+                if matches!(pool.kind, PoolKind::Concentrated { .. }) {
+                    return Err(OsmosisError::ConcentratedPool(pool_id).into());
+                }
+                let tokens_out = pool.exit(share_in_amount, &[])?;
+                Ok(to_binary(&CalcExitResponse { tokens_out })?)
+            }
+            OsmosisQuery::SpotPrice {
+                swap,
+                with_swap_fee,
+            } => {
+                let pool = load_pool(storage, swap.pool_id)?;
+                let price = pool.spot_price(&swap.denom_in, &swap.denom_out, with_swap_fee)?;
+                Ok(to_binary(&SpotPriceResponse { price })?)
+            }
+            OsmosisQuery::SpotPriceBoth {
+                pool_id,
+                denom_a,
+                denom_b,
+                with_swap_fee,
+            } => {
+                let pool = load_pool(storage, pool_id)?;
+                let a_per_b = pool.spot_price(&denom_b, &denom_a, with_swap_fee)?;
+                let b_per_a = pool.spot_price(&denom_a, &denom_b, with_swap_fee)?;
+                Ok(to_binary(&SpotPriceBothResponse { a_per_b, b_per_a })?)
+            }
+            OsmosisQuery::SpotPriceVerbose { swap, swap_fee } => {
+                let pool = load_pool(storage, swap.pool_id)?;
+                let swap_fee = swap_fee.unwrap_or(pool.fee);
+                let marginal =
+                    pool.spot_price(&swap.denom_in, &swap.denom_out, SpotPriceKind::Marginal)?;
+                let price = marginal * Decimal::one().checked_sub(swap_fee)?;
+                Ok(to_binary(&SpotPriceWithFeeResponse { price, swap_fee })?)
+            }
+            OsmosisQuery::SpotPriceV2 {
+                pool_id,
+                base_asset_denom,
+                quote_asset_denom,
+            } => {
+                let pool = load_pool(storage, pool_id)?;
+                // one base asset "in" buys this many quote assets "out"
+                let price = pool.spot_price(
+                    &base_asset_denom,
+                    &quote_asset_denom,
+                    SpotPriceKind::Marginal,
+                )?;
+                Ok(to_binary(&SpotPriceResponse { price })?)
+            }
+            OsmosisQuery::LpSpotPrice {
+                pool_id,
+                quote_denom,
+            } => {
+                let pool = load_pool(storage, pool_id)?;
+                if pool.get_amount(&quote_denom).is_none() {
+                    return Err(OsmosisError::DenomNotInPool {
+                        denom: quote_denom,
+                        pool_id,
+                    }
+                    .into());
+                }
+                if pool.shares.is_zero() {
+                    return Err(OsmosisError::EmptyPool.into());
+                }
+                // sum up what each asset backing one share is worth in the quote denom
+                let mut price = Decimal::zero();
+                for asset in &pool.assets {
+                    let per_share = Decimal::checked_from_ratio(asset.amount, pool.shares)?;
+                    let value = if asset.denom == quote_denom {
+                        per_share
+                    } else {
+                        let spot =
+                            pool.spot_price(&asset.denom, &quote_denom, SpotPriceKind::Marginal)?;
+                        per_share.checked_mul(spot)?
+                    };
+                    price = price.checked_add(value)?;
+                }
+                Ok(to_binary(&LpSpotPriceResponse { price })?)
+            }
+            OsmosisQuery::EstimateSwap {
+                sender: _sender,
+                first,
+                route,
+                amount,
+            } => {
+                let (amount, _) = complex_swap(storage, first, route, amount)?;
+
+                Ok(to_binary(&SwapResponse { amount })?)
+            }
+            OsmosisQuery::EstimatePriceImpact {
+                first,
+                route,
+                amount,
+            } => {
+                let mut spot_price_before = Decimal::one();
+                for hop in route_hops(first.clone(), route.clone()) {
+                    let pool = load_pool(storage, hop.pool_id)?;
+                    spot_price_before *=
+                        pool.spot_price(&hop.denom_in, &hop.denom_out, SpotPriceKind::Marginal)?;
+                }
+                // pools are only updated in the returned copies, storage is left alone
+                let (result, _) = complex_swap(storage, first, route, amount.clone())?;
+                let (input, output) = match amount {
+                    SwapAmount::In(input) => (input, result.as_out()),
+                    SwapAmount::Out(output) => (result.as_in(), output),
+                };
+                let effective_price = Decimal::checked_from_ratio(output, input)?;
+                let impact = if effective_price < spot_price_before {
+                    Decimal::one() - effective_price / spot_price_before
+                } else {
+                    Decimal::zero()
+                };
+                Ok(to_binary(&PriceImpactResponse {
+                    spot_price_before,
+                    effective_price,
+                    impact,
+                })?)
+            }
+            OsmosisQuery::EstimateSwapExactAmountIn {
+                sender: _sender,
+                first,
+                route,
+                token_in,
+            } => {
+                let (amount, _) = complex_swap(storage, first, route, SwapAmount::In(token_in))?;
+                let token_out = amount.as_out();
+                Ok(to_binary(&EstimateSwapInResponse { token_out })?)
+            }
+            OsmosisQuery::EstimateSwapExactAmountOut {
+                sender: _sender,
+                first,
+                route,
+                token_out,
+            } => {
+                let (amount, _) = complex_swap(storage, first, route, SwapAmount::Out(token_out))?;
+                let token_in = amount.as_in();
+                Ok(to_binary(&EstimateSwapOutResponse { token_in })?)
+            }
+            // The TWAPs average over the mocked price history of the pair, or return the spot price
+            // if there is none.
+            OsmosisQuery::ArithmeticTwap {
+                id,
+                quote_asset_denom,
+                base_asset_denom,
+                start_time,
+                end_time,
+            } => {
+                let pool = load_pool(storage, id)?;
+                let twap = match twap_history(
+                    storage,
+                    &pool,
+                    id,
+                    &quote_asset_denom,
+                    &base_asset_denom,
+                )? {
+                    Some(history) => {
+                        arithmetic_mean(&twap_segments(&history, start_time, end_time)?)?
+                    }
+                    None => pool.arithmetic_twap(&quote_asset_denom, &base_asset_denom)?,
+                };
+                Ok(to_binary(&ArithmeticTwapResponse { twap })?)
+            }
+            OsmosisQuery::GeometricTwap {
+                id,
+                quote_asset_denom,
+                base_asset_denom,
+                start_time,
+                end_time,
+            } => {
+                let pool = load_pool(storage, id)?;
+                let twap = match twap_history(
+                    storage,
+                    &pool,
+                    id,
+                    &quote_asset_denom,
+                    &base_asset_denom,
+                )? {
+                    Some(history) => {
+                        geometric_mean(&twap_segments(&history, start_time, end_time)?)?
+                    }
+                    // without any history the price has been constant
+                    None => pool.arithmetic_twap(&quote_asset_denom, &base_asset_denom)?,
+                };
+                Ok(to_binary(&GeometricTwapResponse { twap })?)
+            }
+            OsmosisQuery::ArithmeticTwapToNow {
+                id,
+                quote_asset_denom,
+                base_asset_denom,
+                start_time,
+            } => {
+                let pool = load_pool(storage, id)?;
+                let now = (block.time.nanos() / 1_000_000) as i64;
+                let twap = match twap_history(
+                    storage,
+                    &pool,
+                    id,
+                    &quote_asset_denom,
+                    &base_asset_denom,
+                )? {
+                    Some(history) => arithmetic_mean(&twap_segments(&history, start_time, now)?)?,
+                    None => pool.arithmetic_twap_to_now(&quote_asset_denom, &base_asset_denom)?,
+                };
+                Ok(to_binary(&ArithmeticTwapToNowResponse { twap })?)
+            }
+            OsmosisQuery::LockedById { lock_id } => {
+                let lock = LOCKS
+                    .may_load(storage, lock_id)?
+                    .ok_or(OsmosisError::LockNotFound(lock_id))?;
+                Ok(to_binary(&lock.into_response(lock_id))?)
+            }
+            OsmosisQuery::AccountLockedCoins { address } => {
+                let owner = api.addr_validate(&address)?;
+                let mut locked = vec![];
+                for item in LOCKS.range(storage, None, None, Order::Ascending) {
+                    let (_, lock) = item?;
+                    if lock.owner == owner {
+                        locked.extend(lock.coins);
+                    }
+                }
+                let coins = merge_coins(locked)?;
+                Ok(to_binary(&AccountLockedCoinsResponse { coins })?)
+            }
+            OsmosisQuery::AccountLocks {
+                address,
+                start_after,
+                limit,
+            } => {
+                let owner = api.addr_validate(&address)?;
+                let limit = limit.unwrap_or(MAX_LOCKS_LIMIT).min(MAX_LOCKS_LIMIT) as usize;
+                let locks = LOCKS
+                    .range(
+                        storage,
+                        start_after.map(Bound::exclusive),
+                        None,
+                        Order::Ascending,
+                    )
+                    .filter(|item| match item {
+                        Ok((_, lock)) => lock.owner == owner,
+                        Err(_) => true,
+                    })
+                    .take(limit)
+                    .map(|item| {
+                        let (id, lock) = item?;
+                        Ok(lock.into_response(id))
+                    })
+                    .collect::<StdResult<_>>()?;
+                Ok(to_binary(&AccountLocksResponse { locks })?)
+            }
+            OsmosisQuery::SuperfluidDelegation { lock_id } => {
+                let lock = LOCKS
+                    .may_load(storage, lock_id)?
+                    .ok_or(OsmosisError::LockNotFound(lock_id))?;
+                let res = match SUPERFLUID.may_load(storage, lock_id)? {
+                    Some(validator) => SuperfluidDelegationResponse {
+                        validator: Some(validator),
+                        delegated_coins: lock.coins,
+                    },
+                    None => SuperfluidDelegationResponse {
+                        validator: None,
+                        delegated_coins: vec![],
+                    },
+                };
+                Ok(to_binary(&res)?)
+            }
+            OsmosisQuery::DenomSupply { denom } => {
+                let amount = bank_supply(storage, &denom)?;
+                Ok(to_binary(&DenomSupplyResponse { amount })?)
+            }
+            OsmosisQuery::TotalLiquidity {} => {
+                let mut assets = vec![];
+                for item in POOLS.range(storage, None, None, Order::Ascending) {
+                    let (_, pool) = item?;
+                    assets.extend(pool.assets);
+                }
+                let coins = merge_coins(assets)?;
+                Ok(to_binary(&TotalLiquidityResponse { coins })?)
+            }
+            OsmosisQuery::GaugeById { id } => {
+                let gauge = GAUGES
+                    .may_load(storage, id)?
+                    .ok_or(OsmosisError::GaugeNotFound(id))?;
+                Ok(to_binary(&gauge.into_response(id))?)
+            }
+            OsmosisQuery::PositionById { position_id } => {
+                let position = POSITIONS
+                    .may_load(storage, position_id)?
+                    .ok_or(OsmosisError::PositionNotFound(position_id))?;
+                let pool = load_pool(storage, position.pool_id)?;
+                Ok(to_binary(&position.into_response(&pool))?)
+            }
+            OsmosisQuery::EpochProvisions {} => {
+                let epoch_provisions = EPOCH_PROVISIONS
+                    .may_load(storage)?
+                    .unwrap_or(DEFAULT_EPOCH_PROVISIONS);
+                Ok(to_binary(&EpochProvisionsResponse { epoch_provisions })?)
+            }
+            OsmosisQuery::EpochInfo { identifier } => {
+                let epoch = match EPOCH.may_load(storage)? {
+                    Some(epoch) if identifier == Epoch::IDENTIFIER => epoch,
+                    _ => return Err(OsmosisError::EpochNotFound(identifier).into()),
+                };
+                Ok(to_binary(&epoch.info(block.time))?)
+            }
+        }
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum OsmosisError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("{0}")]
+    Overflow(#[from] cosmwasm_std::OverflowError),
+
+    #[error("{0}")]
+    DivideByZero(#[from] cosmwasm_std::DivideByZeroError),
+
+    #[error("{0}")]
+    CheckedFromRatio(#[from] cosmwasm_std::CheckedFromRatioError),
+
+    #[error("{0}")]
+    ConversionOverflow(#[from] cosmwasm_std::ConversionOverflowError),
+
+    #[error("Asset not in pool")]
+    AssetNotInPool,
+
+    #[error("Pool has no liquidity for the requested assets")]
+    EmptyPool,
+
+    #[error("asset {denom} not in pool {pool_id}")]
+    DenomNotInPool { denom: String, pool_id: u64 },
+
+    #[error("Aborting swap - payout: {got} is smaller then minimal output: {min}")]
+    MinOutputNotMet { got: Uint128, min: Uint128 },
+
+    #[error("Aborting swap - payin: {got} is bigger then maximum input: {max}")]
+    MaxInputExceeded { got: Uint128, max: Uint128 },
+
+    #[error("Route must not end on {0}, the denom it starts with")]
+    InvalidRoute(String),
+
+    #[error("Cannot swap {0} for itself")]
+    SameDenomSwap(String),
+
+    #[error("Swap denoms must not be empty")]
+    EmptyDenom,
+
+    #[error("Pool {pool_id} has too little liquidity to pay out the requested amount")]
+    InsufficientPoolLiquidity { pool_id: u64 },
+
+    #[error("Swap route must have at least one pool")]
+    EmptyRoute,
+
+    #[error("All split routes must end on {expected}, got {got}")]
+    SplitRouteDenomMismatch { expected: String, got: String },
+
+    #[error("Unauthorized")]
+    Unauthorized,
+
+    #[error("Amount must not be zero")]
+    ZeroAmount,
+
+    #[error("Denom {0} does not exist")]
+    DenomDoesNotExist(String),
+
+    #[error("{0} is not an LP share denom")]
+    InvalidLpDenom(String),
+
+    #[error("Denom {0} already exists")]
+    DenomAlreadyExists(String),
+
+    #[error(
+        "Minting would raise the supply of {denom} to {supply}, above its max of {max_supply}"
+    )]
+    MaxSupplyExceeded {
+        denom: String,
+        supply: Uint128,
+        max_supply: Uint128,
+    },
+
+    #[error("Lock {0} does not exist")]
+    LockNotFound(u64),
+
+    #[error("Pool {id} does not exist")]
+    PoolNotFound { id: u64 },
+
+    #[error("Position {0} does not exist")]
+    PositionNotFound(u64),
+
+    #[error("Cannot withdraw {requested} liquidity, position only has {available}")]
+    InsufficientPositionLiquidity {
+        requested: Uint128,
+        available: Uint128,
+    },
+
+    #[error("Deadline {deadline} has passed, it is now {now}")]
+    Expired { deadline: i64, now: i64 },
+
+    #[error("TWAP window must start before it ends, got {start_time} to {end_time}")]
+    InvalidTwapWindow { start_time: i64, end_time: i64 },
+
+    #[error("No price observations to average over")]
+    EmptyTwapWindow,
+
+    #[error("Tick {0} is outside of the range [{}, {}]", MIN_TICK, MAX_TICK)]
+    InvalidTick(i64),
+
+    #[error("Lower tick {lower_tick} must be below upper tick {upper_tick}")]
+    InvalidTickRange { lower_tick: i64, upper_tick: i64 },
+
+    #[error("Pool {0} is not a concentrated liquidity pool")]
+    NotConcentratedPool(u64),
+
+    #[error("Pool {0} is a concentrated liquidity pool, use positions instead")]
+    ConcentratedPool(u64),
+
+    #[error("Aborting position - would only use {amount}, less than the minimum of {min}")]
+    PositionMinAmountNotMet { amount: Coin, min: Uint128 },
+
+    #[error("Gauge {0} does not exist")]
+    GaugeNotFound(u64),
+
+    #[error("A gauge needs coins to distribute")]
+    EmptyGauge,
+
+    #[error("A mint batch needs at least one recipient")]
+    EmptyMintBatch,
+
+    #[error("Gauge start time {start_time} is before the current time {now}")]
+    GaugeStartInPast { start_time: i64, now: i64 },
+
+    #[error("Epoch {0} does not exist")]
+    EpochNotFound(String),
+
+    #[error("Aborting join - requires {required}, which is bigger then maximum input: {max}")]
+    JoinPoolMaxInputExceeded { required: Coin, max: Uint128 },
+
+    #[error("Aborting exit - payout {output} is smaller then minimal output: {min}")]
+    ExitPoolMinOutputNotMet { output: Coin, min: Uint128 },
+
+    #[error("Cannot exit with {requested} shares, the pool only has {available}")]
+    InsufficientPoolShares {
+        requested: Uint128,
+        available: Uint128,
+    },
+
+    #[error("A pool needs at least two assets")]
+    TooFewPoolAssets,
+
+    #[error("Weight of {0} must not be zero")]
+    ZeroPoolWeight(String),
+
+    #[error("Asset {0} was given more than once")]
+    DuplicatePoolAsset(String),
+
+    #[error("Expected {expected} scaling factors, got {got}")]
+    ScalingFactorsMismatch { expected: usize, got: usize },
+
+    #[error("Scaling factor of {0} must not be zero")]
+    ZeroScalingFactor(String),
+
+    #[error("Swap fee {0} must be less than 1")]
+    InvalidSwapFee(Decimal),
+
+    /// Remove this to let the compiler find all TODOs
+    #[error("Not yet implemented (TODO)")]
+    Unimplemented,
+}
+
+pub type OsmosisAppWrapped =
+    App<BankKeeper, MockApi, MockStorage, OsmosisModule, WasmKeeper<OsmosisMsg, OsmosisQuery>>;
+
+pub struct OsmosisApp {
+    app: OsmosisAppWrapped,
+    /// Seconds per block when advancing the chain
+    block_time: u64,
+    /// Whether advancing the chain past the end of an epoch distributes gauges
+    epoch_distributions: bool,
+}
+
+impl Deref for OsmosisApp {
+    type Target = OsmosisAppWrapped;
+
+    fn deref(&self) -> &Self::Target {
+        &self.app
+    }
+}
+
+impl DerefMut for OsmosisApp {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.app
+    }
+}
+
+impl Querier for OsmosisApp {
+    fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+        self.app.raw_query(bin_request)
+    }
+}
+
+impl Default for OsmosisApp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OsmosisApp {
+    pub fn new() -> Self {
+        Self::new_with_epoch_duration(DEFAULT_EPOCH_DURATION)
+    }
+
+    /// Like `new`, but the "day" epoch lasts `epoch_duration` seconds instead of a day.
+    /// Either way the first epoch starts at the genesis block time.
+    pub fn new_with_epoch_duration(epoch_duration: u64) -> Self {
+        assert!(epoch_duration > 0, "epoch duration must be positive");
+        let mut app = Self {
+            app: BasicAppBuilder::<OsmosisMsg, OsmosisQuery>::new_custom()
+                .with_custom(OsmosisModule {})
+                .build(|_router, _, _storage| {
+                    // router.custom.set_owner(storage, &owner).unwrap();
+                }),
+            block_time: BLOCK_TIME,
+            epoch_distributions: false,
+        };
+        let epoch = Epoch {
+            start_time: app.block_info().time,
+            duration: epoch_duration,
+        };
+        app.init_modules(|router, _, storage| {
+            router.custom.set_epoch(storage, &epoch)?;
+            router
+                .custom
+                .set_epoch_provisions(storage, DEFAULT_EPOCH_PROVISIONS)
+        })
+        .unwrap();
+        app
+    }
+
+    /// Sets what `OsmosisQuery::EpochProvisions` returns (default `DEFAULT_EPOCH_PROVISIONS`)
+    pub fn with_epoch_provisions(mut self, epoch_provisions: Decimal) -> Self {
+        self.init_modules(|router, _, storage| {
+            router
+                .custom
+                .set_epoch_provisions(storage, epoch_provisions)
+        })
+        .unwrap();
+        self
+    }
+
+    /// Sets how many seconds pass per block when advancing the chain (default `BLOCK_TIME`)
+    pub fn with_block_time(mut self, seconds: u64) -> Self {
+        assert!(seconds > 0, "block time must be positive");
+        self.block_time = seconds;
+        self
+    }
+
+    pub fn block_time(&self) -> u64 {
+        self.block_time
+    }
+
+    /// Makes `advance_blocks` and `advance_seconds` send `OsmosisSudo::EpochEnd` for every epoch
+    /// they move past, so gauges pay out as time passes like on chain
+    pub fn with_epoch_distributions(mut self) -> Self {
+        self.epoch_distributions = true;
+        self
+    }
+
+    pub fn block_info(&self) -> BlockInfo {
+        self.app.block_info()
+    }
+
+    /// This advances BlockInfo by given number of blocks.
+    /// It keeps the ratio of seconds/block, and only ends epochs if enabled with
+    /// `with_epoch_distributions`, panicking if ending one fails
+    pub fn advance_blocks(&mut self, blocks: u64) {
+        let block_time = self.block_time;
+        let epoch = self.epoch_distributions.then(|| self.current_epoch());
+        self.update_block(|block| {
+            block.time = block.time.plus_seconds(block_time * blocks);
+            block.height += blocks;
+        });
+        if let Some(epoch) = epoch {
+            self.end_epochs_since(epoch);
+        }
+    }
+
+    /// This advances BlockInfo by given number of seconds.
+    /// It keeps the ratio of seconds/block, and only ends epochs if enabled with
+    /// `with_epoch_distributions`, panicking if ending one fails
+    pub fn advance_seconds(&mut self, seconds: u64) {
+        let block_time = self.block_time;
+        let epoch = self.epoch_distributions.then(|| self.current_epoch());
+        self.update_block(|block| {
+            block.time = block.time.plus_seconds(seconds);
+            block.height += max(1, seconds / block_time);
+        });
+        if let Some(epoch) = epoch {
+            self.end_epochs_since(epoch);
+        }
+    }
+
+    fn current_epoch(&self) -> i64 {
+        let now = self.block_info().time;
+        self.read_module(|_, _, storage| EPOCH.load(storage))
+            .map(|epoch| epoch.info(now).current_epoch)
+            .unwrap_or_default()
+    }
+
+    /// Ends every epoch from `epoch` up to (excluding) the current one, in order.
+    ///
+    /// # Panics
+    ///
+    /// If `OsmosisSudo::EpochEnd` fails, as the advance functions calling this have no way to
+    /// report it. The panic names the epoch and the error.
+    fn end_epochs_since(&mut self, epoch: i64) {
+        for epoch_number in max(epoch, 1)..self.current_epoch() {
+            self.custom_sudo(OsmosisSudo::EpochEnd {
+                identifier: Epoch::IDENTIFIER.to_string(),
+                epoch_number,
+            })
+            .unwrap_or_else(|err| panic!("ending epoch {} failed: {}", epoch_number, err));
+        }
+    }
+
+    /// Every factory denom created so far as (creator, full denom), sorted by denom.
+    /// The creator stays the same if the admin changes.
+    pub fn created_denoms(&self) -> Vec<(Addr, String)> {
+        self.read_module(|_, _, storage| {
+            DENOM_ADMINS
+                .keys(storage, None, None, Order::Ascending)
+                .map(|denom| {
+                    let denom = denom?;
+                    let creator = denom.split('/').nth(1).unwrap_or_default();
+                    Ok((Addr::unchecked(creator), denom))
+                })
+                .collect::<StdResult<_>>()
+        })
+        .expect("reading the created denoms failed")
+    }
+
+    /// Runs a privileged message on the `OsmosisModule`.
+    /// cw-multi-test cannot route custom sudo messages through `App::sudo`, so use this instead.
+    pub fn custom_sudo(&mut self, msg: OsmosisSudo) -> AnyResult<AppResponse> {
+        let block = self.block_info();
+        self.init_modules(|router, api, storage| {
+            let router = &*router;
+            router.custom.sudo(api, storage, router, &block, msg)
+        })
+    }
+
+    /// Seeds the price observations (Unix time millisecond, price) between two assets of `pool_id`,
+    /// which the TWAP queries then average over instead of the spot price. Each price holds until
+    /// the next one. Queries for the reversed pair average over the inverted prices.
+    pub fn set_twap_history(
+        &mut self,
+        pool_id: u64,
+        quote_asset_denom: &str,
+        base_asset_denom: &str,
+        history: Vec<(i64, Decimal)>,
+    ) {
+        self.init_modules(|router, _, storage| {
+            router.custom.set_twap_history(
+                storage,
+                pool_id,
+                quote_asset_denom,
+                base_asset_denom,
+                history,
+            )
+        })
+        .expect("setting the twap history failed");
+    }
+
+    /// Simple iterator when you don't care too much about the details and just want to
+    /// simulate forward motion.
+    pub fn next_block(&mut self) {
+        self.advance_blocks(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::MOCK_CONTRACT_ADDR;
+    use cosmwasm_std::QuerierWrapper;
+    use cosmwasm_std::{coin, from_slice, Uint128};
+    use cw_multi_test::Executor;
+    use osmo_bindings::{OsmosisQuerier, Step, Swap};
+
+    #[test]
+    fn mint_token() {
+        let contract = Addr::unchecked("govner");
+        let rcpt = Addr::unchecked("townies");
+        let subdenom = "fundz";
+
+        let mut app = OsmosisApp::new();
+
+        // no tokens
+        let start = app.wrap().query_all_balances(rcpt.as_str()).unwrap();
+        assert_eq!(start, vec![]);
+
+        // let's find the mapping
+        let FullDenomResponse { denom } = app
+            .wrap()
+            .query(
+                &OsmosisQuery::FullDenom {
+                    creator_addr: contract.to_string(),
+                    subdenom: subdenom.to_string(),
+                }
+                .into(),
+            )
+            .unwrap();
+        assert_ne!(denom, subdenom);
+        assert!(denom.len() > 10);
+
+        // prepare to mint
+        let amount = Uint128::new(1234567);
+        let msg = OsmosisMsg::MintTokens {
+            denom: denom.to_string(),
+            amount,
+            mint_to_address: rcpt.to_string(),
+        };
+
+        // the denom must be created first
+        let err = app
+            .execute(contract.clone(), msg.clone().into())
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::DenomDoesNotExist(denom.clone())
+        );
+        let create = OsmosisMsg::CreateDenom {
+            subdenom: subdenom.to_string(),
+        };
+        app.execute(contract.clone(), create.into()).unwrap();
+
+        // simulate contract calling
+        app.execute(contract, msg.into()).unwrap();
+
+        // we got tokens!
+        let end = app.wrap().query_balance(rcpt.as_str(), &denom).unwrap();
+        let expected = Coin { denom, amount };
+        assert_eq!(end, expected);
+
+        // but no minting of unprefixed version
+        let empty = app.wrap().query_balance(rcpt.as_str(), subdenom).unwrap();
+        assert_eq!(empty.amount, Uint128::zero());
+    }
+
+    #[test]
+    fn mint_zero_tokens() {
+        let contract = Addr::unchecked("govner");
+
+        let mut app = OsmosisApp::new();
+        let create = OsmosisMsg::CreateDenom {
+            subdenom: "fundz".to_string(),
+        };
+        let res = app.execute(contract.clone(), create.into()).unwrap();
+        let FullDenomResponse { denom } = from_slice(res.data.unwrap().as_slice()).unwrap();
+
+        let msg = OsmosisMsg::MintTokens {
+            denom,
+            amount: Uint128::zero(),
+            mint_to_address: contract.to_string(),
+        };
+        let err = app.execute(contract, msg.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::ZeroAmount
+        );
+    }
+
+    #[test]
+    fn create_denom_subdenom_charset() {
+        let creator = Addr::unchecked("creator");
+        let mut app = OsmosisApp::new();
+
+        for subdenom in ["my token", "tökén", "coin$", "under_score"] {
+            let msg = OsmosisMsg::create_denom(subdenom);
+            let err = app.execute(creator.clone(), msg.into()).unwrap_err();
+            assert_eq!(
+                err.downcast::<ContractError>().unwrap(),
+                ContractError::InvalidSubdenom {
+                    subdenom: subdenom.to_string()
+                }
+            );
+        }
+
+        for subdenom in ["ustake", "lp.v2", "nested/path/Token9", ""] {
+            let msg = OsmosisMsg::create_denom(subdenom);
+            let res = app.execute(creator.clone(), msg.into()).unwrap();
+            let FullDenomResponse { denom } = from_slice(res.data.unwrap().as_slice()).unwrap();
+            assert_eq!(denom, format!("factory/creator/{}", subdenom));
+        }
+    }
+
+    #[test]
+    fn mint_tokens_batch() {
+        let contract = Addr::unchecked("govner");
+        let rcpts = ["alice", "bob", "carl"];
+
+        let mut app = OsmosisApp::new();
+        let create = OsmosisMsg::CreateDenom {
+            subdenom: "airdrop".to_string(),
+        };
+        let res = app.execute(contract.clone(), create.into()).unwrap();
+        let FullDenomResponse { denom } = from_slice(&res.data.unwrap()).unwrap();
+
+        let mints = rcpts
+            .iter()
+            .zip(1u128..)
+            .map(|(rcpt, amount)| (rcpt.to_string(), Uint128::new(amount * 1000)))
+            .collect();
+        let msg = OsmosisMsg::MintTokensBatch {
+            denom: denom.clone(),
+            mints,
+        };
+        app.execute(contract.clone(), msg.into()).unwrap();
+
+        for (rcpt, amount) in rcpts.iter().zip(1u128..) {
+            let balance = app.wrap().query_balance(*rcpt, &denom).unwrap();
+            assert_eq!(balance, coin(amount * 1000, &denom));
+        }
+
+        // a single zero amount rejects the whole batch
+        let msg = OsmosisMsg::MintTokensBatch {
+            denom: denom.clone(),
+            mints: vec![
+                ("alice".to_string(), Uint128::new(5)),
+                ("bob".to_string(), Uint128::zero()),
+            ],
+        };
+        let err = app.execute(contract.clone(), msg.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::ZeroAmount
+        );
+
+        // as does an empty one
+        let msg = OsmosisMsg::MintTokensBatch {
+            denom,
+            mints: vec![],
+        };
+        let err = app.execute(contract, msg.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::EmptyMintBatch
+        );
+    }
+
+    #[test]
+    fn query_denoms_by_creator() {
+        let creator = Addr::unchecked("creator");
+        let other = Addr::unchecked("other");
+
+        let mut app = OsmosisApp::new();
+        for subdenom in ["zeta", "alpha"] {
+            let create = OsmosisMsg::CreateDenom {
+                subdenom: subdenom.to_string(),
+            };
+            app.execute(creator.clone(), create.into()).unwrap();
+        }
+        let create = OsmosisMsg::CreateDenom {
+            subdenom: "other".to_string(),
+        };
+        app.execute(other.clone(), create.into()).unwrap();
+
+        // handing over a denom does not change who created it
+        let change = OsmosisMsg::ChangeAdmin {
+            denom: "factory/creator/zeta".to_string(),
+            new_admin_address: other.to_string(),
+        };
+        app.execute(creator.clone(), change.into()).unwrap();
+
+        let querier = QuerierWrapper::<OsmosisQuery>::new(&app);
+        let querier = OsmosisQuerier::new(&querier);
+        let DenomsByCreatorResponse { denoms } =
+            querier.denoms_by_creator(creator.to_string()).unwrap();
+        assert_eq!(
+            denoms,
+            vec![
+                "factory/creator/alpha".to_string(),
+                "factory/creator/zeta".to_string()
+            ]
+        );
+        let DenomsByCreatorResponse { denoms } =
+            querier.denoms_by_creator(other.to_string()).unwrap();
+        assert_eq!(denoms, vec!["factory/other/other".to_string()]);
+        let DenomsByCreatorResponse { denoms } =
+            querier.denoms_by_creator("nobody".to_string()).unwrap();
+        assert_eq!(denoms, Vec::<String>::new());
+    }
+
+    #[test]
+    fn created_denoms() {
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
+
+        let mut app = OsmosisApp::new();
+        assert_eq!(app.created_denoms(), Vec::<(Addr, String)>::new());
+
+        app.execute(bob.clone(), OsmosisMsg::create_denom("fundz").into())
+            .unwrap();
+        app.execute(
+            alice.clone(),
+            OsmosisMsg::create_denom("nested/coin").into(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            app.created_denoms(),
+            vec![
+                (alice, "factory/alice/nested/coin".to_string()),
+                (bob, "factory/bob/fundz".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn is_factory_denom_requires_creation() {
+        let creator = Addr::unchecked("creator");
+
+        let mut app = OsmosisApp::new();
+        let create = OsmosisMsg::CreateDenom {
+            subdenom: "fundz".to_string(),
+        };
+        let res = app.execute(creator, create.into()).unwrap();
+        let FullDenomResponse { denom } = from_slice(res.data.unwrap().as_slice()).unwrap();
+
+        let wrapper = QuerierWrapper::<OsmosisQuery>::new(&*app);
+        let querier = OsmosisQuerier::new(&wrapper);
+        let IsFactoryDenomResponse { is_factory } = querier.is_factory_denom(denom).unwrap();
+        assert!(is_factory);
+
+        // well-formed, but never created
+        let lookalike = "factory/creator/never".to_string();
+        assert!(is_factory_denom(&lookalike));
+        let IsFactoryDenomResponse { is_factory } = querier.is_factory_denom(lookalike).unwrap();
+        assert!(!is_factory);
+
+        let IsFactoryDenomResponse { is_factory } =
+            querier.is_factory_denom("uosmo".to_string()).unwrap();
+        assert!(!is_factory);
+    }
+
+    #[test]
+    fn change_admin() {
+        let creator = Addr::unchecked("creator");
+        let new_admin = Addr::unchecked("new_admin");
+        let rcpt = Addr::unchecked("rcpt");
+
+        let mut app = OsmosisApp::new();
+        let create = OsmosisMsg::CreateDenom {
+            subdenom: "fundz".to_string(),
+        };
+        let res = app.execute(creator.clone(), create.into()).unwrap();
+        let FullDenomResponse { denom } = from_slice(res.data.unwrap().as_slice()).unwrap();
+        let mint =
+            OsmosisMsg::mint_contract_tokens(denom.clone(), Uint128::new(100), rcpt.to_string());
+
+        // the creator starts as admin
+        let query = OsmosisQuery::DenomAdmin {
+            denom: denom.clone(),
+        };
+        let DenomAdminResponse { admin } = app.wrap().query(&query.clone().into()).unwrap();
+        assert_eq!(admin, creator.as_str());
+
+        // only the admin may hand over the denom
+        let change = OsmosisMsg::ChangeAdmin {
+            denom: denom.clone(),
+            new_admin_address: new_admin.to_string(),
+        };
+        let err = app
+            .execute(new_admin.clone(), change.clone().into())
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::Unauthorized
+        );
+        app.execute(creator.clone(), change.into()).unwrap();
+        let DenomAdminResponse { admin } = app.wrap().query(&query.clone().into()).unwrap();
+        assert_eq!(admin, new_admin.as_str());
+
+        // the old admin can no longer mint, the new one can
+        let err = app
+            .execute(creator.clone(), mint.clone().into())
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::Unauthorized
+        );
+        app.execute(new_admin.clone(), mint.clone().into()).unwrap();
+        let balance = app.wrap().query_balance(&rcpt, &denom).unwrap();
+        assert_eq!(balance.amount, Uint128::new(100));
+
+        // clearing the admin locks the supply for good
+        let clear = OsmosisMsg::ChangeAdmin {
+            denom,
+            new_admin_address: String::new(),
+        };
+        app.execute(new_admin.clone(), clear.into()).unwrap();
+        let DenomAdminResponse { admin } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(admin, "");
+        let err = app.execute(new_admin, mint.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::Unauthorized
+        );
+    }
+
+    #[test]
+    fn set_before_send_hook() {
+        let admin = Addr::unchecked("admin");
+
+        let mut app = OsmosisApp::new();
+        let create = OsmosisMsg::CreateDenom {
+            subdenom: "fundz".to_string(),
+        };
+        let res = app.execute(admin.clone(), create.into()).unwrap();
+        let FullDenomResponse { denom } = from_slice(res.data.unwrap().as_slice()).unwrap();
+        let query = OsmosisQuery::BeforeSendHook {
+            denom: denom.clone(),
+        };
+
+        // no hook to start with
+        let BeforeSendHookResponse { cosmwasm_address } =
+            app.wrap().query(&query.clone().into()).unwrap();
+        assert_eq!(cosmwasm_address, "");
+
+        let msg = OsmosisMsg::SetBeforeSendHook {
+            denom: denom.clone(),
+            cosmwasm_address: "hook".to_string(),
+        };
+        app.execute(admin.clone(), msg.into()).unwrap();
+        let BeforeSendHookResponse { cosmwasm_address } =
+            app.wrap().query(&query.clone().into()).unwrap();
+        assert_eq!(cosmwasm_address, "hook");
+
+        // the address must be valid
+        let msg = OsmosisMsg::SetBeforeSendHook {
+            denom: denom.clone(),
+            cosmwasm_address: "x".to_string(),
+        };
+        app.execute(admin.clone(), msg.into()).unwrap_err();
+
+        // and an empty one removes the hook
+        let msg = OsmosisMsg::SetBeforeSendHook {
+            denom,
+            cosmwasm_address: String::new(),
+        };
+        app.execute(admin, msg.into()).unwrap();
+        let BeforeSendHookResponse { cosmwasm_address } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(cosmwasm_address, "");
+    }
+
+    #[test]
+    fn set_before_send_hook_unauthorized() {
+        let admin = Addr::unchecked("admin");
+
+        let mut app = OsmosisApp::new();
+        let create = OsmosisMsg::CreateDenom {
+            subdenom: "fundz".to_string(),
+        };
+        let res = app.execute(admin, create.into()).unwrap();
+        let FullDenomResponse { denom } = from_slice(res.data.unwrap().as_slice()).unwrap();
+
+        let msg = OsmosisMsg::SetBeforeSendHook {
+            denom: denom.clone(),
+            cosmwasm_address: "hook".to_string(),
+        };
+        let err = app
+            .execute(Addr::unchecked("intruder"), msg.into())
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::Unauthorized
+        );
+        let query = OsmosisQuery::BeforeSendHook { denom };
+        let BeforeSendHookResponse { cosmwasm_address } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(cosmwasm_address, "");
+    }
+
+    #[test]
+    fn force_transfer() {
+        let admin = Addr::unchecked("admin");
+        let holder = Addr::unchecked("holder");
+        let rcpt = Addr::unchecked("rcpt");
+
+        let mut app = OsmosisApp::new();
+        let create = OsmosisMsg::CreateDenom {
+            subdenom: "fundz".to_string(),
+        };
+        let res = app.execute(admin.clone(), create.into()).unwrap();
+        let FullDenomResponse { denom } = from_slice(res.data.unwrap().as_slice()).unwrap();
+        let mint =
+            OsmosisMsg::mint_contract_tokens(denom.clone(), Uint128::new(500), holder.to_string());
+        app.execute(admin.clone(), mint.into()).unwrap();
+
+        // the admin takes 300 tokens from the holder
+        let transfer = OsmosisMsg::ForceTransfer {
+            denom: denom.clone(),
+            amount: Uint128::new(300),
+            from_address: holder.to_string(),
+            to_address: rcpt.to_string(),
+        };
+        app.execute(admin.clone(), transfer.clone().into()).unwrap();
+        let balance = app.wrap().query_balance(&holder, &denom).unwrap();
+        assert_eq!(balance.amount, Uint128::new(200));
+        let balance = app.wrap().query_balance(&rcpt, &denom).unwrap();
+        assert_eq!(balance.amount, Uint128::new(300));
+
+        // there are only 200 left to take
+        app.execute(admin, transfer.into()).unwrap_err();
+        let balance = app.wrap().query_balance(&holder, &denom).unwrap();
+        assert_eq!(balance.amount, Uint128::new(200));
+    }
+
+    #[test]
+    fn force_transfer_unauthorized() {
+        let admin = Addr::unchecked("admin");
+        let holder = Addr::unchecked("holder");
+
+        let mut app = OsmosisApp::new();
+        let create = OsmosisMsg::CreateDenom {
+            subdenom: "fundz".to_string(),
+        };
+        let res = app.execute(admin.clone(), create.into()).unwrap();
+        let FullDenomResponse { denom } = from_slice(res.data.unwrap().as_slice()).unwrap();
+        let mint =
+            OsmosisMsg::mint_contract_tokens(denom.clone(), Uint128::new(500), holder.to_string());
+        app.execute(admin, mint.into()).unwrap();
+
+        // not even the holder may force a transfer
+        let transfer = OsmosisMsg::ForceTransfer {
+            denom: denom.clone(),
+            amount: Uint128::new(300),
+            from_address: holder.to_string(),
+            to_address: "thief".to_string(),
+        };
+        let err = app.execute(holder.clone(), transfer.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::Unauthorized
+        );
+        let balance = app.wrap().query_balance(&holder, &denom).unwrap();
+        assert_eq!(balance.amount, Uint128::new(500));
+    }
+
+    #[test]
+    fn query_pool() {
+        let coin_a = coin(6_000_000u128, "osmo");
+        let coin_b = coin(1_500_000u128, "atom");
+        let pool_id = 43;
+        let pool = Pool::new(coin_a.clone(), coin_b.clone());
+
+        // set up with one pool
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, pool_id, &pool).unwrap();
+        });
+
+        // query the pool state
+        let query = OsmosisQuery::PoolState { id: pool_id }.into();
+        let state: PoolStateResponse = app.wrap().query(&query).unwrap();
+        let expected_shares = coin(3_000_000, "gamm/pool/43");
+        assert_eq!(state.shares, expected_shares);
+        assert_eq!(state.assets, vec![coin_a.clone(), coin_b.clone()]);
+
+        // check spot price both directions
+        let query = OsmosisQuery::spot_price(pool_id, &coin_a.denom, &coin_b.denom).into();
+        let SpotPriceResponse { price } = app.wrap().query(&query).unwrap();
+        assert_eq!(price, Decimal::percent(25));
+
+        // and atom -> osmo
+        let query = OsmosisQuery::spot_price(pool_id, &coin_b.denom, &coin_a.denom).into();
+        let SpotPriceResponse { price } = app.wrap().query(&query).unwrap();
+        assert_eq!(price, Decimal::percent(400));
+
+        // with fee
+        let query = OsmosisQuery::SpotPrice {
+            swap: Swap::new(pool_id, &coin_b.denom, &coin_a.denom),
+            with_swap_fee: true,
+        };
+        let SpotPriceResponse { price } = app.wrap().query(&query.into()).unwrap();
+        // 4.00 * (1- 0.3%) = 4 * 0.997 = 3.988
+        assert_eq!(price, Decimal::permille(3988));
+    }
+
+    #[test]
+    fn query_spot_price_both() {
+        let stable = Pool::new_stableswap(
+            vec![coin(1_000_000, "usdc"), coin(1_200_000, "usdt")],
+            vec![1, 1],
+            Decimal::permille(3),
+        )
+        .unwrap();
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            let balancer = Pool::new(coin(6_000_000, "osmo"), coin(1_500_000, "atom"));
+            router.custom.set_pool(storage, 1, &balancer).unwrap();
+            router.custom.set_pool(storage, 2, &stable).unwrap();
+        });
+
+        let query = |pool_id, denom_a: &str, denom_b: &str, with_swap_fee| {
+            let query = OsmosisQuery::SpotPriceBoth {
+                pool_id,
+                denom_a: denom_a.to_string(),
+                denom_b: denom_b.to_string(),
+                with_swap_fee,
+            };
+            app.wrap()
+                .query::<SpotPriceBothResponse>(&query.into())
+                .unwrap()
+        };
+
+        // without fees the two directions are inverse to each other
+        let SpotPriceBothResponse { a_per_b, b_per_a } = query(1, "osmo", "atom", false);
+        assert_eq!(a_per_b, Decimal::percent(400));
+        assert_eq!(b_per_a, Decimal::percent(25));
+        let SpotPriceBothResponse { a_per_b, b_per_a } = query(2, "usdc", "usdt", false);
+        let product = a_per_b * b_per_a;
+        let tolerance = Decimal::raw(1_000_000);
+        assert!(
+            product > Decimal::one() - tolerance && product < Decimal::one() + tolerance,
+            "{}",
+            product
+        );
+
+        // fees are taken in both directions
+        let SpotPriceBothResponse { a_per_b, b_per_a } = query(1, "osmo", "atom", true);
+        assert_eq!(a_per_b, Decimal::permille(3988));
+        // 0.25 * 0.997 = 0.24925
+        assert_eq!(b_per_a, Decimal::from_ratio(24_925u128, 100_000u128));
+    }
+
+    #[test]
+    fn query_spot_price_verbose() {
+        let pool_id = 43;
+        let pool = Pool::new(coin(6_000_000, "osmo"), coin(1_500_000, "atom"));
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, pool_id, &pool).unwrap();
+        });
+
+        // the pool's own fee by default, matching SpotPrice
+        let query = OsmosisQuery::SpotPriceVerbose {
+            swap: Swap::new(pool_id, "atom", "osmo"),
+            swap_fee: None,
+        };
+        let SpotPriceWithFeeResponse { price, swap_fee } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(swap_fee, pool.fee);
+        let query = OsmosisQuery::spot_price_of_kind(
+            pool_id,
+            "atom",
+            "osmo",
+            SpotPriceKind::EffectiveWithFee,
+        );
+        let SpotPriceResponse { price: expected } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(price, expected);
+
+        // or a custom one: 4.00 * (1 - 1%) = 3.96
+        let query = OsmosisQuery::SpotPriceVerbose {
+            swap: Swap::new(pool_id, "atom", "osmo"),
+            swap_fee: Some(Decimal::percent(1)),
+        };
+        let SpotPriceWithFeeResponse { price, swap_fee } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(swap_fee, Decimal::percent(1));
+        assert_eq!(price, Decimal::percent(396));
+    }
+
+    #[test]
+    fn spot_price_empty_pool() {
+        let pool_id = 7;
+        let pool = Pool::new(coin(0, "osmo"), coin(1_500_000, "atom"));
+
+        let err = pool
+            .spot_price("osmo", "atom", SpotPriceKind::Marginal)
+            .unwrap_err();
+        assert_eq!(err, OsmosisError::EmptyPool);
+        let err = pool
+            .spot_price("atom", "osmo", SpotPriceKind::Marginal)
+            .unwrap_err();
+        assert_eq!(err, OsmosisError::EmptyPool);
+
+        // the query reports it as an error rather than aborting
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, pool_id, &pool).unwrap();
+        });
+        let query = OsmosisQuery::spot_price(pool_id, "osmo", "atom");
+        let err = app
+            .wrap()
+            .query::<SpotPriceResponse>(&query.into())
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains(&OsmosisError::EmptyPool.to_string()));
+    }
+
+    #[test]
+    fn missing_pool_errors_name_the_id() {
+        let pool = Pool::new(coin(6_000_000, "osmo"), coin(1_500_000, "atom"));
+        let trader = Addr::unchecked("trader");
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, 1, &pool).unwrap();
+            router
+                .bank
+                .init_balance(storage, &trader, coins(5000, "osmo"))
+                .unwrap();
+        });
+
+        let expected = OsmosisError::PoolNotFound { id: 42 }.to_string();
+        assert_eq!(expected, "Pool 42 does not exist");
+        let queries = [
+            OsmosisQuery::PoolState { id: 42 },
+            OsmosisQuery::spot_price(42, "osmo", "atom"),
+            OsmosisQuery::estimate_swap(
+                MOCK_CONTRACT_ADDR,
+                42,
+                "osmo",
+                "atom",
+                SwapAmount::In(Uint128::new(1000)),
+            ),
+        ];
+        for query in queries {
+            let err = app
+                .wrap()
+                .query::<Binary>(&query.clone().into())
+                .unwrap_err();
+            assert!(err.to_string().contains(&expected), "{:?}: {}", query, err);
+        }
+
+        // a route through a missing pool fails the same way
+        let query = OsmosisQuery::estimate_route_swap(
+            MOCK_CONTRACT_ADDR,
+            Swap::new(1, "osmo", "atom"),
+            vec![Step::new(42, "btc")],
+            SwapAmount::In(Uint128::new(1000)),
+        );
+        let err = app.wrap().query::<Binary>(&query.into()).unwrap_err();
+        assert!(err.to_string().contains(&expected), "{}", err);
+
+        let swap = OsmosisMsg::swap_exact_in(42, coin(1000, "osmo"), "atom", Uint128::zero());
+        let err = app.execute(trader, swap.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::PoolNotFound { id: 42 }
+        );
+    }
+
+    #[test]
+    fn spot_price_after_fee_change() {
+        let pool = Pool::new(coin(6_000_000, "osmo"), coin(1_500_000, "atom"));
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| router.custom.set_pool(storage, 1, &pool))
+            .unwrap();
+
+        let spot_price = |app: &OsmosisApp| {
+            let query = OsmosisQuery::SpotPrice {
+                swap: Swap::new(1, "osmo", "atom"),
+                with_swap_fee: true,
+            };
+            let SpotPriceResponse { price } = app.wrap().query(&query.into()).unwrap();
+            price
+        };
+        assert_eq!(
+            spot_price(&app),
+            Decimal::percent(25) * Decimal::permille(997)
+        );
+
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool_fee(storage, 1, Decimal::percent(1))
+        })
+        .unwrap();
+        assert_eq!(
+            spot_price(&app),
+            Decimal::percent(25) * Decimal::percent(99)
+        );
+
+        // the reserves are untouched
+        let state: PoolStateResponse = app
+            .wrap()
+            .query(&OsmosisQuery::PoolState { id: 1 }.into())
+            .unwrap();
+        assert_eq!(state, pool.into_response(1));
+
+        let err = app
+            .init_modules(|router, _, storage| {
+                router.custom.set_pool_fee(storage, 2, Decimal::percent(1))
+            })
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::PoolNotFound { id: 2 }
+        );
+
+        // a fee of 100% or more would eat the whole input
+        for fee in [Decimal::one(), Decimal::percent(150)] {
+            let err = app
+                .init_modules(|router, _, storage| router.custom.set_pool_fee(storage, 1, fee))
+                .unwrap_err();
+            assert_eq!(
+                err.downcast::<OsmosisError>().unwrap(),
+                OsmosisError::InvalidSwapFee(fee)
+            );
+        }
+        assert_eq!(
+            spot_price(&app),
+            Decimal::percent(25) * Decimal::percent(99)
+        );
+    }
+
+    #[test]
+    fn spot_price_kinds() {
+        let pool_id = 43;
+        let mut pool = Pool::new(coin(6_000_000, "osmo"), coin(1_500_000, "atom"));
+        pool.fee = Decimal::percent(1);
+
+        // the marginal price is the plain reserve ratio, the effective one is net of the 1% fee
+        let marginal = pool
+            .spot_price("atom", "osmo", SpotPriceKind::Marginal)
+            .unwrap();
+        assert_eq!(marginal, Decimal::percent(400));
+        let effective = pool
+            .spot_price("atom", "osmo", SpotPriceKind::EffectiveWithFee)
+            .unwrap();
+        assert_eq!(effective, Decimal::percent(396));
+
+        // the boolean form maps onto the same kinds
+        assert_eq!(pool.spot_price("atom", "osmo", false).unwrap(), marginal);
+        assert_eq!(pool.spot_price("atom", "osmo", true).unwrap(), effective);
+
+        // and so does the query
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, pool_id, &pool).unwrap();
+        });
+        let query =
+            OsmosisQuery::spot_price_of_kind(pool_id, "atom", "osmo", SpotPriceKind::Marginal);
+        let SpotPriceResponse { price } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(price, marginal);
+        let query = OsmosisQuery::spot_price_of_kind(
+            pool_id,
+            "atom",
+            "osmo",
+            SpotPriceKind::EffectiveWithFee,
+        );
+        let SpotPriceResponse { price } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(price, effective);
+    }
+
+    #[test]
+    fn estimate_swap() {
+        let coin_a = coin(6_000_000u128, "osmo");
+        let coin_b = coin(1_500_000u128, "atom");
+        let pool_id = 43;
+        let pool = Pool::new(coin_a.clone(), coin_b.clone());
+
+        // set up with one pool
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, pool_id, &pool).unwrap();
+        });
+
+        // estimate the price (501505 * 0.997 = 500_000) after fees gone
+        let query = OsmosisQuery::estimate_swap(
+            MOCK_CONTRACT_ADDR,
+            pool_id,
+            &coin_b.denom,
+            &coin_a.denom,
+            SwapAmount::In(Uint128::new(501505)),
+        );
+        let SwapResponse { amount } = app.wrap().query(&query.into()).unwrap();
+        // 6M * 1.5M = 2M * 4.5M -> output = 1.5M
+        let expected = SwapAmount::Out(Uint128::new(1_500_000));
+        assert_eq!(amount, expected);
+
+        // now try the reverse query. we know what we need to pay to get 1.5M out
+        let query = OsmosisQuery::estimate_swap(
+            MOCK_CONTRACT_ADDR,
+            pool_id,
+            &coin_b.denom,
+            &coin_a.denom,
+            SwapAmount::Out(Uint128::new(1500000)),
+        );
+        let SwapResponse { amount } = app.wrap().query(&query.into()).unwrap();
+        let expected = SwapAmount::In(Uint128::new(501505));
+        assert_eq!(amount, expected);
+    }
+
+    #[test]
+    fn perform_swap() {
+        let coin_a = coin(6_000_000u128, "osmo");
+        let coin_b = coin(1_500_000u128, "atom");
+        let pool_id = 43;
+        let pool = Pool::new(coin_a.clone(), coin_b.clone());
+        let trader = Addr::unchecked("trader");
+
+        // set up with one pool
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, pool_id, &pool).unwrap();
+            router
+                .bank
+                .init_balance(storage, &trader, coins(800_000, &coin_b.denom))
+                .unwrap()
+        });
+
+        // check balance before
+        let Coin { amount, .. } = app.wrap().query_balance(&trader, &coin_a.denom).unwrap();
+        assert_eq!(amount, Uint128::new(0));
+        let Coin { amount, .. } = app.wrap().query_balance(&trader, &coin_b.denom).unwrap();
+        assert_eq!(amount, Uint128::new(800_000));
+
+        // this is too low a payment, will error
+        let msg = OsmosisMsg::simple_swap(
+            pool_id,
+            &coin_b.denom,
+            &coin_a.denom,
+            SwapAmountWithLimit::ExactOut {
+                output: Uint128::new(1_500_000),
+                max_input: Uint128::new(400_000),
+            },
+        );
+        let err = app.execute(trader.clone(), msg.into()).unwrap_err();
+        println!("{:?}", err);
+
+        // now a proper swap
+        let msg = OsmosisMsg::simple_swap(
+            pool_id,
+            &coin_b.denom,
+            &coin_a.denom,
+            SwapAmountWithLimit::ExactOut {
+                output: Uint128::new(1_500_000),
+                max_input: Uint128::new(600_000),
+            },
+        );
+        let res = app.execute(trader.clone(), msg.into()).unwrap();
+
+        // update balances (800_000 - 501_505 paid = 298_495)
+        let Coin { amount, .. } = app.wrap().query_balance(&trader, &coin_a.denom).unwrap();
+        assert_eq!(amount, Uint128::new(1_500_000));
+        let Coin { amount, .. } = app.wrap().query_balance(&trader, &coin_b.denom).unwrap();
+        assert_eq!(amount, Uint128::new(298_495));
+
+        // check the response contains proper value
+        let input = SwapResponse::from_data(&res.data).unwrap();
+        assert_eq!(input.amount, SwapAmount::In(Uint128::new(501_505)));
+
+        // check pool state properly updated with fees
+        let query = OsmosisQuery::PoolState { id: pool_id }.into();
+        let state: PoolStateResponse = app.wrap().query(&query).unwrap();
+        let expected_assets = vec![
+            coin(4_500_000, &coin_a.denom),
+            coin(2_001_505, &coin_b.denom),
+        ];
+        assert_eq!(state.assets, expected_assets);
+    }
+
+    #[test]
+    fn swap_with_route_max_input_exceeded() {
+        let pool1 = Pool::new(coin(6_000_000, "osmo"), coin(3_000_000, "atom"));
+        let pool2 = Pool::new(coin(2_000_000, "atom"), coin(1_000_000, "btc"));
+        let trader = Addr::unchecked("trader");
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, 1, &pool1).unwrap();
+            router.custom.set_pool(storage, 2, &pool2).unwrap();
+            router
+                .bank
+                .init_balance(storage, &trader, coins(5000, "osmo"))
+                .unwrap()
+        });
+
+        let msg = OsmosisMsg::Swap {
+            first: Swap {
+                pool_id: 1,
+                denom_in: "osmo".to_string(),
+                denom_out: "atom".to_string(),
+            },
+            route: vec![Step {
+                pool_id: 2,
+                denom_out: "btc".to_string(),
+            }],
+            amount: SwapAmountWithLimit::ExactOut {
+                output: Uint128::new(1000),
+                max_input: Uint128::new(4000),
+            },
+        };
+        let err = app.execute(trader, msg.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::MaxInputExceeded {
+                got: Uint128::new(4028),
+                max: Uint128::new(4000)
+            }
+        );
+    }
+
+    #[test]
+    fn swap_with_route_min_output_not_met() {
+        let pool1 = Pool::new(coin(6_000_000, "osmo"), coin(3_000_000, "atom"));
+        let pool2 = Pool::new(coin(2_000_000, "atom"), coin(1_000_000, "btc"));
+        let trader = Addr::unchecked("trader");
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, 1, &pool1).unwrap();
+            router.custom.set_pool(storage, 2, &pool2).unwrap();
+            router
+                .bank
+                .init_balance(storage, &trader, coins(5000, "osmo"))
+                .unwrap()
+        });
+
+        let msg = OsmosisMsg::Swap {
+            first: Swap {
+                pool_id: 1,
+                denom_in: "osmo".to_string(),
+                denom_out: "atom".to_string(),
+            },
+            route: vec![Step {
+                pool_id: 2,
+                denom_out: "btc".to_string(),
+            }],
+            amount: SwapAmountWithLimit::ExactIn {
+                input: Uint128::new(4000),
+                min_output: Uint128::new(1000),
+            },
+        };
+        let err = app.execute(trader, msg.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::MinOutputNotMet {
+                got: Uint128::new(993),
+                min: Uint128::new(1000)
+            }
+        );
+    }
+
+    #[test]
+    fn swap_with_route_wrong_denom() {
+        let pool1 = Pool::new(coin(6_000_000, "osmo"), coin(3_000_000, "atom"));
+        let pool2 = Pool::new(coin(2_000_000, "atom"), coin(1_000_000, "eth"));
+        let trader = Addr::unchecked("trader");
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, 1, &pool1).unwrap();
+            router.custom.set_pool(storage, 2, &pool2).unwrap();
+            router
+                .bank
+                .init_balance(storage, &trader, coins(5000, "osmo"))
+                .unwrap()
+        });
+
+        let msg = OsmosisMsg::Swap {
+            first: Swap {
+                pool_id: 1,
+                denom_in: "osmo".to_string(),
+                denom_out: "atom".to_string(),
+            },
+            route: vec![Step {
+                pool_id: 2,
+                denom_out: "btc".to_string(),
+            }],
+            amount: SwapAmountWithLimit::ExactOut {
+                output: Uint128::new(1000),
+                max_input: Uint128::new(4000),
+            },
+        };
+        let err = app.execute(trader, msg.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::DenomNotInPool {
+                denom: "btc".to_string(),
+                pool_id: 2
+            }
+        );
+    }
+
+    #[test]
+    fn broken_route_leaves_pools_untouched() {
+        let pool1 = Pool::new(coin(6_000_000, "osmo"), coin(3_000_000, "atom"));
+        let pool2 = Pool::new(coin(2_000_000, "atom"), coin(1_000_000, "eth"));
+        let pool3 = Pool::new(coin(1_000_000, "btc"), coin(5_000_000, "osmo"));
+        let trader = Addr::unchecked("trader");
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, 1, &pool1).unwrap();
+            router.custom.set_pool(storage, 2, &pool2).unwrap();
+            router.custom.set_pool(storage, 3, &pool3).unwrap();
+            router
+                .bank
+                .init_balance(storage, &trader, coins(5000, "osmo"))
+                .unwrap()
+        });
+
+        // osmo -> atom -> eth is fine, but pool 3 has no eth to sell into
+        let msg = OsmosisMsg::Swap {
+            first: Swap::new(1, "osmo", "atom"),
+            route: vec![Step::new(2, "eth"), Step::new(3, "btc")],
+            amount: SwapAmountWithLimit::ExactIn {
+                input: Uint128::new(1000),
+                min_output: Uint128::new(1),
+            },
+        };
+        let err = app.execute(trader.clone(), msg.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::DenomNotInPool {
+                denom: "eth".to_string(),
+                pool_id: 3
+            }
+        );
+
+        for (id, pool) in [(1, pool1), (2, pool2), (3, pool3)] {
+            let query = OsmosisQuery::PoolState { id }.into();
+            let state: PoolStateResponse = app.wrap().query(&query).unwrap();
+            assert_eq!(state, pool.into_response(id));
+        }
+        let balance = app.wrap().query_balance(&trader, "osmo").unwrap();
+        assert_eq!(balance.amount, Uint128::new(5000));
+    }
+
+    #[test]
+    fn estimate_swap_wrong_denom_error_message() {
+        let pool = Pool::new(coin(6_000_000, "osmo"), coin(3_000_000, "atom"));
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, 1, &pool).unwrap();
+        });
+
+        let query = OsmosisQuery::estimate_swap(
+            MOCK_CONTRACT_ADDR,
+            1,
+            "osmo",
+            "btc",
+            SwapAmount::In(Uint128::new(1000)),
+        );
+        let err = QuerierWrapper::<OsmosisQuery>::new(&*app)
+            .query::<SwapResponse>(&query.into())
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("asset btc not in pool 1"),
+            "unexpected error: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn perform_swap_with_route_exact_out() {
+        let pool1 = Pool::new(coin(6_000_000, "osmo"), coin(3_000_000, "atom"));
+        let pool2 = Pool::new(coin(2_000_000, "atom"), coin(1_000_000, "btc"));
+        let trader = Addr::unchecked("trader");
+
+        // set up pools
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, 1, &pool1).unwrap();
+            router.custom.set_pool(storage, 2, &pool2).unwrap();
+            router
+                .bank
+                .init_balance(storage, &trader, coins(5000, "osmo"))
+                .unwrap()
+        });
+
+        let msg = OsmosisMsg::Swap {
+            first: Swap {
+                pool_id: 1,
+                denom_in: "osmo".to_string(),
+                denom_out: "atom".to_string(),
+            },
+            route: vec![Step {
+                pool_id: 2,
+                denom_out: "btc".to_string(),
+            }],
+            amount: SwapAmountWithLimit::ExactOut {
+                output: Uint128::new(1000),
+                max_input: Uint128::new(5000),
+            },
+        };
+        let res = app.execute(trader.clone(), msg.into()).unwrap();
+
+        let Coin { amount, .. } = app.wrap().query_balance(&trader, "osmo").unwrap();
+        assert_eq!(amount, Uint128::new(5000 - 4028));
+        let Coin { amount, .. } = app.wrap().query_balance(&trader, "btc").unwrap();
+        assert_eq!(amount, Uint128::new(1000));
+
+        // check the response contains proper value
+        let input: SwapResponse = from_slice(res.data.unwrap().as_slice()).unwrap();
+        assert_eq!(input.amount, SwapAmount::In(Uint128::new(4028)));
+
+        // check pool state properly updated with fees
+        let query = OsmosisQuery::PoolState { id: 1 }.into();
+        let state: PoolStateResponse = app.wrap().query(&query).unwrap();
+        let expected_assets = vec![
+            coin(6_000_000 + 4028, "osmo"),
+            coin(3_000_000 - 2007, "atom"),
+        ];
+        assert_eq!(state.assets, expected_assets);
+
+        let query = OsmosisQuery::PoolState { id: 2 }.into();
+        let state: PoolStateResponse = app.wrap().query(&query).unwrap();
+        let expected_assets = vec![
+            coin(2_000_000 + 2007, "atom"),
+            coin(1_000_000 - 1000, "btc"),
+        ];
+        assert_eq!(state.assets, expected_assets);
+    }
+
+    #[test]
+    fn perform_swap_with_route_exact_in() {
+        let pool1 = Pool::new(coin(6_000_000, "osmo"), coin(3_000_000, "atom"));
+        let pool2 = Pool::new(coin(2_000_000, "atom"), coin(1_000_000, "btc"));
+        let trader = Addr::unchecked("trader");
+
+        // set up pools
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, 1, &pool1).unwrap();
+            router.custom.set_pool(storage, 2, &pool2).unwrap();
+            router
+                .bank
+                .init_balance(storage, &trader, coins(5000, "osmo"))
+                .unwrap()
+        });
+
+        // now a proper swap
+        let msg = OsmosisMsg::Swap {
+            first: Swap {
+                pool_id: 1,
+                denom_in: "osmo".to_string(),
+                denom_out: "atom".to_string(),
+            },
+            route: vec![Step {
+                pool_id: 2,
+                denom_out: "btc".to_string(),
+            }],
+            amount: SwapAmountWithLimit::ExactIn {
+                input: Uint128::new(4000),
+                min_output: Uint128::new(900),
+            },
+        };
+        let res = app.execute(trader.clone(), msg.into()).unwrap();
+
+        let Coin { amount, .. } = app.wrap().query_balance(&trader, "osmo").unwrap();
+        assert_eq!(amount, Uint128::new(5000 - 4000));
+        let Coin { amount, .. } = app.wrap().query_balance(&trader, "btc").unwrap();
+        assert_eq!(amount, Uint128::new(993));
+
+        // check the response contains proper value
+        let input: SwapResponse = from_slice(res.data.unwrap().as_slice()).unwrap();
+        assert_eq!(input.amount, SwapAmount::Out(Uint128::new(993)));
+
+        // check pool state properly updated with fees
+        let query = OsmosisQuery::PoolState { id: 1 }.into();
+        let state: PoolStateResponse = app.wrap().query(&query).unwrap();
+        let expected_assets = vec![
+            coin(6_000_000 + 4000, "osmo"),
+            coin(3_000_000 - 1993, "atom"),
+        ];
+        assert_eq!(state.assets, expected_assets);
+
+        let query = OsmosisQuery::PoolState { id: 2 }.into();
+        let state: PoolStateResponse = app.wrap().query(&query).unwrap();
+        let expected_assets = vec![coin(2_000_000 + 1993, "atom"), coin(1_000_000 - 993, "btc")];
+        assert_eq!(state.assets, expected_assets);
+    }
+
+    #[test]
+    fn split_route_swap_beats_single_route() {
+        let pool = Pool::new(coin(6_000_000, "osmo"), coin(3_000_000, "atom"));
+        let trader = Addr::unchecked("trader");
+
+        let setup = || {
+            let mut app = OsmosisApp::new();
+            app.init_modules(|router, _, storage| {
+                router.custom.set_pool(storage, 1, &pool).unwrap();
+                router.custom.set_pool(storage, 2, &pool).unwrap();
+                let btc = Pool::new(coin(2_000_000, "atom"), coin(1_000_000, "btc"));
+                router.custom.set_pool(storage, 3, &btc).unwrap();
+                router
+                    .bank
+                    .init_balance(storage, &trader, coins(400_000, "osmo"))
+                    .unwrap()
+            });
+            app
+        };
+
+        // a single route pays the full price impact on one pool
+        let mut app = setup();
+        let single = OsmosisMsg::simple_swap(
+            1,
+            "osmo",
+            "atom",
+            SwapAmountWithLimit::ExactIn {
+                input: Uint128::new(400_000),
+                min_output: Uint128::zero(),
+            },
+        );
+        app.execute(trader.clone(), single.into()).unwrap();
+        let single_out = app.wrap().query_balance(&trader, "atom").unwrap().amount;
+
+        // splitting it in half over two equal pools gets twice the output of a half-sized swap
+        let half_out = pool
+            .clone()
+            .swap("osmo", "atom", SwapAmount::In(Uint128::new(200_000)))
+            .unwrap()
+            .as_out();
+        let split = |token_out_min_amount: u128| OsmosisMsg::SplitRouteSwapExactAmountIn {
+            routes: vec![
+                SwapRoute::new(vec![Step::new(1, "atom")], 200_000u128),
+                SwapRoute::new(vec![Step::new(2, "atom")], 200_000u128),
+            ],
+            token_in_denom: "osmo".to_string(),
+            token_out_min_amount: Uint128::new(token_out_min_amount),
+        };
+
+        // the minimum applies to the sum of all routes
+        let mut app = setup();
+        let err = app
+            .execute(trader.clone(), split(half_out.u128() * 2 + 1).into())
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::MinOutputNotMet {
+                got: half_out * Uint128::new(2),
+                min: half_out * Uint128::new(2) + Uint128::new(1),
+            }
+        );
+
+        let res = app
+            .execute(trader.clone(), split(half_out.u128() * 2).into())
+            .unwrap();
+        let SwapResponse { amount } = from_slice(res.data.unwrap().as_slice()).unwrap();
+        assert_eq!(amount, SwapAmount::Out(half_out * Uint128::new(2)));
+
+        let split_out = app.wrap().query_balance(&trader, "atom").unwrap().amount;
+        assert_eq!(split_out, half_out * Uint128::new(2));
+        assert!(split_out > single_out);
+        let osmo = app.wrap().query_balance(&trader, "osmo").unwrap().amount;
+        assert_eq!(osmo, Uint128::zero());
+
+        // both pools moved
+        for id in [1, 2] {
+            let state: PoolStateResponse = app
+                .wrap()
+                .query(&OsmosisQuery::PoolState { id }.into())
+                .unwrap();
+            assert_eq!(state.assets[0], coin(6_200_000, "osmo"));
+        }
+
+        // routes must agree on the output denom
+        let mismatch = OsmosisMsg::SplitRouteSwapExactAmountIn {
+            routes: vec![
+                SwapRoute::new(vec![Step::new(1, "atom")], 100u128),
+                SwapRoute::new(vec![Step::new(2, "atom"), Step::new(3, "btc")], 100u128),
+            ],
+            token_in_denom: "osmo".to_string(),
+            token_out_min_amount: Uint128::zero(),
+        };
+        let err = setup().execute(trader, mismatch.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::SplitRouteDenomMismatch {
+                expected: "atom".to_string(),
+                got: "btc".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn swap_with_huge_reserves() {
+        let reserve = u128::MAX / 2;
+        let mut pool = Pool::new(coin(reserve, "osmo"), coin(reserve, "atom"));
+        assert_eq!(pool.shares, Uint128::new(reserve));
+
+        // with reserves this deep there's no price impact, only the 0.3% fee
+        let payout = pool
+            .swap("osmo", "atom", SwapAmount::In(Uint128::new(1_000_000)))
+            .unwrap();
+        assert_eq!(payout, SwapAmount::Out(Uint128::new(997_000)));
+        let payin = pool
+            .swap("osmo", "atom", SwapAmount::Out(Uint128::new(997_000)))
+            .unwrap();
+        assert_eq!(payin, SwapAmount::In(Uint128::new(1_000_000)));
+        assert_eq!(
+            pool.assets,
+            vec![
+                coin(reserve + 2_000_000, "osmo"),
+                coin(reserve - 1_994_000, "atom")
+            ]
+        );
+
+        // a result that can't fit in a Uint128 is still an error, not a panic
+        let err = pool
+            .swap(
+                "osmo",
+                "atom",
+                SwapAmount::Out(Uint128::new(reserve - 1_994_000)),
+            )
+            .unwrap_err();
+        assert!(
+            matches!(err, OsmosisError::ConversionOverflow(_)),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn round_trip_route_only_estimates() {
+        // atom is cheaper in pool 1 (2 osmo) than in pool 2 (3 osmo)
+        let pool1 = Pool::new(coin(6_000_000, "osmo"), coin(3_000_000, "atom"));
+        let pool2 = Pool::new(coin(1_000_000, "atom"), coin(3_000_000, "osmo"));
+        let trader = Addr::unchecked("trader");
+
+        // set up pools
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, 1, &pool1).unwrap();
+            router.custom.set_pool(storage, 2, &pool2).unwrap();
+            router
+                .bank
+                .init_balance(storage, &trader, coins(10_000, "osmo"))
+                .unwrap()
+        });
+
+        // buying atom on pool 1 and selling it on pool 2 can be estimated:
+        // 10_000 osmo -> 4977 atom -> 14_813 osmo, after fees and price impact on both pools
+        let query = OsmosisQuery::estimate_route_swap(
+            MOCK_CONTRACT_ADDR,
+            Swap::new(1, "osmo", "atom"),
+            vec![Step::new(2, "osmo")],
+            SwapAmount::In(Uint128::new(10_000)),
+        );
+        let SwapResponse { amount } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(amount, SwapAmount::Out(Uint128::new(14_813)));
+
+        // but executing a route back to the starting denom is rejected
+        let msg = OsmosisMsg::Swap {
+            first: Swap::new(1, "osmo", "atom"),
+            route: vec![Step::new(2, "osmo")],
+            amount: SwapAmountWithLimit::ExactIn {
+                input: Uint128::new(10_000),
+                min_output: Uint128::new(10_000),
+            },
+        };
+        let err = app.execute(trader.clone(), msg.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::InvalidRoute("osmo".to_string())
+        );
+
+        // leaving balances and pools untouched
+        let balances = app.wrap().query_all_balances(&trader).unwrap();
+        assert_eq!(balances, coins(10_000, "osmo"));
+        for (id, pool) in [(1, pool1), (2, pool2)] {
+            let query = OsmosisQuery::PoolState { id }.into();
+            let state: PoolStateResponse = app.wrap().query(&query).unwrap();
+            assert_eq!(state, pool.into_response(id));
+        }
+    }
+
+    #[test]
+    fn swap_with_route_same_denom_hop() {
+        let pool1 = Pool::new(coin(6_000_000, "osmo"), coin(3_000_000, "atom"));
+        let pool2 = Pool::new(coin(1_000_000, "atom"), coin(3_000_000, "osmo"));
+        let trader = Addr::unchecked("trader");
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, 1, &pool1).unwrap();
+            router.custom.set_pool(storage, 2, &pool2).unwrap();
+            router
+                .bank
+                .init_balance(storage, &trader, coins(10_000, "osmo"))
+                .unwrap()
+        });
+
+        // second hop would swap atom for atom
+        let msg = OsmosisMsg::Swap {
+            first: Swap::new(1, "osmo", "atom"),
+            route: vec![Step::new(2, "atom")],
+            amount: SwapAmountWithLimit::ExactIn {
+                input: Uint128::new(10_000),
+                min_output: Uint128::new(1),
+            },
+        };
+        let err = app.execute(trader, msg.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::SameDenomSwap("atom".to_string())
+        );
+    }
+
+    #[test]
+    fn exact_out_beyond_intermediate_liquidity() {
+        let pool1 = Pool::new(coin(6_000_000, "osmo"), coin(3_000, "atom"));
+        let pool2 = Pool::new(coin(2_000_000, "atom"), coin(1_000_000, "btc"));
+        let trader = Addr::unchecked("trader");
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, 1, &pool1).unwrap();
+            router.custom.set_pool(storage, 2, &pool2).unwrap();
+            router
+                .bank
+                .init_balance(storage, &trader, coins(6_000_000, "osmo"))
+                .unwrap()
+        });
+
+        // 5000 btc need more than 5000 atom from the 3000 atom pool 1
+        let first = Swap::new(1, "osmo", "atom");
+        let route = vec![Step::new(2, "btc")];
+        let query = OsmosisQuery::estimate_route_swap(
+            MOCK_CONTRACT_ADDR,
+            first.clone(),
+            route.clone(),
+            SwapAmount::Out(Uint128::new(5_000)),
+        );
+        let err = app.wrap().query::<SwapResponse>(&query.into()).unwrap_err();
+        let expected = OsmosisError::InsufficientPoolLiquidity { pool_id: 1 };
+        assert!(err.to_string().contains(&expected.to_string()), "{}", err);
+
+        let msg = OsmosisMsg::Swap {
+            first,
+            route,
+            amount: SwapAmountWithLimit::ExactOut {
+                output: Uint128::new(5_000),
+                max_input: Uint128::new(6_000_000),
+            },
+        };
+        let err = app.execute(trader, msg.into()).unwrap_err();
+        assert_eq!(err.downcast::<OsmosisError>().unwrap(), expected);
+
+        // the last pool is checked as well
+        let query = OsmosisQuery::estimate_swap(
+            MOCK_CONTRACT_ADDR,
+            2,
+            "atom",
+            "btc",
+            SwapAmount::Out(Uint128::new(1_000_000)),
+        );
+        let err = app.wrap().query::<SwapResponse>(&query.into()).unwrap_err();
+        let expected = OsmosisError::InsufficientPoolLiquidity { pool_id: 2 };
+        assert!(err.to_string().contains(&expected.to_string()), "{}", err);
+    }
+
+    #[test]
+    fn simple_swap_rejects_invalid_denoms() {
+        let pool = Pool::new(coin(6_000_000, "osmo"), coin(3_000_000, "atom"));
+        let trader = Addr::unchecked("trader");
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, 1, &pool).unwrap();
+            router
+                .bank
+                .init_balance(storage, &trader, coins(10_000, "osmo"))
+                .unwrap()
+        });
+
+        let swap = OsmosisMsg::swap_exact_in(1, coin(10_000, "osmo"), "osmo", Uint128::zero());
+        let err = app.execute(trader.clone(), swap.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::SameDenomSwap("osmo".to_string())
+        );
+
+        let swap = OsmosisMsg::swap_exact_in(1, coin(10_000, "osmo"), "", Uint128::zero());
+        let err = app.execute(trader, swap.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::EmptyDenom
+        );
+
+        // estimates are validated the same way
+        for (denom_in, denom_out, expected) in [
+            (
+                "osmo",
+                "osmo",
+                OsmosisError::SameDenomSwap("osmo".to_string()),
+            ),
+            ("", "atom", OsmosisError::EmptyDenom),
+        ] {
+            let query = OsmosisQuery::estimate_swap(
+                MOCK_CONTRACT_ADDR,
+                1,
+                denom_in,
+                denom_out,
+                SwapAmount::In(Uint128::new(1000)),
+            );
+            let err = app.wrap().query::<SwapResponse>(&query.into()).unwrap_err();
+            assert!(err.to_string().contains(&expected.to_string()), "{}", err);
+        }
+    }
+
+    #[test]
+    fn join_pool_imbalanced() {
+        let pool_id = 43;
+        let pool = Pool::new(coin(6_000_000, "osmo"), coin(1_500_000, "atom"));
+        let provider = Addr::unchecked("provider");
+        let deposit = vec![coin(60_000, "osmo"), coin(50_000, "atom")];
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, pool_id, &pool).unwrap();
+            router
+                .bank
+                .init_balance(storage, &provider, deposit.clone())
+                .unwrap()
+        });
+
+        // 1% of the 3M shares would need 60_000 osmo, so asking for more is too expensive
+        let msg = OsmosisMsg::JoinPool {
+            pool_id,
+            share_out_amount: Uint128::new(40_000),
+            token_in_maxs: deposit.clone(),
+        };
+        let err = app.execute(provider.clone(), msg.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::JoinPoolMaxInputExceeded {
+                required: coin(80_000, "osmo"),
+                max: Uint128::new(60_000)
+            }
+        );
+
+        // 1% of the shares only needs 15_000 of the 50_000 atom on offer
+        let msg = OsmosisMsg::JoinPool {
+            pool_id,
+            share_out_amount: Uint128::new(30_000),
+            token_in_maxs: deposit.clone(),
+        };
+        let res = app.execute(provider.clone(), msg.into()).unwrap();
+        let JoinPoolResponse {
+            tokens_consumed,
+            shares_out,
+        } = from_slice(res.data.unwrap().as_slice()).unwrap();
+        assert_eq!(
+            tokens_consumed,
+            vec![coin(60_000, "osmo"), coin(15_000, "atom")]
+        );
+        assert_eq!(shares_out, Uint128::new(30_000));
+
+        // consumed + refunded adds up to what we offered
+        for (offered, consumed) in deposit.iter().zip(tokens_consumed.iter()) {
+            let refunded = app.wrap().query_balance(&provider, &offered.denom).unwrap();
+            assert_eq!(consumed.amount + refunded.amount, offered.amount);
+        }
+        let lp = app.wrap().query_balance(&provider, "gamm/pool/43").unwrap();
+        assert_eq!(lp.amount, shares_out);
+
+        // check pool state properly updated
+        let query = OsmosisQuery::PoolState { id: pool_id }.into();
+        let state: PoolStateResponse = app.wrap().query(&query).unwrap();
+        let expected_assets = vec![coin(6_060_000, "osmo"), coin(1_515_000, "atom")];
+        assert_eq!(state.assets, expected_assets);
+        assert_eq!(state.shares, coin(3_030_000, "gamm/pool/43"));
+    }
+
+    #[test]
+    fn join_pool_at_limiting_ratio() {
+        let pool_id = 43;
+        let pool = Pool::new(coin(6_000_000, "osmo"), coin(1_500_000, "atom"));
+        let provider = Addr::unchecked("provider");
+        // atom is offered far above the pool ratio of 4 osmo per atom
+        let deposit = vec![coin(120_000, "osmo"), coin(100_000, "atom")];
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, pool_id, &pool).unwrap();
+            router
+                .bank
+                .init_balance(storage, &provider, deposit.clone())
+                .unwrap()
+        });
+
+        // only asking for a minimum, osmo limits the join to 2% of the 3M shares
+        let msg = OsmosisMsg::JoinPool {
+            pool_id,
+            share_out_amount: Uint128::new(1),
+            token_in_maxs: deposit,
+        };
+        let res = app.execute(provider.clone(), msg.into()).unwrap();
+        let JoinPoolResponse {
+            tokens_consumed,
+            shares_out,
+        } = from_slice(res.data.unwrap().as_slice()).unwrap();
+        assert_eq!(
+            tokens_consumed,
+            vec![coin(120_000, "osmo"), coin(30_000, "atom")]
+        );
+        assert_eq!(shares_out, Uint128::new(60_000));
+
+        // the surplus atom is refunded, all osmo is used
+        let osmo = app.wrap().query_balance(&provider, "osmo").unwrap();
+        assert_eq!(osmo.amount, Uint128::zero());
+        let atom = app.wrap().query_balance(&provider, "atom").unwrap();
+        assert_eq!(atom.amount, Uint128::new(70_000));
+        let lp = app.wrap().query_balance(&provider, "gamm/pool/43").unwrap();
+        assert_eq!(lp.amount, Uint128::new(60_000));
+    }
+
+    #[test]
+    fn join_pool_with_huge_offer() {
+        let pool_id = 43;
+        let pool = Pool::new(coin(6_000_000, "osmo"), coin(1_500_000, "atom"));
+        let provider = Addr::unchecked("provider");
+        let deposit = vec![coin(u128::MAX, "osmo"), coin(u128::MAX, "atom")];
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, pool_id, &pool).unwrap();
+            router
+                .bank
+                .init_balance(storage, &provider, deposit.clone())
+                .unwrap()
+        });
+
+        // the pool reserves cannot grow by that much, which is an error rather than a panic
+        let msg = OsmosisMsg::JoinPool {
+            pool_id,
+            share_out_amount: Uint128::new(1),
+            token_in_maxs: deposit.clone(),
+        };
+        let err = app.execute(provider, msg.into()).unwrap_err();
+        assert!(matches!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::Overflow(_)
+        ));
+        let query = OsmosisQuery::CalcJoinPoolShares {
+            pool_id,
+            tokens_in: deposit,
+        };
+        app.wrap()
+            .query::<CalcJoinPoolSharesResponse>(&query.into())
+            .unwrap_err();
+
+        // and an offer worth more shares than a Uint128 holds doesn't overflow the ratio either
+        let mut tiny = Pool::new(coin(1, "osmo"), coin(1, "atom"));
+        tiny.shares = Uint128::new(1_000);
+        let err = tiny
+            .join(
+                Uint128::zero(),
+                &[coin(u128::MAX, "osmo"), coin(u128::MAX, "atom")],
+            )
+            .unwrap_err();
+        assert!(
+            matches!(err, OsmosisError::ConversionOverflow(_)),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn calc_join_pool_shares_matches_join() {
+        let pool_id = 43;
+        let pool = Pool::new(coin(6_000_000, "osmo"), coin(1_500_000, "atom"));
+        let provider = Addr::unchecked("provider");
+        let deposit = vec![coin(123_457, "osmo"), coin(100_000, "atom")];
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, pool_id, &pool).unwrap();
+            router
+                .bank
+                .init_balance(storage, &provider, deposit.clone())
+                .unwrap()
+        });
+
+        // osmo limits the join, and the odd unit of it can't buy a whole share
+        let query = OsmosisQuery::CalcJoinPoolShares {
+            pool_id,
+            tokens_in: deposit.clone(),
+        };
+        let CalcJoinPoolSharesResponse {
+            share_out_amount,
+            tokens_used,
+        } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(share_out_amount, Uint128::new(61_728));
+        assert_eq!(
+            tokens_used,
+            vec![coin(123_456, "osmo"), coin(30_864, "atom")]
+        );
+
+        // the query left the pool untouched
+        let query = OsmosisQuery::PoolState { id: pool_id }.into();
+        let state: PoolStateResponse = app.wrap().query(&query).unwrap();
+        assert_eq!(state.assets, pool.assets);
+
+        // joining with exactly the quoted tokens mints exactly the quoted shares
+        let msg = OsmosisMsg::JoinPool {
+            pool_id,
+            share_out_amount,
+            token_in_maxs: tokens_used.clone(),
+        };
+        let res = app.execute(provider.clone(), msg.into()).unwrap();
+        let JoinPoolResponse {
+            tokens_consumed,
+            shares_out,
+        } = from_slice(res.data.unwrap().as_slice()).unwrap();
+        assert_eq!(tokens_consumed, tokens_used);
+        assert_eq!(shares_out, share_out_amount);
+        let lp = app.wrap().query_balance(&provider, "gamm/pool/43").unwrap();
+        assert_eq!(lp.amount, share_out_amount);
+
+        // tokens the pool doesn't hold are rejected
+        let query = OsmosisQuery::CalcJoinPoolShares {
+            pool_id,
+            tokens_in: vec![coin(100, "btc")],
+        };
+        let err = app
+            .wrap()
+            .query::<CalcJoinPoolSharesResponse>(&query.into())
+            .unwrap_err();
+        let expected = OsmosisError::AssetNotInPool;
+        assert!(err.to_string().contains(&expected.to_string()), "{}", err);
+    }
+
+    #[test]
+    fn pool_state_sorted() {
+        let reversed = Pool::new(coin(6_000_000, "osmo"), coin(1_500_000, "atom"));
+        let ordered = Pool::new(coin(1_500_000, "atom"), coin(6_000_000, "osmo"));
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, 1, &reversed).unwrap();
+            router.custom.set_pool(storage, 2, &ordered).unwrap();
+        });
+
+        let wrapper = QuerierWrapper::<OsmosisQuery>::new(&app);
+        let querier = OsmosisQuerier::new(&wrapper);
+
+        // the response keeps the order of the pool
+        let state = querier.pool_state(1).unwrap();
+        assert_eq!(
+            state.assets,
+            vec![coin(6_000_000, "osmo"), coin(1_500_000, "atom")]
+        );
+
+        let sorted = state.sorted();
+        assert_eq!(
+            sorted.assets,
+            vec![coin(1_500_000, "atom"), coin(6_000_000, "osmo")]
+        );
+        assert_eq!(sorted.shares, coin(3_000_000, "gamm/pool/1"));
+
+        // so equivalent pools compare equal once sorted
+        let other = querier.pool_state(2).unwrap();
+        assert_eq!(sorted.assets, other.sorted().assets);
+    }
+
+    #[test]
+    fn query_epoch_provisions() {
+        let app = OsmosisApp::new();
+        let wrapper = QuerierWrapper::<OsmosisQuery>::new(&app);
+        let querier = OsmosisQuerier::new(&wrapper);
+        let EpochProvisionsResponse { epoch_provisions } = querier.epoch_provisions().unwrap();
+        assert_eq!(epoch_provisions, DEFAULT_EPOCH_PROVISIONS);
+
+        let configured = Decimal::from_str("123456.789").unwrap();
+        let app = OsmosisApp::new().with_epoch_provisions(configured);
+        let wrapper = QuerierWrapper::<OsmosisQuery>::new(&app);
+        let querier = OsmosisQuerier::new(&wrapper);
+        let EpochProvisionsResponse { epoch_provisions } = querier.epoch_provisions().unwrap();
+        assert_eq!(epoch_provisions, configured);
+    }
+
+    #[test]
+    fn querier_pool_helpers() {
+        let pool1 = Pool::new(coin(6_000_000, "osmo"), coin(1_500_000, "atom"));
+        let pool2 = Pool::new(coin(2_000_000, "atom"), coin(1_000_000, "btc"));
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, 1, &pool1).unwrap();
+            router.custom.set_pool(storage, 2, &pool2).unwrap();
+        });
+
+        let wrapper = QuerierWrapper::<OsmosisQuery>::new(&app);
+        let querier = OsmosisQuerier::new(&wrapper);
+
+        let state = querier.pool_state(1).unwrap();
+        assert_eq!(state, pool1.clone().into_response(1));
+        let osmo = querier.pool_state_asset(1, "osmo").unwrap();
+        assert_eq!(osmo, Uint128::new(6_000_000));
+        let err = querier.pool_state_asset(1, "btc").unwrap_err();
+        assert_eq!(err, StdError::generic_err("asset btc not in pool 1"));
+
+        let SpotPriceResponse { price } = querier
+            .spot_price(1, "osmo".to_string(), "atom".to_string())
+            .unwrap();
+        assert_eq!(price, Decimal::percent(25));
+        let SpotPriceResponse { price } = querier
+            .spot_price_with_fee(1, "osmo".to_string(), "atom".to_string())
+            .unwrap();
+        assert_eq!(price, Decimal::percent(25) * (Decimal::one() - pool1.fee));
+
+        // the same as the two hops estimated on the pools directly
+        let amount = SwapAmount::In(Uint128::new(10_000));
+        let SwapResponse { amount: estimate } = querier
+            .estimate_swap(
+                MOCK_CONTRACT_ADDR.to_string(),
+                Swap::new(1, "osmo", "atom"),
+                vec![Step::new(2, "btc")],
+                amount.clone(),
+            )
+            .unwrap();
+        let atom = pool1.clone().swap("osmo", "atom", amount).unwrap().as_out();
+        let btc = pool2
+            .clone()
+            .swap("atom", "btc", SwapAmount::In(atom))
+            .unwrap();
+        assert_eq!(estimate, btc);
+        assert_eq!(
+            querier
+                .estimate_swap(
+                    MOCK_CONTRACT_ADDR.to_string(),
+                    Swap::new(1, "osmo", "atom"),
+                    vec![],
+                    SwapAmount::Out(Uint128::new(5_000)),
+                )
+                .unwrap()
+                .amount,
+            pool1
+                .clone()
+                .swap("osmo", "atom", SwapAmount::Out(Uint128::new(5_000)))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn query_locked_by_id() {
+        let mut lock = Lock::new("staker", 14 * 24 * 60 * 60, coins(500, "gamm/pool/1"));
+        lock.end_time = Some(1_600_000_000_000);
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_lock(storage, 7, &lock).unwrap();
+        });
+
+        let wrapper = QuerierWrapper::<OsmosisQuery>::new(&app);
+        let querier = OsmosisQuerier::new(&wrapper);
+        let locked = querier.locked_by_id(7).unwrap();
+        assert_eq!(
+            locked,
+            LockedResponse {
+                lock_id: 7,
+                owner: "staker".to_string(),
+                duration: 1_209_600,
+                end_time: Some(1_600_000_000_000),
+                coins: coins(500, "gamm/pool/1"),
+            }
+        );
+
+        // unknown lock ids say so
+        let err = querier.locked_by_id(8).unwrap_err();
+        assert!(err.to_string().contains("Lock 8 does not exist"), "{}", err);
+    }
+
+    #[test]
+    fn query_account_locked_coins() {
+        let mut unlocking = Lock::new("staker", 86400, vec![coin(300, "gamm/pool/1")]);
+        unlocking.end_time = Some(1_600_000_000_000);
+        let locks = [
+            Lock::new(
+                "staker",
+                86400,
+                vec![coin(500, "gamm/pool/2"), coin(200, "gamm/pool/1")],
+            ),
+            Lock::new("someone_else", 86400, vec![coin(1000, "gamm/pool/1")]),
+            unlocking,
+        ];
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            for (id, lock) in locks.iter().enumerate() {
+                router.custom.set_lock(storage, id as u64, lock).unwrap();
+            }
+        });
+
+        // locked and unlocking coins of the staker are summed by denom
+        let query = OsmosisQuery::AccountLockedCoins {
+            address: "staker".to_string(),
+        };
+        let AccountLockedCoinsResponse { coins } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(
+            coins,
+            vec![coin(500, "gamm/pool/1"), coin(500, "gamm/pool/2")]
+        );
+
+        // no locks, no coins
+        let query = OsmosisQuery::AccountLockedCoins {
+            address: "nobody".to_string(),
+        };
+        let AccountLockedCoinsResponse { coins } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(coins, vec![]);
+    }
+
+    #[test]
+    fn query_account_locks_paginated() {
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            for id in 1..=5 {
+                let lock = Lock::new("staker", 86400, coins(100 * id as u128, "gamm/pool/1"));
+                router.custom.set_lock(storage, id, &lock).unwrap();
+            }
+            let other = Lock::new("someone_else", 86400, coins(1000, "gamm/pool/1"));
+            router.custom.set_lock(storage, 3, &other).unwrap();
+        });
+
+        let wrapper = QuerierWrapper::<OsmosisQuery>::new(&app);
+        let querier = OsmosisQuerier::new(&wrapper);
+        let page = |start_after, limit| {
+            querier
+                .account_locks("staker".to_string(), start_after, limit)
+                .unwrap()
+                .locks
+                .into_iter()
+                .map(|lock| lock.lock_id)
+                .collect::<Vec<_>>()
+        };
+
+        // everything by default, skipping the lock of someone else
+        assert_eq!(page(None, None), vec![1, 2, 4, 5]);
+
+        // page through two at a time
+        assert_eq!(page(None, Some(2)), vec![1, 2]);
+        assert_eq!(page(Some(2), Some(2)), vec![4, 5]);
+        assert_eq!(page(Some(5), Some(2)), Vec::<u64>::new());
+
+        let AccountLocksResponse { locks } = querier
+            .account_locks("staker".to_string(), Some(3), Some(1))
+            .unwrap();
+        assert_eq!(
+            locks,
+            vec![LockedResponse {
+                lock_id: 4,
+                owner: "staker".to_string(),
+                duration: 86400,
+                end_time: None,
+                coins: coins(400, "gamm/pool/1"),
+            }]
+        );
+    }
+
+    #[test]
+    fn estimate_swap_regression() {
+        let pool = Pool::new(coin(2_000_000, "atom"), coin(1_000_000, "btc"));
+
+        // set up with one pool
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, 1, &pool).unwrap();
+        });
+
+        // 2007 * 0.997 = 2000 after fees, 2M * 1M = 2.002M * 999_001 -> output = 1000
+        let query = OsmosisQuery::estimate_swap(
+            MOCK_CONTRACT_ADDR,
+            1,
+            "atom",
+            "btc",
+            SwapAmount::In(Uint128::new(2007)),
+        );
+        let SwapResponse { amount } = app.wrap().query(&query.into()).unwrap();
+        let expected = SwapAmount::Out(Uint128::new(1000));
+        assert_eq!(amount, expected);
+
+        // now try the reverse query. we know what we need to pay to get 1000 out
+        let query = OsmosisQuery::estimate_swap(
+            MOCK_CONTRACT_ADDR,
+            1,
+            "atom",
+            "btc",
+            SwapAmount::Out(Uint128::new(1000)),
+        );
+        let SwapResponse { amount } = app.wrap().query(&query.into()).unwrap();
+        let expected = SwapAmount::In(Uint128::new(2007));
+        assert_eq!(amount, expected);
+    }
+
+    #[test]
+    fn estimate_swap_round_trip_small_amounts() {
+        let pool = Pool::new(coin(2_000_000, "atom"), coin(1_000_000, "btc"));
+        // swap mutates the pool, so estimate against a fresh copy every time
+        let estimate = |amount| pool.clone().swap("atom", "btc", amount).unwrap();
+
+        for output in 1..=2500u128 {
+            let output = Uint128::new(output);
+            let input = estimate(SwapAmount::Out(output)).as_in();
+
+            // paying the estimated input gets us the requested output (within one unit)
+            let received = estimate(SwapAmount::In(input)).as_out();
+            assert!(
+                received >= output,
+                "{} in gave {} < {}",
+                input,
+                received,
+                output
+            );
+            assert!(
+                received <= output + Uint128::new(1),
+                "{} in gave {} > {} + 1",
+                input,
+                received,
+                output
+            );
+
+            // and it is the cheapest input that does so
+            let received = estimate(SwapAmount::In(input - Uint128::new(1))).as_out();
+            assert!(received < output, "{} in already gave {}", input, received);
+        }
+    }
+
+    #[test]
+    fn query_total_liquidity() {
+        let pool1 = Pool::new(coin(6_000_000, "osmo"), coin(3_000_000, "atom"));
+        let pool2 = Pool::new(coin(2_000_000, "atom"), coin(1_000_000, "btc"));
+
+        let mut app = OsmosisApp::new();
+
+        // no pools, no liquidity
+        let querier = QuerierWrapper::<OsmosisQuery>::new(&app);
+        let TotalLiquidityResponse { coins } =
+            OsmosisQuerier::new(&querier).total_liquidity().unwrap();
+        assert_eq!(coins, vec![]);
+
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, 1, &pool1).unwrap();
+            router.custom.set_pool(storage, 2, &pool2).unwrap();
+        });
+
+        // atom is merged across both pools
+        let querier = QuerierWrapper::<OsmosisQuery>::new(&app);
+        let TotalLiquidityResponse { coins } =
+            OsmosisQuerier::new(&querier).total_liquidity().unwrap();
+        assert_eq!(
+            coins,
+            vec![
+                coin(5_000_000, "atom"),
+                coin(1_000_000, "btc"),
+                coin(6_000_000, "osmo")
+            ]
+        );
+    }
+
+    #[test]
+    fn estimate_swap_exact_amounts() {
+        let pool1 = Pool::new(coin(6_000_000, "osmo"), coin(3_000_000, "atom"));
+        let pool2 = Pool::new(coin(2_000_000, "atom"), coin(1_000_000, "btc"));
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, 1, &pool1).unwrap();
+            router.custom.set_pool(storage, 2, &pool2).unwrap();
+        });
+
+        // single pool
+        let query = OsmosisQuery::estimate_swap_exact_amount_out(
+            MOCK_CONTRACT_ADDR,
+            1,
+            "atom",
+            "osmo",
+            1_500_000u128,
+        );
+        let EstimateSwapOutResponse { token_in } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(token_in, Uint128::new(1_003_010));
+
+        let query = OsmosisQuery::estimate_swap_exact_amount_in(
+            MOCK_CONTRACT_ADDR,
+            1,
+            "atom",
+            "osmo",
+            1_003_010u128,
+        );
+        let EstimateSwapInResponse { token_out } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(token_out, Uint128::new(1_500_000));
+
+        // routes work the same as with EstimateSwap
+        let first = Swap::new(1, "osmo", "atom");
+        let route = vec![Step::new(2, "btc")];
+        let query = OsmosisQuery::EstimateSwapExactAmountOut {
+            sender: MOCK_CONTRACT_ADDR.to_string(),
+            first: first.clone(),
+            route: route.clone(),
+            token_out: Uint128::new(1000),
+        };
+        let EstimateSwapOutResponse { token_in } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(token_in, Uint128::new(4028));
+
+        let query = OsmosisQuery::EstimateSwapExactAmountIn {
+            sender: MOCK_CONTRACT_ADDR.to_string(),
+            first,
+            route,
+            token_in: Uint128::new(4028),
+        };
+        let EstimateSwapInResponse { token_out } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(token_out, Uint128::new(1000));
+    }
+
+    #[test]
+    fn spot_price_v2_orientation() {
+        let pool = Pool::new(coin(6_000_000, "osmo"), coin(3_000_000, "atom"));
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, 1, &pool).unwrap();
+        });
+
+        // one atom is worth two osmo
+        let query = OsmosisQuery::spot_price_v2(1, "atom", "osmo");
+        let SpotPriceResponse { price } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(price, Decimal::percent(200));
+
+        // one osmo is worth half an atom
+        let query = OsmosisQuery::spot_price_v2(1, "osmo", "atom");
+        let SpotPriceResponse { price } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(price, Decimal::percent(50));
+
+        // which matches the old query with base as input and no fee
+        let query = OsmosisQuery::spot_price(1, "atom", "osmo");
+        let SpotPriceResponse { price } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(price, Decimal::percent(200));
+    }
+
+    #[test]
+    fn lp_spot_price() {
+        let pool = Pool::new(coin(4_000_000, "osmo"), coin(1_000_000, "atom"));
+        assert_eq!(pool.shares, Uint128::new(2_000_000));
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, 1, &pool).unwrap();
+        });
+
+        // the pool holds 4M osmo + 1M atom at 4 osmo each, so 8M osmo over 2M shares
+        let query = OsmosisQuery::LpSpotPrice {
+            pool_id: 1,
+            quote_denom: "osmo".to_string(),
+        };
+        let LpSpotPriceResponse { price } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(price, Decimal::from_ratio(8_000_000u128, 2_000_000u128));
+
+        // or 2M atom over the same shares
+        let query = OsmosisQuery::LpSpotPrice {
+            pool_id: 1,
+            quote_denom: "atom".to_string(),
+        };
+        let LpSpotPriceResponse { price } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(price, Decimal::from_ratio(2_000_000u128, 2_000_000u128));
+
+        let query = OsmosisQuery::LpSpotPrice {
+            pool_id: 1,
+            quote_denom: "btc".to_string(),
+        };
+        let err = app
+            .wrap()
+            .query::<LpSpotPriceResponse>(&query.into())
+            .unwrap_err();
+        assert!(
+            err.to_string().ends_with(
+                &OsmosisError::DenomNotInPool {
+                    denom: "btc".to_string(),
+                    pool_id: 1
+                }
+                .to_string()
+            ),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn exit_pool_with_exit_fee() {
+        let pool_id = 43;
+        let mut pool = Pool::new(coin(6_000_000, "osmo"), coin(1_500_000, "atom"));
+        pool.exit_fee = Decimal::percent(1);
+        let provider = Addr::unchecked("provider");
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, pool_id, &pool).unwrap();
+            router
+                .bank
+                .init_balance(storage, &provider, coins(30_000, "gamm/pool/43"))
+                .unwrap();
+        });
+
+        // the fee is exposed with the pool params
+        let query = OsmosisQuery::PoolParams { id: pool_id }.into();
+        let PoolParamsResponse { params } = app.wrap().query(&query).unwrap();
+        assert_eq!(
+            params,
+            PoolParams {
+                swap_fee: Decimal::permille(3),
+                exit_fee: Decimal::percent(1),
+            }
+        );
+
+        // without a fee this would pay out 60_000 osmo and 15_000 atom
+        let msg = OsmosisMsg::ExitPool {
+            pool_id,
+            share_in_amount: Uint128::new(30_000),
+            token_out_mins: vec![coin(60_000, "osmo")],
+        };
+        let err = app.execute(provider.clone(), msg.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::ExitPoolMinOutputNotMet {
+                output: coin(59_400, "osmo"),
+                min: Uint128::new(60_000)
+            }
+        );
+
+        let msg = OsmosisMsg::ExitPool {
+            pool_id,
+            share_in_amount: Uint128::new(30_000),
+            token_out_mins: vec![],
+        };
+        let res = app.execute(provider.clone(), msg.into()).unwrap();
+        let ExitPoolResponse { tokens_out } = from_slice(res.data.unwrap().as_slice()).unwrap();
+        assert_eq!(tokens_out, vec![coin(59_400, "osmo"), coin(14_850, "atom")]);
+        let balances = app.wrap().query_all_balances(&provider).unwrap();
+        assert_eq!(balances, vec![coin(14_850, "atom"), coin(59_400, "osmo")]);
+
+        // all 30_000 shares are burnt, but the assets backing the 300 skimmed ones stay in the pool
+        let query = OsmosisQuery::PoolState { id: pool_id }.into();
+        let state: PoolStateResponse = app.wrap().query(&query).unwrap();
+        assert_eq!(
+            state.assets,
+            vec![coin(5_940_600, "osmo"), coin(1_485_150, "atom")]
+        );
+        assert_eq!(state.shares, coin(3_000_000 - 30_000, "gamm/pool/43"));
+
+        // so the claim of the remaining holders grew: 1M shares were worth 2M osmo and 500k atom
+        let before = pool.into_response(pool_id).shares_value(1_000_000u128);
+        assert_eq!(before, vec![coin(2_000_000, "osmo"), coin(500_000, "atom")]);
+        let after = state.shares_value(1_000_000u128);
+        assert_eq!(after, vec![coin(2_000_202, "osmo"), coin(500_050, "atom")]);
+    }
+
+    #[test]
+    fn calc_exit_pool_coins_matches_exit() {
+        let pool_id = 43;
+        let mut pool = Pool::new(coin(6_000_000, "osmo"), coin(1_500_000, "atom"));
+        pool.exit_fee = Decimal::percent(1);
+        let provider = Addr::unchecked("provider");
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, pool_id, &pool).unwrap();
+            router
+                .bank
+                .init_balance(storage, &provider, coins(12_345, "gamm/pool/43"))
+                .unwrap();
+        });
+
+        // 124 of the shares are skimmed (rounded up), the other 12_221 are redeemed
+        let query = OsmosisQuery::CalcExitPoolCoinsFromShares {
+            pool_id,
+            share_in_amount: Uint128::new(12_345),
+        };
+        let CalcExitResponse { tokens_out } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(tokens_out, vec![coin(24_442, "osmo"), coin(6_110, "atom")]);
+
+        // the query left the pool untouched
+        let query = OsmosisQuery::PoolState { id: pool_id }.into();
+        let state: PoolStateResponse = app.wrap().query(&query).unwrap();
+        assert_eq!(state.assets, pool.assets);
+        assert_eq!(state.shares.amount, pool.shares);
+
+        // and the actual exit pays out the same
+        let msg = OsmosisMsg::ExitPool {
+            pool_id,
+            share_in_amount: Uint128::new(12_345),
+            token_out_mins: tokens_out.clone(),
+        };
+        let res = app.execute(provider.clone(), msg.into()).unwrap();
+        let ExitPoolResponse { tokens_out: paid } =
+            from_slice(res.data.unwrap().as_slice()).unwrap();
+        assert_eq!(paid, tokens_out);
+        let balances = app.wrap().query_all_balances(&provider).unwrap();
+        assert_eq!(balances, vec![coin(6_110, "atom"), coin(24_442, "osmo")]);
+
+        // more shares than exist can't be redeemed, however many are asked for
+        let remaining = Uint128::new(3_000_000 - 12_345);
+        for share_in_amount in [remaining + Uint128::one(), Uint128::MAX] {
+            let query = OsmosisQuery::CalcExitPoolCoinsFromShares {
+                pool_id,
+                share_in_amount,
+            };
+            let err = app
+                .wrap()
+                .query::<CalcExitResponse>(&query.into())
+                .unwrap_err();
+            let expected = OsmosisError::InsufficientPoolShares {
+                requested: share_in_amount,
+                available: remaining,
+            };
+            assert!(err.to_string().contains(&expected.to_string()), "{}", err);
+        }
+    }
+
+    #[test]
+    fn exit_pool_beyond_total_shares() {
+        let pool_id = 43;
+        let pool = Pool::new(coin(6_000_000, "osmo"), coin(1_500_000, "atom"));
+        let provider = Addr::unchecked("provider");
+
+        let mut emptied = Pool::new(coin(0, "osmo"), coin(0, "atom"));
+        emptied.shares = Uint128::zero();
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, pool_id, &pool).unwrap();
+            router.custom.set_pool(storage, 44, &emptied).unwrap();
+            router
+                .bank
+                .init_balance(storage, &provider, coins(u128::MAX, "gamm/pool/43"))
+                .unwrap();
+        });
+
+        for share_in_amount in [Uint128::new(3_000_001), Uint128::MAX] {
+            let msg = OsmosisMsg::ExitPool {
+                pool_id,
+                share_in_amount,
+                token_out_mins: vec![],
+            };
+            let err = app.execute(provider.clone(), msg.into()).unwrap_err();
+            assert_eq!(
+                err.downcast::<OsmosisError>().unwrap(),
+                OsmosisError::InsufficientPoolShares {
+                    requested: share_in_amount,
+                    available: Uint128::new(3_000_000),
+                }
+            );
+        }
+
+        // a pool without any shares has nothing to pay out
+        let msg = OsmosisMsg::ExitPool {
+            pool_id: 44,
+            share_in_amount: Uint128::zero(),
+            token_out_mins: vec![],
+        };
+        let err = app.execute(provider, msg.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::EmptyPool
+        );
+    }
+
+    #[test]
+    fn join_then_exit_burns_lp_shares() {
+        let pool_id = 43;
+        let pool = Pool::new(coin(6_000_000, "osmo"), coin(1_500_000, "atom"));
+        let provider = Addr::unchecked("provider");
+        let deposit = vec![coin(60_000, "osmo"), coin(15_000, "atom")];
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, pool_id, &pool).unwrap();
+            router
+                .bank
+                .init_balance(storage, &provider, deposit.clone())
+                .unwrap()
+        });
+
+        let msg = OsmosisMsg::JoinPool {
+            pool_id,
+            share_out_amount: Uint128::new(30_000),
+            token_in_maxs: deposit,
+        };
+        app.execute(provider.clone(), msg.into()).unwrap();
+        let lp = app.wrap().query_balance(&provider, "gamm/pool/43").unwrap();
+        assert_eq!(lp.amount, Uint128::new(30_000));
+
+        let msg = OsmosisMsg::ExitPool {
+            pool_id,
+            share_in_amount: lp.amount,
+            token_out_mins: vec![],
+        };
+        app.execute(provider.clone(), msg.into()).unwrap();
+
+        // the shares are gone from both the provider and the pool
+        let lp = app.wrap().query_balance(&provider, "gamm/pool/43").unwrap();
+        assert_eq!(lp.amount, Uint128::zero());
+        let query = OsmosisQuery::PoolState { id: pool_id }.into();
+        let state: PoolStateResponse = app.wrap().query(&query).unwrap();
+        assert_eq!(state, pool.into_response(pool_id));
+
+        // and they cannot be redeemed twice
+        let msg = OsmosisMsg::ExitPool {
+            pool_id,
+            share_in_amount: Uint128::new(30_000),
+            token_out_mins: vec![],
+        };
+        app.execute(provider, msg.into()).unwrap_err();
+    }
+
+    #[test]
+    fn create_balancer_pool() {
+        let creator = Addr::unchecked("creator");
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(
+                    storage,
+                    &creator,
+                    vec![coin(1_200_000, "atom"), coin(3_000_000, "osmo")],
+                )
+                .unwrap();
+        });
+
+        let msg = OsmosisMsg::CreateBalancerPool {
+            pool_params: PoolParams {
+                swap_fee: Decimal::permille(2),
+                exit_fee: Decimal::zero(),
+            },
+            pool_assets: vec![
+                PoolAsset::new(coin(2_000_000, "osmo"), 1u128),
+                PoolAsset::new(coin(1_000_000, "atom"), 1u128),
+            ],
+        };
+        let res = app.execute(creator.clone(), msg.into()).unwrap();
+        let CreatePoolResponse { pool_id } = from_slice(res.data.unwrap().as_slice()).unwrap();
+        assert_eq!(pool_id, 1);
+
+        // the new pool can be queried right away
+        let query = OsmosisQuery::PoolState { id: pool_id }.into();
+        let state: PoolStateResponse = app.wrap().query(&query).unwrap();
+        assert_eq!(
+            state.assets,
+            vec![coin(2_000_000, "osmo"), coin(1_000_000, "atom")]
+        );
+        assert_eq!(state.shares, coin(INIT_POOL_SHARES, "gamm/pool/1"));
+        let SpotPriceResponse { price } = app
+            .wrap()
+            .query(&OsmosisQuery::spot_price(pool_id, "atom", "osmo").into())
+            .unwrap();
+        assert_eq!(price, Decimal::percent(200));
+
+        // the creator paid the liquidity and holds all the shares
+        let balances = app.wrap().query_all_balances(&creator).unwrap();
+        assert_eq!(
+            balances,
+            vec![
+                coin(200_000, "atom"),
+                coin(INIT_POOL_SHARES, "gamm/pool/1"),
+                coin(1_000_000, "osmo")
+            ]
+        );
+
+        // ids keep counting up from the highest pool
+        app.init_modules(|router, _, storage| {
+            let pool = Pool::new(coin(100, "osmo"), coin(100, "btc"));
+            router.custom.set_pool(storage, 7, &pool).unwrap();
+        });
+        let msg = OsmosisMsg::CreateBalancerPool {
+            pool_params: PoolParams {
+                swap_fee: Decimal::permille(2),
+                exit_fee: Decimal::zero(),
+            },
+            pool_assets: vec![
+                PoolAsset::new(coin(100_000, "osmo"), 1u128),
+                PoolAsset::new(coin(100_000, "atom"), 1u128),
+            ],
+        };
+        let res = app.execute(creator, msg.into()).unwrap();
+        let CreatePoolResponse { pool_id } = from_slice(res.data.unwrap().as_slice()).unwrap();
+        assert_eq!(pool_id, 8);
+    }
+
+    #[test]
+    fn create_balancer_pool_invalid_assets() {
+        let params = PoolParams {
+            swap_fee: Decimal::permille(3),
+            exit_fee: Decimal::zero(),
+        };
+
+        let err = Pool::new_balancer(&params, vec![PoolAsset::new(coin(100, "osmo"), 1u128)])
+            .unwrap_err();
+        assert_eq!(err, OsmosisError::TooFewPoolAssets);
+
+        let err = Pool::new_balancer(
+            &params,
+            vec![
+                PoolAsset::new(coin(100, "osmo"), 1u128),
+                PoolAsset::new(coin(100, "atom"), 0u128),
+            ],
+        )
+        .unwrap_err();
+        assert_eq!(err, OsmosisError::ZeroPoolWeight("atom".to_string()));
+
+        let err = Pool::new_balancer(
+            &params,
+            vec![
+                PoolAsset::new(coin(100, "osmo"), 1u128),
+                PoolAsset::new(coin(200, "osmo"), 1u128),
+            ],
+        )
+        .unwrap_err();
+        assert_eq!(err, OsmosisError::DuplicatePoolAsset("osmo".to_string()));
+
+        // nothing is created when the message fails
+        let creator = Addr::unchecked("creator");
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &creator, coins(1000, "osmo"))
+                .unwrap();
+        });
+        let msg = OsmosisMsg::CreateBalancerPool {
+            pool_params: params,
+            pool_assets: vec![PoolAsset::new(coin(1000, "osmo"), 1u128)],
+        };
+        let err = app.execute(creator.clone(), msg.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::TooFewPoolAssets
+        );
+        let balance = app.wrap().query_balance(&creator, "osmo").unwrap();
+        assert_eq!(balance.amount, Uint128::new(1000));
+    }
+
+    #[test]
+    fn pool_with_missing_weights() {
+        // assets and weights are public, so a mocked pool can get them out of step
+        let mut pool = Pool::new(coin(6_000_000, "osmo"), coin(1_500_000, "atom"));
+        pool.weights.pop();
+        assert_eq!(pool.get_weight("osmo"), Some(Uint128::new(1)));
+        assert_eq!(pool.get_weight("atom"), None);
+        let err = pool
+            .spot_price("osmo", "atom", SpotPriceKind::Marginal)
+            .unwrap_err();
+        assert_eq!(err, OsmosisError::AssetNotInPool);
+    }
+
+    #[test]
+    fn weighted_pool_swap() {
+        // an 80/20 pool where both assets are worth the same
+        let params = PoolParams {
+            swap_fee: Decimal::permille(3),
+            exit_fee: Decimal::zero(),
+        };
+        let pool = Pool::new_balancer(
+            &params,
+            vec![
+                PoolAsset::new(coin(8_000_000, "osmo"), 4u128),
+                PoolAsset::new(coin(2_000_000, "atom"), 1u128),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            pool.spot_price("atom", "osmo", SpotPriceKind::Marginal)
+                .unwrap(),
+            Decimal::one()
+        );
+
+        // 8M * (1 - (2M / (2M + 9970)) ^ (1/4)) = 9939.05
+        let payout = pool
+            .clone()
+            .swap("atom", "osmo", SwapAmount::In(Uint128::new(10_000)))
+            .unwrap();
+        assert_eq!(payout, SwapAmount::Out(Uint128::new(9939)));
+
+        // 2M * ((8M / (8M - 2000)) ^ 4 - 1) = 2001.25 must be left after fees,
+        // so we pay 2002 / 0.997 = 2008.02, rounded up
+        let payin = pool
+            .clone()
+            .swap("atom", "osmo", SwapAmount::Out(Uint128::new(2000)))
+            .unwrap();
+        assert_eq!(payin, SwapAmount::In(Uint128::new(2009)));
+
+        // the other direction raises to the power of 4 and 1/4 respectively
+        let payout = pool
+            .clone()
+            .swap("osmo", "atom", SwapAmount::In(Uint128::new(2008)))
+            .unwrap();
+        let payin = pool
+            .clone()
+            .swap("osmo", "atom", SwapAmount::Out(payout.as_out()))
+            .unwrap();
+        assert!(payin.as_in() <= Uint128::new(2008));
+        assert!(payin.as_in() >= Uint128::new(2000));
+    }
+
+    #[test]
+    fn weighted_spot_price() {
+        let params = PoolParams {
+            swap_fee: Decimal::permille(3),
+            exit_fee: Decimal::zero(),
+        };
+        // 80/20 with equal balances, so the naive balance ratio would be 1
+        let weighted = Pool::new_balancer(
+            &params,
+            vec![
+                PoolAsset::new(coin(1_000_000, "osmo"), 80u128),
+                PoolAsset::new(coin(1_000_000, "atom"), 20u128),
+            ],
+        )
+        .unwrap();
+        // a 50/50 pool prices like the unweighted constructor
+        let even = Pool::new_balancer(
+            &params,
+            vec![
+                PoolAsset::new(coin(1_000_000, "osmo"), 50u128),
+                PoolAsset::new(coin(4_000_000, "atom"), 50u128),
+            ],
+        )
+        .unwrap();
+        let plain = Pool::new(coin(1_000_000, "osmo"), coin(4_000_000, "atom"));
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, 1, &weighted).unwrap();
+            router.custom.set_pool(storage, 2, &even).unwrap();
+            router.custom.set_pool(storage, 3, &plain).unwrap();
+        });
+
+        // (1M / 20) / (1M / 80) = 4 atom per osmo
+        let query = OsmosisQuery::spot_price(1, "osmo", "atom");
+        let SpotPriceResponse { price } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(price, Decimal::percent(400));
+        let query = OsmosisQuery::spot_price_v2(1, "atom", "osmo");
+        let SpotPriceResponse { price } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(price, Decimal::percent(25));
+
+        for (base, quote) in [("osmo", "atom"), ("atom", "osmo")] {
+            let query = OsmosisQuery::spot_price_v2(2, base, quote);
+            let SpotPriceResponse { price: even } = app.wrap().query(&query.into()).unwrap();
+            let query = OsmosisQuery::spot_price_v2(3, base, quote);
+            let SpotPriceResponse { price: plain } = app.wrap().query(&query.into()).unwrap();
+            assert_eq!(even, plain);
+        }
+    }
+
+    #[test]
+    fn query_pool_type() {
+        let creator = Addr::unchecked("creator");
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(
+                    storage,
+                    &creator,
+                    vec![
+                        coin(2_000, "osmo"),
+                        coin(2_000, "usdc"),
+                        coin(2_000, "usdt"),
+                    ],
+                )
+                .unwrap();
+        });
+
+        let msg = OsmosisMsg::CreateBalancerPool {
+            pool_params: PoolParams {
+                swap_fee: Decimal::permille(3),
+                exit_fee: Decimal::zero(),
+            },
+            pool_assets: vec![
+                PoolAsset::new(coin(1_000, "osmo"), 1u128),
+                PoolAsset::new(coin(1_000, "usdc"), 1u128),
+            ],
+        };
+        let res = app.execute(creator.clone(), msg.into()).unwrap();
+        let CreatePoolResponse { pool_id: balancer } =
+            from_slice(res.data.unwrap().as_slice()).unwrap();
+
+        let msg = OsmosisMsg::CreateStableswapPool {
+            initial_pool_liquidity: vec![coin(1_000, "usdc"), coin(1_000, "usdt")],
+            scaling_factors: vec![],
+            swap_fee: Decimal::permille(1),
+        };
+        let res = app.execute(creator, msg.into()).unwrap();
+        let CreatePoolResponse {
+            pool_id: stableswap,
+        } = from_slice(res.data.unwrap().as_slice()).unwrap();
+
+        let querier = QuerierWrapper::<OsmosisQuery>::new(&app);
+        let querier = OsmosisQuerier::new(&querier);
+        let PoolTypeResponse { pool_type } = querier.pool_type(balancer).unwrap();
+        assert_eq!(pool_type, "balancer");
+        let PoolTypeResponse { pool_type } = querier.pool_type(stableswap).unwrap();
+        assert_eq!(pool_type, "stableswap");
+        querier.pool_type(stableswap + 1).unwrap_err();
+    }
+
+    #[test]
+    fn stableswap_has_less_slippage() {
+        let reserves = vec![coin(1_000_000, "usdc"), coin(1_000_000, "usdt")];
+        let stable = Pool::new_stableswap(reserves.clone(), vec![], Decimal::permille(3)).unwrap();
+        let params = PoolParams {
+            swap_fee: Decimal::permille(3),
+            exit_fee: Decimal::zero(),
+        };
+        let balancer = Pool::new_balancer(
+            &params,
+            reserves
+                .into_iter()
+                .map(|token| PoolAsset::new(token, 1u128))
+                .collect(),
+        )
+        .unwrap();
+
+        // both start at the peg
+        for pool in [&stable, &balancer] {
+            let price = pool
+                .spot_price("usdc", "usdt", SpotPriceKind::Marginal)
+                .unwrap();
+            assert_eq!(price, Decimal::one());
+        }
+
+        // swapping 10% of the reserves costs the balancer pool ~9% to slippage,
+        // the stableswap pool barely moves
+        let input = SwapAmount::In(Uint128::new(100_000));
+        let balancer_out = balancer
+            .clone()
+            .swap("usdc", "usdt", input.clone())
+            .unwrap();
+        assert_eq!(balancer_out, SwapAmount::Out(Uint128::new(90_662)));
+        let stable_out = stable.clone().swap("usdc", "usdt", input).unwrap();
+        assert_eq!(stable_out, SwapAmount::Out(Uint128::new(99_650)));
+
+        // the same holds when asking for an exact output
+        let output = SwapAmount::Out(Uint128::new(99_650));
+        let stable_in = stable.clone().swap("usdc", "usdt", output.clone()).unwrap();
+        assert_eq!(stable_in, SwapAmount::In(Uint128::new(100_000)));
+        let balancer_in = balancer.clone().swap("usdc", "usdt", output).unwrap();
+        assert!(balancer_in.as_in() > Uint128::new(110_000));
+
+        // after the swap the stableswap price only drifted slightly from the peg
+        let mut stable = stable;
+        stable
+            .swap("usdc", "usdt", SwapAmount::In(Uint128::new(100_000)))
+            .unwrap();
+        let price = stable
+            .spot_price("usdc", "usdt", SpotPriceKind::Marginal)
+            .unwrap();
+        assert!(price < Decimal::one());
+        assert!(price > Decimal::percent(98));
+    }
+
+    #[test]
+    fn stableswap_spot_price_overflow() {
+        let reserves = vec![
+            coin(100_000_000_000_000_000_000_000_000_000_000_000_000, "usdc"),
+            coin(100_000_000_000_000_000_000_000_000_000_000_000_000, "usdt"),
+        ];
+        let pool = Pool::new_stableswap(reserves, vec![], Decimal::permille(3)).unwrap();
+        let err = pool
+            .spot_price("usdc", "usdt", SpotPriceKind::Marginal)
+            .unwrap_err();
+        assert!(matches!(err, OsmosisError::Overflow(_)));
+    }
+
+    #[test]
+    fn create_stableswap_pool_with_scaling_factors() {
+        let creator = Addr::unchecked("creator");
+        // dai has 12 more decimals than usdc
+        let liquidity = vec![
+            coin(1_000_000_000_000, "usdc"),
+            coin(1_000_000_000_000_000_000_000_000, "dai"),
+        ];
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &creator, liquidity.clone())
+                .unwrap();
+        });
+
+        // scaling factors must match the assets
+        let msg = OsmosisMsg::CreateStableswapPool {
+            initial_pool_liquidity: liquidity.clone(),
+            scaling_factors: vec![1],
+            swap_fee: Decimal::permille(1),
+        };
+        let err = app.execute(creator.clone(), msg.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::ScalingFactorsMismatch {
+                expected: 2,
+                got: 1
+            }
+        );
+
+        let msg = OsmosisMsg::CreateStableswapPool {
+            initial_pool_liquidity: liquidity.clone(),
+            scaling_factors: vec![1, 1_000_000_000_000],
+            swap_fee: Decimal::permille(1),
+        };
+        let res = app.execute(creator, msg.into()).unwrap();
+        let CreatePoolResponse { pool_id } = from_slice(res.data.unwrap().as_slice()).unwrap();
+
+        let query = OsmosisQuery::PoolState { id: pool_id }.into();
+        let state: PoolStateResponse = app.wrap().query(&query).unwrap();
+        assert_eq!(state.assets, liquidity);
+
+        // one usdc is pegged to 10^12 units of dai
+        let query = OsmosisQuery::spot_price(pool_id, "usdc", "dai");
+        let SpotPriceResponse { price } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(price, Decimal::from_ratio(1_000_000_000_000u128, 1u128));
+
+        // and swaps near that rate
+        let query = OsmosisQuery::estimate_swap_exact_amount_in(
+            MOCK_CONTRACT_ADDR,
+            pool_id,
+            "usdc",
+            "dai",
+            1_000_000u128,
+        );
+        let EstimateSwapInResponse { token_out } = app.wrap().query(&query.into()).unwrap();
+        assert!(token_out > Uint128::new(998_000_000_000_000_000));
+        assert!(token_out < Uint128::new(999_000_000_000_000_000));
+    }
+
+    #[test]
+    fn query_superfluid_delegation() {
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            let lock = Lock::new("staker", 1_209_600, coins(500, "gamm/pool/1"));
+            router.custom.set_lock(storage, 1, &lock).unwrap();
+            let lock = Lock::new("staker", 1_209_600, coins(700, "gamm/pool/1"));
+            router.custom.set_lock(storage, 2, &lock).unwrap();
+            router
+                .custom
+                .set_superfluid_delegation(storage, 2, "osmovaloper1")
+                .unwrap();
+        });
+        let querier = QuerierWrapper::<OsmosisQuery>::new(&app);
+        let querier = OsmosisQuerier::new(&querier);
+
+        // only lock 2 is delegated
+        let res = querier.superfluid_delegation(1).unwrap();
+        assert_eq!(
+            res,
+            SuperfluidDelegationResponse {
+                validator: None,
+                delegated_coins: vec![],
+            }
+        );
+        let res = querier.superfluid_delegation(2).unwrap();
+        assert_eq!(
+            res,
+            SuperfluidDelegationResponse {
+                validator: Some("osmovaloper1".to_string()),
+                delegated_coins: coins(700, "gamm/pool/1"),
+            }
+        );
+
+        // unknown locks error
+        querier.superfluid_delegation(3).unwrap_err();
+    }
+
+    #[test]
+    #[should_panic(expected = "epoch duration must be positive")]
+    fn zero_epoch_duration() {
+        OsmosisApp::new_with_epoch_duration(0);
+    }
+
+    #[test]
+    fn query_epoch_info() {
+        // a short epoch of 100 blocks
+        let duration = 100 * BLOCK_TIME;
+        let mut app = OsmosisApp::new_with_epoch_duration(duration);
+        let start_time = app.block_info().time.seconds() as i64;
+
+        let wrapper = QuerierWrapper::<OsmosisQuery>::new(&*app);
+        let querier = OsmosisQuerier::new(&wrapper);
+        let info = querier.epoch_info("day".to_string()).unwrap();
+        assert_eq!(
+            info,
+            EpochInfoResponse {
+                current_epoch: 1,
+                start_time,
+                duration,
+                current_epoch_start_time: start_time,
+            }
+        );
+
+        // halfway through the third epoch
+        app.advance_blocks(250);
+        let wrapper = QuerierWrapper::<OsmosisQuery>::new(&*app);
+        let querier = OsmosisQuerier::new(&wrapper);
+        let info = querier.epoch_info("day".to_string()).unwrap();
+        assert_eq!(info.current_epoch, 3);
+        assert_eq!(info.start_time, start_time);
+        assert_eq!(
+            info.current_epoch_start_time,
+            start_time + 2 * duration as i64
+        );
+
+        // a new epoch starts exactly on the boundary
+        app.advance_blocks(50);
+        let wrapper = QuerierWrapper::<OsmosisQuery>::new(&*app);
+        let querier = OsmosisQuerier::new(&wrapper);
+        let info = querier.epoch_info("day".to_string()).unwrap();
+        assert_eq!(info.current_epoch, 4);
+        assert_eq!(
+            info.current_epoch_start_time,
+            start_time + 3 * duration as i64
+        );
+
+        // only the day epoch is mocked
+        let err = querier.epoch_info("week".to_string()).unwrap_err();
+        assert!(
+            err.to_string().contains("Epoch week does not exist"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn create_gauge() {
+        let owner = Addr::unchecked("owner");
+        let funds = vec![coin(10_000, "osmo"), coin(500, "uion")];
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &owner, funds.clone())
+                .unwrap()
+        });
+        let now = (app.block_info().time.nanos() / 1_000_000) as i64;
+
+        // gauges must start now or later
+        let msg = OsmosisMsg::CreateGauge {
+            is_perpetual: false,
+            denom: "gamm/pool/1".to_string(),
+            coins: funds.clone(),
+            start_time: now - 1,
+            num_epochs_paid_over: 7,
+        };
+        let err = app.execute(owner.clone(), msg.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::GaugeStartInPast {
+                start_time: now - 1,
+                now
+            }
+        );
+
+        // and pay something
+        let msg = OsmosisMsg::CreateGauge {
+            is_perpetual: false,
+            denom: "gamm/pool/1".to_string(),
+            coins: vec![],
+            start_time: now,
+            num_epochs_paid_over: 7,
+        };
+        let err = app.execute(owner.clone(), msg.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::EmptyGauge
+        );
+
+        let msg = OsmosisMsg::CreateGauge {
+            is_perpetual: false,
+            denom: "gamm/pool/1".to_string(),
+            coins: funds.clone(),
+            start_time: now,
+            num_epochs_paid_over: 7,
+        };
+        let res = app.execute(owner.clone(), msg.into()).unwrap();
+        let CreateGaugeResponse { gauge_id } = from_slice(res.data.unwrap().as_slice()).unwrap();
+        assert_eq!(gauge_id, 1);
+
+        // the coins went into escrow
+        let balances = app.wrap().query_all_balances(&owner).unwrap();
+        assert_eq!(balances, vec![]);
+
+        let gauge = app
+            .read_module(|_, _, storage| GAUGES.load(storage, gauge_id))
+            .unwrap();
+        assert_eq!(
+            gauge,
+            Gauge {
+                owner,
+                is_perpetual: false,
+                denom: "gamm/pool/1".to_string(),
+                coins: funds,
+                start_time: now,
+                num_epochs_paid_over: 7,
+                filled_epochs: 0,
+                distributed_coins: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn query_gauge_by_id() {
+        let owner = Addr::unchecked("owner");
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &owner, coins(7_000, "osmo"))
+                .unwrap()
+        });
+        let start_time = (app.block_info().time.nanos() / 1_000_000) as i64 + 60_000;
+        let msg = OsmosisMsg::CreateGauge {
+            is_perpetual: true,
+            denom: "gamm/pool/3".to_string(),
+            coins: coins(7_000, "osmo"),
+            start_time,
+            num_epochs_paid_over: 1,
+        };
+        let res = app.execute(owner, msg.into()).unwrap();
+        let CreateGaugeResponse { gauge_id } = from_slice(res.data.unwrap().as_slice()).unwrap();
+
+        let wrapper = QuerierWrapper::<OsmosisQuery>::new(&*app);
+        let querier = OsmosisQuerier::new(&wrapper);
+        let gauge = querier.gauge_by_id(gauge_id).unwrap();
+        assert_eq!(
+            gauge,
+            GaugeResponse {
+                id: gauge_id,
+                is_perpetual: true,
+                denom: "gamm/pool/3".to_string(),
+                coins: coins(7_000, "osmo"),
+                start_time,
+                num_epochs_paid_over: 1,
+                filled_epochs: 0,
+                distributed_coins: vec![],
+            }
+        );
+
+        let err = querier.gauge_by_id(gauge_id + 1).unwrap_err();
+        assert!(
+            err.to_string().contains("Gauge 2 does not exist"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn add_to_gauge() {
+        let owner = Addr::unchecked("owner");
+        let donor = Addr::unchecked("donor");
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &owner, coins(1_000, "osmo"))
+                .unwrap();
+            router
+                .bank
+                .init_balance(storage, &donor, vec![coin(500, "osmo"), coin(200, "uion")])
+                .unwrap();
+        });
+        let start_time = (app.block_info().time.nanos() / 1_000_000) as i64;
+        let msg = OsmosisMsg::CreateGauge {
+            is_perpetual: true,
+            denom: "gamm/pool/1".to_string(),
+            coins: coins(1_000, "osmo"),
+            start_time,
+            num_epochs_paid_over: 1,
+        };
+        let res = app.execute(owner, msg.into()).unwrap();
+        let CreateGaugeResponse { gauge_id } = from_slice(res.data.unwrap().as_slice()).unwrap();
+
+        // unknown gauges cannot be topped up
+        let msg = OsmosisMsg::AddToGauge {
+            gauge_id: gauge_id + 1,
+            coins: coins(500, "osmo"),
+        };
+        let err = app.execute(donor.clone(), msg.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::GaugeNotFound(gauge_id + 1)
+        );
+
+        // anyone can add to an existing one
+        let msg = OsmosisMsg::AddToGauge {
+            gauge_id,
+            coins: vec![coin(500, "osmo"), coin(200, "uion")],
+        };
+        app.execute(donor.clone(), msg.into()).unwrap();
+        let balances = app.wrap().query_all_balances(&donor).unwrap();
+        assert_eq!(balances, vec![]);
+
+        let wrapper = QuerierWrapper::<OsmosisQuery>::new(&*app);
+        let querier = OsmosisQuerier::new(&wrapper);
+        let gauge = querier.gauge_by_id(gauge_id).unwrap();
+        assert_eq!(gauge.coins, vec![coin(1_500, "osmo"), coin(200, "uion")]);
+    }
+
+    #[test]
+    fn swap_with_deadline() {
+        let pool = Pool::new(coin(6_000_000, "osmo"), coin(3_000_000, "atom"));
+        let trader = Addr::unchecked("trader");
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, 1, &pool).unwrap();
+            router
+                .bank
+                .init_balance(storage, &trader, coins(5000, "osmo"))
+                .unwrap()
+        });
+        // one minute from now
+        let deadline = (app.block_info().time.nanos() / 1_000_000) as i64 + 60_000;
+        let msg = OsmosisMsg::SwapWithDeadline {
+            first: Swap::new(1, "osmo", "atom"),
+            route: vec![],
+            amount: SwapAmountWithLimit::ExactIn {
+                input: Uint128::new(1000),
+                min_output: Uint128::new(1),
+            },
+            deadline: Some(deadline),
+        };
+
+        // in time, this swaps like a plain Swap
+        let res = app.execute(trader.clone(), msg.clone().into()).unwrap();
+        let SwapResponse { amount } = SwapResponse::from_data(&res.data).unwrap();
+        assert_eq!(amount, SwapAmount::Out(Uint128::new(499)));
+
+        // exactly on the deadline is still fine
+        app.advance_seconds(60);
+        app.execute(trader.clone(), msg.clone().into()).unwrap();
+
+        // but after it the swap is rejected and nothing moves
+        app.advance_seconds(1);
+        let err = app.execute(trader.clone(), msg.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::Expired {
+                deadline,
+                now: deadline + 1_000
+            }
+        );
+        let balance = app.wrap().query_balance(&trader, "osmo").unwrap();
+        assert_eq!(balance.amount, Uint128::new(3000));
+    }
+
+    #[test]
+    fn configurable_block_time() {
+        let mut app = OsmosisApp::new().with_block_time(6);
+        let start = app.block_info();
+
+        app.advance_blocks(10);
+        let block = app.block_info();
+        assert_eq!(block.height, start.height + 10);
+        assert_eq!(block.time, start.time.plus_seconds(60));
+
+        // and the other way round
+        app.advance_seconds(30);
+        let end = app.block_info();
+        assert_eq!(end.height, block.height + 5);
+
+        // the default is unchanged
+        let mut app = OsmosisApp::new();
+        let start = app.block_info();
+        app.advance_blocks(10);
+        assert_eq!(
+            app.block_info().time,
+            start.time.plus_seconds(10 * BLOCK_TIME)
+        );
+    }
+
+    #[test]
+    fn query_pool_liquidity_of() {
+        let pool = Pool::new(coin(6_000_000, "osmo"), coin(1_500_000, "atom"));
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| router.custom.set_pool(storage, 1, &pool))
+            .unwrap();
+
+        let wrapper = QuerierWrapper::<OsmosisQuery>::new(&*app);
+        let querier = OsmosisQuerier::new(&wrapper);
+        let PoolLiquidityResponse { amount } =
+            querier.pool_liquidity_of(1, "atom".to_string()).unwrap();
+        assert_eq!(amount, Uint128::new(1_500_000));
+
+        let err = querier.pool_liquidity_of(1, "btc".to_string()).unwrap_err();
+        assert!(
+            err.to_string().contains("asset btc not in pool 1"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn query_pools_paginated() {
+        let pools: Vec<_> = (1..=5u128)
+            .map(|i| Pool::new(coin(i * 1_000, "osmo"), coin(i * 2_000, "atom")))
+            .collect();
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            for (id, pool) in (1..).zip(&pools) {
+                router.custom.set_pool(storage, id, pool).unwrap();
+            }
+        });
+
+        let query = OsmosisQuery::Pools {
+            start_after: None,
+            limit: Some(3),
+        };
+        let PoolsResponse { pools: page } = app.wrap().query(&query.into()).unwrap();
+        let ids: Vec<_> = page.iter().map(|p| p.id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        assert_eq!(page[0].state, pools[0].clone().into_response(1));
+
+        let query = OsmosisQuery::Pools {
+            start_after: Some(3),
+            limit: Some(3),
+        };
+        let PoolsResponse { pools: page } = app.wrap().query(&query.into()).unwrap();
+        let ids: Vec<_> = page.iter().map(|p| p.id).collect();
+        assert_eq!(ids, vec![4, 5]);
+        assert_eq!(page[1].state, pools[4].clone().into_response(5));
+
+        // no limit returns everything, as there are less than 100
+        let query = OsmosisQuery::Pools {
+            start_after: None,
+            limit: None,
+        };
+        let PoolsResponse { pools: page } = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(page.len(), 5);
+    }
+
+    #[test]
+    fn query_total_pool_liquidity() {
+        let pool = Pool::new(coin(6_000_000, "osmo"), coin(1_500_000, "atom"));
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| router.custom.set_pool(storage, 1, &pool))
+            .unwrap();
+
+        let wrapper = QuerierWrapper::<OsmosisQuery>::new(&*app);
+        let querier = OsmosisQuerier::new(&wrapper);
+        let TotalPoolLiquidityResponse { liquidity } = querier.total_pool_liquidity(1).unwrap();
+        let state: PoolStateResponse = app
+            .wrap()
+            .query(&OsmosisQuery::PoolState { id: 1 }.into())
+            .unwrap();
+        assert_eq!(liquidity, state.assets);
+        assert_eq!(
+            liquidity,
+            vec![coin(6_000_000, "osmo"), coin(1_500_000, "atom")]
+        );
+
+        querier.total_pool_liquidity(2).unwrap_err();
+    }
+
+    #[test]
+    fn seeded_twap_history() {
+        let pool = Pool::new(coin(6_000_000, "osmo"), coin(1_500_000, "atom"));
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| router.custom.set_pool(storage, 1, &pool).unwrap());
+
+        // 1 for the first second, 2 for the next two, then 4
+        app.set_twap_history(
+            1,
+            "osmo",
+            "atom",
+            vec![
+                (0, Decimal::one()),
+                (1_000, Decimal::percent(200)),
+                (3_000, Decimal::percent(400)),
+            ],
+        );
+
+        let wrapper = QuerierWrapper::<OsmosisQuery>::new(&*app);
+        let querier = OsmosisQuerier::new(&wrapper);
+        let twap = |start_time, end_time| {
+            querier
+                .arithmetic_twap(
+                    1,
+                    "osmo".to_string(),
+                    "atom".to_string(),
+                    start_time,
+                    end_time,
+                )
+                .unwrap()
+                .twap
+        };
+
+        // (1 * 1s + 2 * 2s + 4 * 1s) / 4s
+        assert_eq!(twap(0, 4_000), Decimal::percent(225));
+        // (2 * 1s + 4 * 1s) / 2s
+        assert_eq!(twap(2_000, 4_000), Decimal::percent(300));
+        // within a single step
+        assert_eq!(twap(1_000, 3_000), Decimal::percent(200));
+
+        // the reversed pair averages the inverted prices: (1 * 1s + 1/2 * 2s + 1/4 * 1s) / 4s
+        let reversed = querier
+            .arithmetic_twap(1, "atom".to_string(), "osmo".to_string(), 0, 4_000)
+            .unwrap()
+            .twap;
+        assert_eq!(reversed, Decimal::from_ratio(9u128, 16u128));
+    }
+
+    #[test]
+    fn geometric_twap() {
+        let pool = Pool::new(coin(6_000_000, "osmo"), coin(1_500_000, "atom"));
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, 1, &pool).unwrap();
+            router.custom.set_pool(storage, 2, &pool).unwrap();
+            let constant = vec![(0, Decimal::percent(250))];
+            router
+                .custom
+                .set_twap_history(storage, 1, "osmo", "atom", constant)
+                .unwrap();
+            // 1 for the first second, 4 for the next
+            let varying = vec![(1_000, Decimal::percent(400)), (0, Decimal::one())];
+            router
+                .custom
+                .set_twap_history(storage, 2, "osmo", "atom", varying)
+                .unwrap();
+        });
+
+        let wrapper = QuerierWrapper::<OsmosisQuery>::new(&*app);
+        let querier = OsmosisQuerier::new(&wrapper);
+        let twap_of = |id| {
+            querier
+                .geometric_twap(id, "osmo".to_string(), "atom".to_string(), 0, 2_000)
+                .unwrap()
+                .twap
+        };
+
+        let arithmetic_of = |id| {
+            querier
+                .arithmetic_twap(id, "osmo".to_string(), "atom".to_string(), 0, 2_000)
+                .unwrap()
+                .twap
+        };
+
+        // a constant price is its own geometric and arithmetic mean
+        assert_eq!(twap_of(1), Decimal::percent(250));
+        assert_eq!(arithmetic_of(1), Decimal::percent(250));
+
+        // otherwise the geometric mean is lower: sqrt(1 * 4) = 2 < (1 + 4) / 2
+        let arithmetic = arithmetic_of(2);
+        assert_eq!(arithmetic, Decimal::percent(250));
+        let geometric = twap_of(2);
+        assert!(geometric < arithmetic);
+        let error = if geometric > Decimal::percent(200) {
+            geometric - Decimal::percent(200)
+        } else {
+            Decimal::percent(200) - geometric
+        };
+        assert!(error < Decimal::raw(10_000_000_000), "{}", geometric);
+
+        // the reversed pair averages over the inverted prices: sqrt(1 * 1/4) = 1/2
+        let reversed = querier
+            .geometric_twap(2, "atom".to_string(), "osmo".to_string(), 0, 2_000)
+            .unwrap()
+            .twap;
+        let error = if reversed > Decimal::percent(50) {
+            reversed - Decimal::percent(50)
+        } else {
+            Decimal::percent(50) - reversed
+        };
+        assert!(error < Decimal::raw(10_000_000_000), "{}", reversed);
+
+        // the window has to be ordered
+        let err = querier
+            .geometric_twap(2, "osmo".to_string(), "atom".to_string(), 2_000, 2_000)
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("must start before it ends"),
+            "{}",
+            err
+        );
+
+        // both denoms must be in the pool, even though it has a history
+        for (quote, base) in [("btc", "eth"), ("osmo", "btc"), ("btc", "atom")] {
+            let err = querier
+                .geometric_twap(2, quote.to_string(), base.to_string(), 0, 2_000)
+                .unwrap_err();
+            let expected = OsmosisError::AssetNotInPool.to_string();
+            assert!(err.to_string().contains(&expected), "{}", err);
+        }
+
+        // and a history without any observations has nothing to average
+        app.set_twap_history(1, "osmo", "atom", vec![]);
+        let wrapper = QuerierWrapper::<OsmosisQuery>::new(&*app);
+        let querier = OsmosisQuerier::new(&wrapper);
+        let err = querier
+            .geometric_twap(1, "osmo".to_string(), "atom".to_string(), 0, 2_000)
+            .unwrap_err();
+        let expected = OsmosisError::EmptyTwapWindow.to_string();
+        assert!(err.to_string().contains(&expected), "{}", err);
+        let err = querier
+            .arithmetic_twap(1, "atom".to_string(), "osmo".to_string(), 0, 2_000)
+            .unwrap_err();
+        assert!(err.to_string().contains(&expected), "{}", err);
+    }
+
+    #[test]
+    fn estimate_price_impact() {
+        let deep = Pool::new(coin(600_000_000, "osmo"), coin(150_000_000, "atom"));
+        let shallow = Pool::new(coin(60_000, "osmo"), coin(15_000, "atom"));
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, 1, &deep).unwrap();
+            router.custom.set_pool(storage, 2, &shallow).unwrap();
+        });
+
+        let impact_of = |pool_id| {
+            let query = OsmosisQuery::EstimatePriceImpact {
+                first: Swap::new(pool_id, "osmo", "atom"),
+                route: vec![],
+                amount: SwapAmount::In(Uint128::new(20_000)),
+            };
+            let res: PriceImpactResponse = app.wrap().query(&query.into()).unwrap();
+            res
+        };
+
+        // in a deep pool only the 0.3% fee is felt
+        let res = impact_of(1);
+        assert_eq!(res.spot_price_before, Decimal::percent(25));
+        assert!(res.impact < Decimal::permille(4), "{}", res.impact);
+
+        // while a third of a shallow pool moves the price a lot:
+        // 20_000 * 0.997 = 19_940 in gets 15_000 - 60_000 * 15_000 / 79_940 = 3742 out
+        let res = impact_of(2);
+        assert_eq!(res.spot_price_before, Decimal::percent(25));
+        assert_eq!(
+            res.effective_price,
+            Decimal::from_ratio(3742u128, 20_000u128)
+        );
+        assert!(res.impact > Decimal::percent(25), "{}", res.impact);
+
+        // and the estimate does not touch the pool
+        let state: PoolStateResponse = app
+            .wrap()
+            .query(&OsmosisQuery::PoolState { id: 2 }.into())
+            .unwrap();
+        assert_eq!(state, shallow.into_response(2));
+    }
 
+    #[test]
+    fn query_pool_state_by_lp_denom() {
+        let pool = Pool::new(coin(6_000_000, "osmo"), coin(1_500_000, "atom"));
         let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| router.custom.set_pool(storage, 12, &pool))
+            .unwrap();
 
-        // no tokens
-        let start = app.wrap().query_all_balances(rcpt.as_str()).unwrap();
-        assert_eq!(start, vec![]);
+        let query = OsmosisQuery::PoolStateByLpDenom {
+            lp_denom: "gamm/pool/12".to_string(),
+        };
+        let state: PoolStateResponse = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(state, pool.into_response(12));
 
-        // let's find the mapping
-        let FullDenomResponse { denom } = app
+        let query = OsmosisQuery::PoolStateByLpDenom {
+            lp_denom: "gamm/pool/twelve".to_string(),
+        };
+        let err = app
             .wrap()
-            .query(
-                &OsmosisQuery::FullDenom {
-                    creator_addr: contract.to_string(),
-                    subdenom: subdenom.to_string(),
-                }
-                .into(),
-            )
-            .unwrap();
-        assert_ne!(denom, subdenom);
-        assert!(denom.len() > 10);
+            .query::<PoolStateResponse>(&query.into())
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("gamm/pool/twelve is not an LP share denom"),
+            "{}",
+            err
+        );
 
-        // prepare to mint
-        let amount = Uint128::new(1234567);
-        let msg = OsmosisMsg::MintTokens {
-            denom: denom.to_string(),
-            amount,
-            mint_to_address: rcpt.to_string(),
+        // well formed, but no such pool
+        let query = OsmosisQuery::PoolStateByLpDenom {
+            lp_denom: "gamm/pool/13".to_string(),
         };
+        app.wrap()
+            .query::<PoolStateResponse>(&query.into())
+            .unwrap_err();
+    }
 
-        // simulate contract calling
-        // TODO: How is this not erroring, the token isn't created
-        app.execute(contract, msg.into()).unwrap();
+    #[test]
+    fn merge_coins_by_denom() {
+        let merged = merge_coins(vec![
+            coin(300, "uosmo"),
+            coin(100, "atom"),
+            coin(0, "btc"),
+            coin(200, "uosmo"),
+            coin(50, "atom"),
+        ])
+        .unwrap();
+        assert_eq!(merged, vec![coin(150, "atom"), coin(500, "uosmo")]);
 
-        // we got tokens!
-        let end = app.wrap().query_balance(rcpt.as_str(), &denom).unwrap();
-        let expected = Coin { denom, amount };
-        assert_eq!(end, expected);
+        // zero totals vanish, as do empty inputs
+        let merged = merge_coins(vec![coin(0, "atom"), coin(0, "atom")]).unwrap();
+        assert_eq!(merged, vec![]);
+        assert_eq!(merge_coins(vec![]).unwrap(), vec![]);
 
-        // but no minting of unprefixed version
-        let empty = app.wrap().query_balance(rcpt.as_str(), subdenom).unwrap();
-        assert_eq!(empty.amount, Uint128::zero());
+        let err = merge_coins(vec![coin(u128::MAX, "atom"), coin(1, "atom")]).unwrap_err();
+        assert!(matches!(err, OsmosisError::Overflow(_)));
     }
 
     #[test]
-    fn query_pool() {
-        let coin_a = coin(6_000_000u128, "osmo");
-        let coin_b = coin(1_500_000u128, "atom");
-        let pool_id = 43;
-        let pool = Pool::new(coin_a.clone(), coin_b.clone());
+    fn create_position() {
+        let owner = Addr::unchecked("owner");
+        let other = Addr::unchecked("other");
+        let pool = Pool::new_concentrated("osmo", "usdc", Decimal::permille(2));
 
-        // set up with one pool
         let mut app = OsmosisApp::new();
         app.init_modules(|router, _, storage| {
-            router.custom.set_pool(storage, pool_id, &pool).unwrap();
+            router.custom.set_pool(storage, 1, &pool).unwrap();
+            router
+                .custom
+                .set_pool(storage, 2, &Pool::new(coin(100, "osmo"), coin(100, "usdc")))
+                .unwrap();
+            let funds = vec![coin(100_000, "osmo"), coin(100_000, "usdc")];
+            router
+                .bank
+                .init_balance(storage, &owner, funds.clone())
+                .unwrap();
+            router.bank.init_balance(storage, &other, funds).unwrap();
         });
 
-        // query the pool state
-        let query = OsmosisQuery::PoolState { id: pool_id }.into();
-        let state: PoolStateResponse = app.wrap().query(&query).unwrap();
-        let expected_shares = coin(3_000_000, "gamm/pool/43");
-        assert_eq!(state.shares, expected_shares);
-        assert_eq!(state.assets, vec![coin_a.clone(), coin_b.clone()]);
+        let create = |pool_id, lower_tick, upper_tick, tokens_provided: Vec<Coin>| {
+            OsmosisMsg::CreatePosition {
+                pool_id,
+                lower_tick,
+                upper_tick,
+                tokens_provided,
+                token_min_amount0: Uint128::zero(),
+                token_min_amount1: Uint128::zero(),
+            }
+        };
+        let tokens = vec![coin(40_000, "osmo"), coin(10_000, "usdc")];
 
-        // check spot price both directions
-        let query = OsmosisQuery::spot_price(pool_id, &coin_a.denom, &coin_b.denom).into();
-        let SpotPriceResponse { price } = app.wrap().query(&query).unwrap();
-        assert_eq!(price, Decimal::percent(25));
+        // ticks must form a range
+        let err = app
+            .execute(owner.clone(), create(1, 100, 100, tokens.clone()).into())
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::InvalidTickRange {
+                lower_tick: 100,
+                upper_tick: 100
+            }
+        );
+        // with something in it
+        let err = app
+            .execute(owner.clone(), create(1, -100, 100, vec![]).into())
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::ZeroAmount
+        );
+        // in a concentrated pool
+        let err = app
+            .execute(owner.clone(), create(2, -100, 100, tokens.clone()).into())
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::NotConcentratedPool(2)
+        );
 
-        // and atom -> osmo
-        let query = OsmosisQuery::spot_price(pool_id, &coin_b.denom, &coin_a.denom).into();
-        let SpotPriceResponse { price } = app.wrap().query(&query).unwrap();
-        assert_eq!(price, Decimal::percent(400));
+        // the first position sets the price, sqrt(40_000 * 10_000) = 20_000 liquidity
+        let res = app
+            .execute(owner.clone(), create(1, -100, 100, tokens).into())
+            .unwrap();
+        let CreatePositionResponse {
+            position_id,
+            amount0,
+            amount1,
+            liquidity_created,
+        } = from_slice(res.data.unwrap().as_slice()).unwrap();
+        assert_eq!(position_id, 1);
+        assert_eq!(
+            (amount0, amount1),
+            (Uint128::new(40_000), Uint128::new(10_000))
+        );
+        assert_eq!(liquidity_created, "20000");
 
-        // with fee
-        let query = OsmosisQuery::SpotPrice {
-            swap: Swap::new(pool_id, &coin_b.denom, &coin_a.denom),
-            with_swap_fee: true,
-        };
-        let SpotPriceResponse { price } = app.wrap().query(&query.into()).unwrap();
-        // 4.00 * (1- 0.3%) = 4 * 0.997 = 3.988
-        assert_eq!(price, Decimal::permille(3988));
+        // the next one joins at that price, refunding the surplus osmo
+        let res = app
+            .execute(
+                other.clone(),
+                create(1, 0, 500, vec![coin(80_000, "osmo"), coin(5_000, "usdc")]).into(),
+            )
+            .unwrap();
+        let CreatePositionResponse {
+            position_id,
+            amount0,
+            amount1,
+            ..
+        } = from_slice(res.data.unwrap().as_slice()).unwrap();
+        assert_eq!(position_id, 2);
+        assert_eq!(
+            (amount0, amount1),
+            (Uint128::new(20_000), Uint128::new(5_000))
+        );
+        let osmo = app.wrap().query_balance(&other, "osmo").unwrap();
+        assert_eq!(osmo.amount, Uint128::new(80_000));
+
+        let position = app
+            .read_module(|_, _, storage| POSITIONS.load(storage, position_id))
+            .unwrap();
+        assert_eq!(
+            position,
+            Position {
+                pool_id: 1,
+                owner: other,
+                lower_tick: 0,
+                upper_tick: 500,
+                liquidity: Uint128::new(10_000),
+                spread_rewards: vec![],
+                incentives: vec![],
+            }
+        );
     }
 
     #[test]
-    fn estimate_swap() {
-        let coin_a = coin(6_000_000u128, "osmo");
-        let coin_b = coin(1_500_000u128, "atom");
-        let pool_id = 43;
-        let pool = Pool::new(coin_a.clone(), coin_b.clone());
+    fn gamm_messages_reject_concentrated_pools() {
+        let owner = Addr::unchecked("owner");
+        let pool = Pool::new_concentrated("osmo", "usdc", Decimal::permille(2));
 
-        // set up with one pool
         let mut app = OsmosisApp::new();
         app.init_modules(|router, _, storage| {
-            router.custom.set_pool(storage, pool_id, &pool).unwrap();
+            router.custom.set_pool(storage, 1, &pool).unwrap();
+            let funds = vec![coin(100_000, "osmo"), coin(100_000, "usdc")];
+            router.bank.init_balance(storage, &owner, funds).unwrap();
         });
+        let create = OsmosisMsg::CreatePosition {
+            pool_id: 1,
+            lower_tick: -100,
+            upper_tick: 100,
+            tokens_provided: vec![coin(40_000, "osmo"), coin(10_000, "usdc")],
+            token_min_amount0: Uint128::zero(),
+            token_min_amount1: Uint128::zero(),
+        };
+        app.execute(owner.clone(), create.into()).unwrap();
 
-        // estimate the price (501505 * 0.997 = 500_000) after fees gone
-        let query = OsmosisQuery::estimate_swap(
-            MOCK_CONTRACT_ADDR,
-            pool_id,
-            &coin_b.denom,
-            &coin_a.denom,
-            SwapAmount::In(Uint128::new(501505)),
-        );
-        let SwapResponse { amount } = app.wrap().query(&query.into()).unwrap();
-        // 6M * 1.5M = 2M * 4.5M -> output = 1.5M
-        let expected = SwapAmount::Out(Uint128::new(1_500_000));
-        assert_eq!(amount, expected);
+        let pool_state = |app: &OsmosisApp| -> PoolStateResponse {
+            app.wrap()
+                .query(&OsmosisQuery::PoolState { id: 1 }.into())
+                .unwrap()
+        };
+        let before = pool_state(&app);
 
-        // now try the reverse query. we know what we need to pay to get 1.5M out
-        let query = OsmosisQuery::estimate_swap(
-            MOCK_CONTRACT_ADDR,
-            pool_id,
-            &coin_b.denom,
-            &coin_a.denom,
-            SwapAmount::Out(Uint128::new(1500000)),
+        // no lp shares can be minted against the position liquidity
+        let join = OsmosisMsg::JoinPool {
+            pool_id: 1,
+            share_out_amount: Uint128::new(1_000),
+            token_in_maxs: vec![coin(10_000, "osmo"), coin(10_000, "usdc")],
+        };
+        let err = app.execute(owner.clone(), join.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::ConcentratedPool(1)
         );
-        let SwapResponse { amount } = app.wrap().query(&query.into()).unwrap();
-        let expected = SwapAmount::In(Uint128::new(501505));
-        assert_eq!(amount, expected);
+        let exit = OsmosisMsg::ExitPool {
+            pool_id: 1,
+            share_in_amount: Uint128::new(1_000),
+            token_out_mins: vec![],
+        };
+        let err = app.execute(owner.clone(), exit.into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::ConcentratedPool(1)
+        );
+        assert_eq!(pool_state(&app), before);
+        let balance = app.wrap().query_balance(&owner, "gamm/pool/1").unwrap();
+        assert_eq!(balance.amount, Uint128::zero());
+
+        // nor be estimated
+        let expected = OsmosisError::ConcentratedPool(1).to_string();
+        let query = OsmosisQuery::CalcJoinPoolShares {
+            pool_id: 1,
+            tokens_in: vec![coin(10_000, "osmo")],
+        };
+        let err = app
+            .wrap()
+            .query::<CalcJoinPoolSharesResponse>(&query.into())
+            .unwrap_err();
+        assert!(err.to_string().contains(&expected), "{}", err);
+        let query = OsmosisQuery::CalcExitPoolCoinsFromShares {
+            pool_id: 1,
+            share_in_amount: Uint128::new(1_000),
+        };
+        let err = app
+            .wrap()
+            .query::<CalcExitResponse>(&query.into())
+            .unwrap_err();
+        assert!(err.to_string().contains(&expected), "{}", err);
     }
 
     #[test]
-    fn perform_swap() {
-        let coin_a = coin(6_000_000u128, "osmo");
-        let coin_b = coin(1_500_000u128, "atom");
-        let pool_id = 43;
-        let pool = Pool::new(coin_a.clone(), coin_b.clone());
-        let trader = Addr::unchecked("trader");
+    fn withdraw_half_position() {
+        let owner = Addr::unchecked("owner");
+        let other = Addr::unchecked("other");
+        let pool = Pool::new_concentrated("osmo", "usdc", Decimal::permille(2));
 
-        // set up with one pool
         let mut app = OsmosisApp::new();
         app.init_modules(|router, _, storage| {
-            router.custom.set_pool(storage, pool_id, &pool).unwrap();
-            router
-                .bank
-                .init_balance(storage, &trader, coins(800_000, &coin_b.denom))
-                .unwrap()
+            router.custom.set_pool(storage, 1, &pool).unwrap();
+            let funds = vec![coin(40_000, "osmo"), coin(10_000, "usdc")];
+            router.bank.init_balance(storage, &owner, funds).unwrap();
         });
 
-        // check balance before
-        let Coin { amount, .. } = app.wrap().query_balance(&trader, &coin_a.denom).unwrap();
-        assert_eq!(amount, Uint128::new(0));
-        let Coin { amount, .. } = app.wrap().query_balance(&trader, &coin_b.denom).unwrap();
-        assert_eq!(amount, Uint128::new(800_000));
+        let create = OsmosisMsg::CreatePosition {
+            pool_id: 1,
+            lower_tick: -100,
+            upper_tick: 100,
+            tokens_provided: vec![coin(40_000, "osmo"), coin(10_000, "usdc")],
+            token_min_amount0: Uint128::zero(),
+            token_min_amount1: Uint128::zero(),
+        };
+        app.execute(owner.clone(), create.into()).unwrap();
+        let withdraw = |liquidity_amount: &str| OsmosisMsg::WithdrawPosition {
+            position_id: 1,
+            liquidity_amount: liquidity_amount.to_string(),
+        };
 
-        // this is too low a payment, will error
-        let msg = OsmosisMsg::simple_swap(
-            pool_id,
-            &coin_b.denom,
-            &coin_a.denom,
-            SwapAmountWithLimit::ExactOut {
-                output: Uint128::new(1_500_000),
-                max_input: Uint128::new(400_000),
-            },
+        // only the owner may withdraw
+        let err = app.execute(other, withdraw("10000").into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::Unauthorized
+        );
+        // and no more than the position holds
+        let err = app
+            .execute(owner.clone(), withdraw("20001").into())
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::InsufficientPositionLiquidity {
+                requested: Uint128::new(20_001),
+                available: Uint128::new(20_000),
+            }
         );
-        let err = app.execute(trader.clone(), msg.into()).unwrap_err();
-        println!("{:?}", err);
 
-        // now a proper swap
-        let msg = OsmosisMsg::simple_swap(
-            pool_id,
-            &coin_b.denom,
-            &coin_a.denom,
-            SwapAmountWithLimit::ExactOut {
-                output: Uint128::new(1_500_000),
-                max_input: Uint128::new(600_000),
-            },
+        // half the liquidity pays out half of each asset
+        let res = app
+            .execute(owner.clone(), withdraw("10000.000000000000000000").into())
+            .unwrap();
+        let WithdrawPositionResponse { amount0, amount1 } =
+            from_slice(res.data.unwrap().as_slice()).unwrap();
+        assert_eq!(
+            (amount0, amount1),
+            (Uint128::new(20_000), Uint128::new(5_000))
         );
-        let res = app.execute(trader.clone(), msg.into()).unwrap();
+        let balances = app.wrap().query_all_balances(&owner).unwrap();
+        assert_eq!(balances, vec![coin(20_000, "osmo"), coin(5_000, "usdc")]);
 
-        // update balances (800_000 - 501_505 paid = 298_495)
-        let Coin { amount, .. } = app.wrap().query_balance(&trader, &coin_a.denom).unwrap();
-        assert_eq!(amount, Uint128::new(1_500_000));
-        let Coin { amount, .. } = app.wrap().query_balance(&trader, &coin_b.denom).unwrap();
-        assert_eq!(amount, Uint128::new(298_495));
+        let position = app
+            .read_module(|_, _, storage| POSITIONS.load(storage, 1))
+            .unwrap();
+        assert_eq!(position.liquidity, Uint128::new(10_000));
+    }
 
-        // check the response contains proper value
-        let input: SwapResponse = from_slice(res.data.unwrap().as_slice()).unwrap();
-        assert_eq!(input.amount, SwapAmount::In(Uint128::new(501_505)));
+    #[test]
+    fn position_with_large_liquidity() {
+        // 1000 tokens of 18 decimals each, more liquidity than a Decimal can hold
+        let amount = 1_000_000_000_000_000_000_000u128;
+        let owner = Addr::unchecked("owner");
+        let pool = Pool::new_concentrated("weth", "wbtc", Decimal::permille(2));
 
-        // check pool state properly updated with fees
-        let query = OsmosisQuery::PoolState { id: pool_id }.into();
-        let state: PoolStateResponse = app.wrap().query(&query).unwrap();
-        let expected_assets = vec![
-            coin(4_500_000, &coin_a.denom),
-            coin(2_001_505, &coin_b.denom),
-        ];
-        assert_eq!(state.assets, expected_assets);
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, 1, &pool).unwrap();
+            let funds = vec![coin(amount, "wbtc"), coin(amount, "weth")];
+            router.bank.init_balance(storage, &owner, funds).unwrap();
+        });
+
+        let create = OsmosisMsg::CreatePosition {
+            pool_id: 1,
+            lower_tick: -100,
+            upper_tick: 100,
+            tokens_provided: vec![coin(amount, "weth"), coin(amount, "wbtc")],
+            token_min_amount0: Uint128::zero(),
+            token_min_amount1: Uint128::zero(),
+        };
+        let res = app.execute(owner.clone(), create.into()).unwrap();
+        let CreatePositionResponse {
+            liquidity_created, ..
+        } = from_slice(res.data.unwrap().as_slice()).unwrap();
+        assert_eq!(liquidity_created, amount.to_string());
+
+        // the decimal string the chain reports can be withdrawn again
+        let withdraw = OsmosisMsg::WithdrawPosition {
+            position_id: 1,
+            liquidity_amount: format!("{}.5", liquidity_created),
+        };
+        let res = app.execute(owner.clone(), withdraw.into()).unwrap();
+        let WithdrawPositionResponse { amount0, amount1 } =
+            from_slice(res.data.unwrap().as_slice()).unwrap();
+        assert_eq!(
+            (amount0, amount1),
+            (Uint128::new(amount), Uint128::new(amount))
+        );
+        let balances = app.wrap().query_all_balances(&owner).unwrap();
+        assert_eq!(balances, vec![coin(amount, "wbtc"), coin(amount, "weth")]);
     }
 
     #[test]
-    fn swap_with_route_max_input_exceeded() {
-        let pool1 = Pool::new(coin(6_000_000, "osmo"), coin(3_000_000, "atom"));
-        let pool2 = Pool::new(coin(2_000_000, "atom"), coin(1_000_000, "btc"));
-        let trader = Addr::unchecked("trader");
+    fn query_position_by_id() {
+        let owner = Addr::unchecked("owner");
+        let pool = Pool::new_concentrated("osmo", "usdc", Decimal::permille(2));
 
         let mut app = OsmosisApp::new();
         app.init_modules(|router, _, storage| {
-            router.custom.set_pool(storage, 1, &pool1).unwrap();
-            router.custom.set_pool(storage, 2, &pool2).unwrap();
-            router
-                .bank
-                .init_balance(storage, &trader, coins(5000, "osmo"))
-                .unwrap()
+            router.custom.set_pool(storage, 7, &pool).unwrap();
+            let funds = vec![coin(40_000, "osmo"), coin(10_000, "usdc")];
+            router.bank.init_balance(storage, &owner, funds).unwrap();
         });
 
-        let msg = OsmosisMsg::Swap {
-            first: Swap {
-                pool_id: 1,
-                denom_in: "osmo".to_string(),
-                denom_out: "atom".to_string(),
-            },
-            route: vec![Step {
-                pool_id: 2,
-                denom_out: "btc".to_string(),
-            }],
-            amount: SwapAmountWithLimit::ExactOut {
-                output: Uint128::new(1000),
-                max_input: Uint128::new(4000),
-            },
+        let create = OsmosisMsg::CreatePosition {
+            pool_id: 7,
+            lower_tick: -100,
+            upper_tick: 250,
+            tokens_provided: vec![coin(40_000, "osmo"), coin(10_000, "usdc")],
+            token_min_amount0: Uint128::zero(),
+            token_min_amount1: Uint128::zero(),
+        };
+        app.execute(owner.clone(), create.into()).unwrap();
+
+        let wrapper = QuerierWrapper::<OsmosisQuery>::new(&*app);
+        let querier = OsmosisQuerier::new(&wrapper);
+        let position = querier.position_by_id(1).unwrap();
+        assert_eq!(
+            position,
+            PositionResponse {
+                pool_id: 7,
+                owner: owner.to_string(),
+                lower_tick: -100,
+                upper_tick: 250,
+                liquidity: "20000".to_string(),
+                coins: vec![coin(40_000, "osmo"), coin(10_000, "usdc")],
+            }
+        );
+
+        let err = querier.position_by_id(2).unwrap_err();
+        assert!(
+            err.to_string().contains("Position 2 does not exist"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn query_position_edge_cases() {
+        let owner = Addr::unchecked("owner");
+        let pool = Pool::new_concentrated("osmo", "usdc", Decimal::permille(2));
+        // 1000 tokens of 18 decimals each, more liquidity than a Decimal can hold
+        let amount = 1_000_000_000_000_000_000_000u128;
+
+        let mut app = OsmosisApp::new();
+        app.init_modules(|router, _, storage| {
+            router.custom.set_pool(storage, 1, &pool).unwrap();
+            let funds = vec![coin(amount, "osmo"), coin(amount, "usdc")];
+            router.bank.init_balance(storage, &owner, funds).unwrap();
+        });
+        let create = OsmosisMsg::CreatePosition {
+            pool_id: 1,
+            lower_tick: -100,
+            upper_tick: 100,
+            tokens_provided: vec![coin(amount, "osmo"), coin(amount, "usdc")],
+            token_min_amount0: Uint128::zero(),
+            token_min_amount1: Uint128::zero(),
         };
-        let err = app.execute(trader, msg.into()).unwrap_err();
+        app.execute(owner.clone(), create.into()).unwrap();
+
+        let query = OsmosisQuery::PositionById { position_id: 1 };
+        let position: PositionResponse = app.wrap().query(&query.clone().into()).unwrap();
+        assert_eq!(position.liquidity, amount.to_string());
         assert_eq!(
-            err.downcast::<OsmosisError>().unwrap(),
-            OsmosisError::PriceTooLowExactOut(Uint128::new(4033), Uint128::new(4000))
+            position.coins,
+            vec![coin(amount, "osmo"), coin(amount, "usdc")]
         );
+
+        // withdrawing everything empties the pool, but uncollected rewards keep the position
+        app.init_modules(|router, _, storage| {
+            router.custom.add_incentives(storage, 1, &coins(10, "uion"))
+        })
+        .unwrap();
+        let withdraw = OsmosisMsg::WithdrawPosition {
+            position_id: 1,
+            liquidity_amount: amount.to_string(),
+        };
+        app.execute(owner, withdraw.into()).unwrap();
+
+        let position: PositionResponse = app.wrap().query(&query.into()).unwrap();
+        assert_eq!(position.liquidity, "0");
+        assert_eq!(position.coins, vec![coin(0, "osmo"), coin(0, "usdc")]);
     }
 
     #[test]
-    fn swap_with_route_min_output_not_met() {
-        let pool1 = Pool::new(coin(6_000_000, "osmo"), coin(3_000_000, "atom"));
-        let pool2 = Pool::new(coin(2_000_000, "atom"), coin(1_000_000, "btc"));
+    fn collect_spread_rewards_and_incentives() {
+        let owner = Addr::unchecked("owner");
         let trader = Addr::unchecked("trader");
+        let pool = Pool::new_concentrated("osmo", "usdc", Decimal::percent(1));
 
         let mut app = OsmosisApp::new();
         app.init_modules(|router, _, storage| {
-            router.custom.set_pool(storage, 1, &pool1).unwrap();
-            router.custom.set_pool(storage, 2, &pool2).unwrap();
+            router.custom.set_pool(storage, 1, &pool).unwrap();
+            let funds = vec![coin(80_000, "osmo"), coin(20_000, "usdc")];
+            router.bank.init_balance(storage, &owner, funds).unwrap();
             router
                 .bank
-                .init_balance(storage, &trader, coins(5000, "osmo"))
-                .unwrap()
+                .init_balance(storage, &trader, coins(30_000, "osmo"))
+                .unwrap();
         });
 
-        let msg = OsmosisMsg::Swap {
-            first: Swap {
-                pool_id: 1,
-                denom_in: "osmo".to_string(),
-                denom_out: "atom".to_string(),
-            },
-            route: vec![Step {
-                pool_id: 2,
-                denom_out: "btc".to_string(),
-            }],
-            amount: SwapAmountWithLimit::ExactIn {
-                input: Uint128::new(4000),
-                min_output: Uint128::new(1000),
+        // the current tick is 0, so only the first two positions are in range.
+        // They hold 20_000 and 10_000 liquidity
+        let create = |lower_tick, upper_tick, tokens_provided| OsmosisMsg::CreatePosition {
+            pool_id: 1,
+            lower_tick,
+            upper_tick,
+            tokens_provided,
+            token_min_amount0: Uint128::zero(),
+            token_min_amount1: Uint128::zero(),
+        };
+        for (lower_tick, upper_tick, osmo, usdc) in [
+            (-100, 100, 40_000, 10_000),
+            (0, 50, 20_000, 5_000),
+            (10, 20, 20_000, 5_000),
+        ] {
+            let msg = create(
+                lower_tick,
+                upper_tick,
+                vec![coin(osmo, "osmo"), coin(usdc, "usdc")],
+            );
+            app.execute(owner.clone(), msg.into()).unwrap();
+        }
+
+        // 1% of the input is set aside for the positions
+        let swap = OsmosisMsg::simple_swap(
+            1,
+            "osmo",
+            "usdc",
+            SwapAmountWithLimit::ExactIn {
+                input: Uint128::new(30_000),
+                min_output: Uint128::zero(),
             },
+        );
+        app.execute(trader.clone(), swap.into()).unwrap();
+        let state = app
+            .read_module(|_, _, storage| POOLS.load(storage, 1))
+            .unwrap();
+        assert_eq!(state.get_amount("osmo"), Some(Uint128::new(109_700)));
+
+        // split 2:1 by liquidity, with nothing for the out of range position
+        let rewards: Vec<_> = app.read_module(|_, _, storage| {
+            (1..=3)
+                .map(|id| POSITIONS.load(storage, id).unwrap().spread_rewards)
+                .collect()
+        });
+        assert_eq!(
+            rewards,
+            vec![coins(200, "osmo"), coins(100, "osmo"), vec![]]
+        );
+
+        // only the owner can collect
+        let collect = OsmosisMsg::CollectSpreadRewards {
+            position_ids: vec![1, 2, 3],
         };
-        let err = app.execute(trader, msg.into()).unwrap_err();
+        let err = app.execute(trader, collect.clone().into()).unwrap_err();
         assert_eq!(
             err.downcast::<OsmosisError>().unwrap(),
-            OsmosisError::PriceTooLowExactIn(Uint128::new(993), Uint128::new(1000))
+            OsmosisError::Unauthorized
         );
+
+        let res = app.execute(owner.clone(), collect.clone().into()).unwrap();
+        let CollectSpreadRewardsResponse {
+            collected_spread_rewards,
+        } = from_slice(res.data.unwrap().as_slice()).unwrap();
+        assert_eq!(collected_spread_rewards, coins(300, "osmo"));
+        let osmo = app.wrap().query_balance(&owner, "osmo").unwrap();
+        assert_eq!(osmo.amount, Uint128::new(300));
+
+        // which leaves nothing to collect a second time
+        let res = app.execute(owner.clone(), collect.into()).unwrap();
+        let CollectSpreadRewardsResponse {
+            collected_spread_rewards,
+        } = from_slice(res.data.unwrap().as_slice()).unwrap();
+        assert_eq!(collected_spread_rewards, vec![]);
+
+        // incentives are collected separately
+        app.init_modules(|router, _, storage| {
+            router
+                .custom
+                .add_incentives(storage, 3, &coins(500, "uion"))
+                .unwrap();
+        });
+        let collect = OsmosisMsg::CollectIncentives {
+            position_ids: vec![3],
+        };
+        let res = app.execute(owner.clone(), collect.into()).unwrap();
+        let CollectIncentivesResponse {
+            collected_incentives,
+        } = from_slice(res.data.unwrap().as_slice()).unwrap();
+        assert_eq!(collected_incentives, coins(500, "uion"));
+        let uion = app.wrap().query_balance(&owner, "uion").unwrap();
+        assert_eq!(uion.amount, Uint128::new(500));
     }
 
     #[test]
-    fn swap_with_route_wrong_denom() {
-        let pool1 = Pool::new(coin(6_000_000, "osmo"), coin(3_000_000, "atom"));
-        let pool2 = Pool::new(coin(2_000_000, "atom"), coin(1_000_000, "eth"));
-        let trader = Addr::unchecked("trader");
+    fn query_denom_supply() {
+        let creator = Addr::unchecked("creator");
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
 
         let mut app = OsmosisApp::new();
         app.init_modules(|router, _, storage| {
-            router.custom.set_pool(storage, 1, &pool1).unwrap();
-            router.custom.set_pool(storage, 2, &pool2).unwrap();
             router
                 .bank
-                .init_balance(storage, &trader, coins(5000, "osmo"))
-                .unwrap()
+                .init_balance(storage, &alice, coins(500, "uosmo"))
+                .unwrap();
         });
+        let create = OsmosisMsg::CreateDenom {
+            subdenom: "stable".to_string(),
+        };
+        app.execute(creator.clone(), create.into()).unwrap();
+        let denom = format!("factory/{}/stable", creator);
 
-        let msg = OsmosisMsg::Swap {
-            first: Swap {
-                pool_id: 1,
-                denom_in: "osmo".to_string(),
-                denom_out: "atom".to_string(),
-            },
-            route: vec![Step {
-                pool_id: 2,
-                denom_out: "btc".to_string(),
-            }],
-            amount: SwapAmountWithLimit::ExactOut {
-                output: Uint128::new(1000),
-                max_input: Uint128::new(4000),
-            },
+        let supply = |app: &OsmosisApp, denom: &str| {
+            let wrapper = QuerierWrapper::<OsmosisQuery>::new(&**app);
+            let querier = OsmosisQuerier::new(&wrapper);
+            querier.denom_supply(denom.to_string()).unwrap().amount
         };
-        let err = app.execute(trader, msg.into()).unwrap_err();
-        assert_eq!(
-            err.downcast::<OsmosisError>().unwrap(),
-            OsmosisError::AssetNotInPool
-        );
+        assert_eq!(supply(&app, &denom), Uint128::zero());
+
+        // minted to several accounts
+        for (rcpt, amount) in [(&alice, 1_000u128), (&bob, 250)] {
+            let mint = OsmosisMsg::MintTokens {
+                denom: denom.clone(),
+                amount: Uint128::new(amount),
+                mint_to_address: rcpt.to_string(),
+            };
+            app.execute(creator.clone(), mint.into()).unwrap();
+        }
+        assert_eq!(supply(&app, &denom), Uint128::new(1_250));
+
+        // transfers do not change the supply, burning does
+        let send = BankMsg::Send {
+            to_address: bob.to_string(),
+            amount: coins(400, &denom),
+        };
+        app.execute(alice.clone(), send.into()).unwrap();
+        assert_eq!(supply(&app, &denom), Uint128::new(1_250));
+        let burn = BankMsg::Burn {
+            amount: coins(300, &denom),
+        };
+        app.execute(bob, burn.into()).unwrap();
+        assert_eq!(supply(&app, &denom), Uint128::new(950));
+
+        // other denoms are counted independently
+        assert_eq!(supply(&app, "uosmo"), Uint128::new(500));
     }
 
     #[test]
-    fn perform_swap_with_route_exact_out() {
-        let pool1 = Pool::new(coin(6_000_000, "osmo"), coin(3_000_000, "atom"));
-        let pool2 = Pool::new(coin(2_000_000, "atom"), coin(1_000_000, "btc"));
-        let trader = Addr::unchecked("trader");
+    fn bank_balances_layout() {
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
 
-        // set up pools
         let mut app = OsmosisApp::new();
         app.init_modules(|router, _, storage| {
-            router.custom.set_pool(storage, 1, &pool1).unwrap();
-            router.custom.set_pool(storage, 2, &pool2).unwrap();
+            let funds = vec![coin(500, "uatom"), coin(700, "uosmo")];
             router
                 .bank
-                .init_balance(storage, &trader, coins(5000, "osmo"))
-                .unwrap()
+                .init_balance(storage, &alice, funds.clone())
+                .unwrap();
+            router
+                .bank
+                .init_balance(storage, &bob, coins(300, "uosmo"))
+                .unwrap();
+
+            // balances are stored by address right under the prefix
+            let key = [BANK_BALANCES_PREFIX, alice.as_bytes()].concat();
+            let raw = storage
+                .get(&key)
+                .expect("balance not found under the prefix");
+            let balance: Vec<Coin> = from_slice(&raw).unwrap();
+            assert_eq!(balance, funds);
+
+            assert_eq!(bank_supply(storage, "uosmo").unwrap(), Uint128::new(1_000));
+            assert_eq!(bank_supply(storage, "uatom").unwrap(), Uint128::new(500));
         });
+    }
 
-        let msg = OsmosisMsg::Swap {
-            first: Swap {
-                pool_id: 1,
-                denom_in: "osmo".to_string(),
-                denom_out: "atom".to_string(),
-            },
-            route: vec![Step {
-                pool_id: 2,
-                denom_out: "btc".to_string(),
-            }],
-            amount: SwapAmountWithLimit::ExactOut {
-                output: Uint128::new(1000),
-                max_input: Uint128::new(5000),
-            },
+    #[test]
+    fn mint_within_max_supply() {
+        let creator = Addr::unchecked("creator");
+        let rcpt = Addr::unchecked("rcpt");
+
+        let mut app = OsmosisApp::new();
+        let create = OsmosisMsg::CreateDenom {
+            subdenom: "capped".to_string(),
         };
-        let res = app.execute(trader.clone(), msg.into()).unwrap();
+        let res = app.execute(creator.clone(), create.into()).unwrap();
+        let FullDenomResponse { denom } = from_slice(res.data.unwrap().as_slice()).unwrap();
 
-        let Coin { amount, .. } = app.wrap().query_balance(&trader, "osmo").unwrap();
-        assert_eq!(amount, Uint128::new(5000 - 4033));
-        let Coin { amount, .. } = app.wrap().query_balance(&trader, "btc").unwrap();
-        assert_eq!(amount, Uint128::new(1000));
+        // only the admin sets the cap
+        let cap = OsmosisMsg::SetDenomMaxSupply {
+            denom: denom.clone(),
+            max_supply: Uint128::new(1_000),
+        };
+        let err = app.execute(rcpt.clone(), cap.clone().into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::Unauthorized
+        );
+        app.execute(creator.clone(), cap.into()).unwrap();
 
-        // check the response contains proper value
-        let input: SwapResponse = from_slice(res.data.unwrap().as_slice()).unwrap();
-        assert_eq!(input.amount, SwapAmount::In(Uint128::new(4033)));
+        let mint = |amount| OsmosisMsg::MintTokens {
+            denom: denom.clone(),
+            amount: Uint128::new(amount),
+            mint_to_address: rcpt.to_string(),
+        };
+        app.execute(creator.clone(), mint(600).into()).unwrap();
+        let err = app.execute(creator.clone(), mint(500).into()).unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::MaxSupplyExceeded {
+                denom: denom.clone(),
+                supply: Uint128::new(1_100),
+                max_supply: Uint128::new(1_000),
+            }
+        );
 
-        // check pool state properly updated with fees
-        let query = OsmosisQuery::PoolState { id: 1 }.into();
-        let state: PoolStateResponse = app.wrap().query(&query).unwrap();
-        let expected_assets = vec![
-            coin(6_000_000 + 4033, "osmo"),
-            coin(3_000_000 - 2009, "atom"),
-        ];
-        assert_eq!(state.assets, expected_assets);
+        // batches count as a whole, and may fill the cap exactly
+        let batch = |amounts: &[u128]| OsmosisMsg::MintTokensBatch {
+            denom: denom.clone(),
+            mints: amounts
+                .iter()
+                .map(|a| (rcpt.to_string(), Uint128::new(*a)))
+                .collect(),
+        };
+        let err = app
+            .execute(creator.clone(), batch(&[300, 101]).into())
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::MaxSupplyExceeded { .. }
+        ));
+        app.execute(creator.clone(), batch(&[300, 100]).into())
+            .unwrap();
 
-        let query = OsmosisQuery::PoolState { id: 2 }.into();
-        let state: PoolStateResponse = app.wrap().query(&query).unwrap();
-        let expected_assets = vec![
-            coin(2_000_000 + 2009, "atom"),
-            coin(1_000_000 - 1000, "btc"),
-        ];
-        assert_eq!(state.assets, expected_assets);
+        let balance = app.wrap().query_balance(&rcpt, &denom).unwrap();
+        assert_eq!(balance.amount, Uint128::new(1_000));
+
+        // invalid entries are reported before the cap is checked
+        let err = app
+            .execute(creator.clone(), batch(&[500, 0]).into())
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::ZeroAmount
+        );
+        let invalid = OsmosisMsg::MintTokensBatch {
+            denom: denom.clone(),
+            mints: vec![("RCPT".to_string(), Uint128::new(500))],
+        };
+        let err = app.execute(creator, invalid.into()).unwrap_err();
+        assert!(err.to_string().contains("not normalized"), "{}", err);
     }
 
     #[test]
-    fn perform_swap_with_route_exact_in() {
-        let pool1 = Pool::new(coin(6_000_000, "osmo"), coin(3_000_000, "atom"));
-        let pool2 = Pool::new(coin(2_000_000, "atom"), coin(1_000_000, "btc"));
-        let trader = Addr::unchecked("trader");
+    fn epoch_end_distributes_gauges() {
+        let owner = Addr::unchecked("owner");
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
+        let carl = Addr::unchecked("carl");
 
-        // set up pools
         let mut app = OsmosisApp::new();
         app.init_modules(|router, _, storage| {
-            router.custom.set_pool(storage, 1, &pool1).unwrap();
-            router.custom.set_pool(storage, 2, &pool2).unwrap();
             router
                 .bank
-                .init_balance(storage, &trader, coins(5000, "osmo"))
-                .unwrap()
+                .init_balance(storage, &owner, coins(1_400, "uion"))
+                .unwrap();
+            let locks = [
+                Lock::new(alice.as_str(), 86400, coins(300, "gamm/pool/1")),
+                Lock::new(bob.as_str(), 86400, coins(100, "gamm/pool/1")),
+                Lock::new(carl.as_str(), 86400, coins(500, "gamm/pool/2")),
+            ];
+            for (id, lock) in locks.iter().enumerate() {
+                router.custom.set_lock(storage, id as u64, lock).unwrap();
+            }
         });
+        let now = (app.block_info().time.nanos() / 1_000_000) as i64;
 
-        // now a proper swap
-        let msg = OsmosisMsg::Swap {
-            first: Swap {
-                pool_id: 1,
-                denom_in: "osmo".to_string(),
-                denom_out: "atom".to_string(),
-            },
-            route: vec![Step {
-                pool_id: 2,
-                denom_out: "btc".to_string(),
-            }],
-            amount: SwapAmountWithLimit::ExactIn {
-                input: Uint128::new(4000),
-                min_output: Uint128::new(900),
-            },
-        };
-        let res = app.execute(trader.clone(), msg.into()).unwrap();
+        // one gauge pays out over 7 epochs starting now, the other has yet to start
+        for start_time in [now, now + 86_400_000] {
+            let msg = OsmosisMsg::CreateGauge {
+                is_perpetual: false,
+                denom: "gamm/pool/1".to_string(),
+                coins: coins(700, "uion"),
+                start_time,
+                num_epochs_paid_over: 7,
+            };
+            app.execute(owner.clone(), msg.into()).unwrap();
+        }
 
-        let Coin { amount, .. } = app.wrap().query_balance(&trader, "osmo").unwrap();
-        assert_eq!(amount, Uint128::new(5000 - 4000));
-        let Coin { amount, .. } = app.wrap().query_balance(&trader, "btc").unwrap();
-        assert_eq!(amount, Uint128::new(993));
+        let err = app
+            .custom_sudo(OsmosisSudo::EpochEnd {
+                identifier: "week".to_string(),
+                epoch_number: 1,
+            })
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<OsmosisError>().unwrap(),
+            OsmosisError::EpochNotFound("week".to_string())
+        );
 
-        // check the response contains proper value
-        let input: SwapResponse = from_slice(res.data.unwrap().as_slice()).unwrap();
-        assert_eq!(input.amount, SwapAmount::Out(Uint128::new(993)));
+        app.custom_sudo(OsmosisSudo::EpochEnd {
+            identifier: "day".to_string(),
+            epoch_number: 1,
+        })
+        .unwrap();
 
-        // check pool state properly updated with fees
-        let query = OsmosisQuery::PoolState { id: 1 }.into();
-        let state: PoolStateResponse = app.wrap().query(&query).unwrap();
-        let expected_assets = vec![
-            coin(6_000_000 + 4000, "osmo"),
-            coin(3_000_000 - 1993, "atom"),
-        ];
-        assert_eq!(state.assets, expected_assets);
+        // 100 uion split 3:1 between the lockers of the gauge's denom
+        let balance =
+            |app: &OsmosisApp, addr: &Addr| app.wrap().query_balance(addr, "uion").unwrap().amount;
+        assert_eq!(balance(&app, &alice), Uint128::new(75));
+        assert_eq!(balance(&app, &bob), Uint128::new(25));
+        assert_eq!(balance(&app, &carl), Uint128::zero());
 
-        let query = OsmosisQuery::PoolState { id: 2 }.into();
-        let state: PoolStateResponse = app.wrap().query(&query).unwrap();
-        let expected_assets = vec![coin(2_000_000 + 1993, "atom"), coin(1_000_000 - 993, "btc")];
-        assert_eq!(state.assets, expected_assets);
+        let (first, second) = app.read_module(|_, _, storage| {
+            (
+                GAUGES.load(storage, 1).unwrap(),
+                GAUGES.load(storage, 2).unwrap(),
+            )
+        });
+        assert_eq!(first.filled_epochs, 1);
+        assert_eq!(first.distributed_coins, coins(100, "uion"));
+        assert_eq!(second.filled_epochs, 0);
+        assert_eq!(second.distributed_coins, vec![]);
     }
 
-    // TODO: make the following test work
     #[test]
-    #[ignore]
-    fn estimate_swap_regression() {
-        let pool = Pool::new(coin(2_000_000, "atom"), coin(1_000_000, "btc"));
+    fn create_position_tick_bounds() {
+        let owner = Addr::unchecked("owner");
+        let pool = Pool::new_concentrated("osmo", "usdc", Decimal::permille(2));
 
-        // set up with one pool
         let mut app = OsmosisApp::new();
         app.init_modules(|router, _, storage| {
             router.custom.set_pool(storage, 1, &pool).unwrap();
+            let funds = vec![coin(100_000, "osmo"), coin(100_000, "usdc")];
+            router.bank.init_balance(storage, &owner, funds).unwrap();
         });
 
-        // estimate the price (501505 * 0.997 = 500_000) after fees gone
-        let query = OsmosisQuery::estimate_swap(
-            MOCK_CONTRACT_ADDR,
-            1,
-            "atom",
-            "btc",
-            SwapAmount::In(Uint128::new(2007)),
-        );
-        let SwapResponse { amount } = app.wrap().query(&query.into()).unwrap();
-        // 6M * 1.5M = 2M * 4.5M -> output = 1.5M
-        let expected = SwapAmount::Out(Uint128::new(1000));
-        assert_eq!(amount, expected);
+        let create = |lower_tick, upper_tick| OsmosisMsg::CreatePosition {
+            pool_id: 1,
+            lower_tick,
+            upper_tick,
+            tokens_provided: vec![coin(1_000, "osmo"), coin(1_000, "usdc")],
+            token_min_amount0: Uint128::zero(),
+            token_min_amount1: Uint128::zero(),
+        };
 
-        // now try the reverse query. we know what we need to pay to get 1.5M out
-        let query = OsmosisQuery::estimate_swap(
-            MOCK_CONTRACT_ADDR,
-            1,
-            "atom",
-            "btc",
-            SwapAmount::Out(Uint128::new(1000)),
-        );
-        let SwapResponse { amount } = app.wrap().query(&query.into()).unwrap();
-        let expected = SwapAmount::In(Uint128::new(2007));
-        assert_eq!(amount, expected);
+        // just outside on either side
+        for (lower_tick, upper_tick, invalid) in [
+            (MIN_TICK - 1, 0, MIN_TICK - 1),
+            (0, MAX_TICK + 1, MAX_TICK + 1),
+        ] {
+            let err = app
+                .execute(owner.clone(), create(lower_tick, upper_tick).into())
+                .unwrap_err();
+            assert_eq!(
+                err.downcast::<OsmosisError>().unwrap(),
+                OsmosisError::InvalidTick(invalid)
+            );
+        }
+
+        // the full range is fine
+        app.execute(owner, create(MIN_TICK, MAX_TICK).into())
+            .unwrap();
+    }
+
+    #[test]
+    fn advancing_past_epochs_distributes_gauges() {
+        let owner = Addr::unchecked("owner");
+        let locker = Addr::unchecked("locker");
+
+        let mut app = OsmosisApp::new_with_epoch_duration(600).with_epoch_distributions();
+        app.init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &owner, coins(1_000, "uion"))
+                .unwrap();
+            let lock = Lock::new(locker.as_str(), 86400, coins(100, "gamm/pool/1"));
+            router.custom.set_lock(storage, 1, &lock).unwrap();
+        });
+        let now = (app.block_info().time.nanos() / 1_000_000) as i64;
+
+        let gauge = |is_perpetual, amount| OsmosisMsg::CreateGauge {
+            is_perpetual,
+            denom: "gamm/pool/1".to_string(),
+            coins: coins(amount, "uion"),
+            start_time: now,
+            num_epochs_paid_over: 4,
+        };
+        app.execute(owner.clone(), gauge(false, 800).into())
+            .unwrap();
+        app.execute(owner, gauge(true, 200).into()).unwrap();
+        let rewards = |app: &OsmosisApp| app.wrap().query_balance(&locker, "uion").unwrap().amount;
+
+        // still within the first epoch
+        app.advance_seconds(599);
+        assert_eq!(rewards(&app), Uint128::zero());
+
+        // the first epoch ends: a quarter of the limited gauge and all of the perpetual one
+        app.next_block();
+        assert_eq!(rewards(&app), Uint128::new(400));
+
+        // two more epochs pass in one go
+        app.advance_seconds(1_200);
+        assert_eq!(rewards(&app), Uint128::new(800));
+        let gauge = app
+            .read_module(|_, _, storage| GAUGES.load(storage, 1))
+            .unwrap();
+        assert_eq!(gauge.filled_epochs, 3);
+    }
+
+    #[test]
+    fn advancing_without_distributions_ignores_epochs() {
+        let mut app = OsmosisApp::new();
+        // an epoch that cannot be evaluated, which is never looked at unless distributing
+        let broken = Epoch {
+            start_time: app.block_info().time,
+            duration: 0,
+        };
+        app.init_modules(|router, _, storage| router.custom.set_epoch(storage, &broken))
+            .unwrap();
+
+        let height = app.block_info().height;
+        app.advance_blocks(1);
+        app.advance_seconds(600);
+        assert_eq!(app.block_info().height, height + 1 + 600 / BLOCK_TIME);
     }
 }
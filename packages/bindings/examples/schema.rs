@@ -4,8 +4,18 @@ use std::fs::create_dir_all;
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
 use osmo_bindings::{
-    ArithmeticTwapResponse, ArithmeticTwapToNowResponse, FullDenomResponse, OsmosisMsg,
-    OsmosisQuery, PoolStateResponse, SpotPriceResponse, SwapResponse,
+    AccountLockedCoinsResponse, AccountLocksResponse, ArithmeticTwapResponse,
+    ArithmeticTwapToNowResponse, BeforeSendHookResponse, CalcExitResponse,
+    CalcJoinPoolSharesResponse, CollectIncentivesResponse, CollectSpreadRewardsResponse,
+    CreateGaugeResponse, CreatePoolResponse, CreatePositionResponse, DenomAdminResponse,
+    DenomSupplyResponse, DenomsByCreatorResponse, EpochInfoResponse, EpochProvisionsResponse,
+    EstimateSwapInResponse, EstimateSwapOutResponse, ExitPoolResponse, FullDenomResponse,
+    GaugeResponse, GeometricTwapResponse, IsFactoryDenomResponse, JoinPoolResponse, LockedResponse,
+    LpSpotPriceResponse, OsmosisMsg, OsmosisQuery, PoolLiquidityResponse, PoolParamsResponse,
+    PoolStateResponse, PoolTypeResponse, PoolsResponse, PositionResponse, PriceImpactResponse,
+    SpotPriceBothResponse, SpotPriceResponse, SpotPriceWithFeeResponse,
+    SuperfluidDelegationResponse, SwapResponse, TotalLiquidityResponse, TotalPoolLiquidityResponse,
+    WithdrawPositionResponse,
 };
 
 fn main() {
@@ -20,6 +30,42 @@ fn main() {
     export_schema(&schema_for!(PoolStateResponse), &out_dir);
     export_schema(&schema_for!(SpotPriceResponse), &out_dir);
     export_schema(&schema_for!(SwapResponse), &out_dir);
+    export_schema(&schema_for!(JoinPoolResponse), &out_dir);
     export_schema(&schema_for!(ArithmeticTwapResponse), &out_dir);
     export_schema(&schema_for!(ArithmeticTwapToNowResponse), &out_dir);
+    export_schema(&schema_for!(LockedResponse), &out_dir);
+    export_schema(&schema_for!(AccountLockedCoinsResponse), &out_dir);
+    export_schema(&schema_for!(TotalLiquidityResponse), &out_dir);
+    export_schema(&schema_for!(EstimateSwapInResponse), &out_dir);
+    export_schema(&schema_for!(EstimateSwapOutResponse), &out_dir);
+    export_schema(&schema_for!(CreatePoolResponse), &out_dir);
+    export_schema(&schema_for!(DenomAdminResponse), &out_dir);
+    export_schema(&schema_for!(DenomsByCreatorResponse), &out_dir);
+    export_schema(&schema_for!(ExitPoolResponse), &out_dir);
+    export_schema(&schema_for!(PoolParamsResponse), &out_dir);
+    export_schema(&schema_for!(SuperfluidDelegationResponse), &out_dir);
+    export_schema(&schema_for!(BeforeSendHookResponse), &out_dir);
+    export_schema(&schema_for!(PoolTypeResponse), &out_dir);
+    export_schema(&schema_for!(EpochInfoResponse), &out_dir);
+    export_schema(&schema_for!(CreateGaugeResponse), &out_dir);
+    export_schema(&schema_for!(GaugeResponse), &out_dir);
+    export_schema(&schema_for!(PoolLiquidityResponse), &out_dir);
+    export_schema(&schema_for!(PoolsResponse), &out_dir);
+    export_schema(&schema_for!(TotalPoolLiquidityResponse), &out_dir);
+    export_schema(&schema_for!(GeometricTwapResponse), &out_dir);
+    export_schema(&schema_for!(PriceImpactResponse), &out_dir);
+    export_schema(&schema_for!(CreatePositionResponse), &out_dir);
+    export_schema(&schema_for!(WithdrawPositionResponse), &out_dir);
+    export_schema(&schema_for!(PositionResponse), &out_dir);
+    export_schema(&schema_for!(CollectSpreadRewardsResponse), &out_dir);
+    export_schema(&schema_for!(CollectIncentivesResponse), &out_dir);
+    export_schema(&schema_for!(DenomSupplyResponse), &out_dir);
+    export_schema(&schema_for!(SpotPriceWithFeeResponse), &out_dir);
+    export_schema(&schema_for!(SpotPriceBothResponse), &out_dir);
+    export_schema(&schema_for!(IsFactoryDenomResponse), &out_dir);
+    export_schema(&schema_for!(LpSpotPriceResponse), &out_dir);
+    export_schema(&schema_for!(AccountLocksResponse), &out_dir);
+    export_schema(&schema_for!(EpochProvisionsResponse), &out_dir);
+    export_schema(&schema_for!(CalcJoinPoolSharesResponse), &out_dir);
+    export_schema(&schema_for!(CalcExitResponse), &out_dir);
 }
@@ -6,10 +6,24 @@ mod types;
 pub use msg::OsmosisMsg;
 pub use querier::OsmosisQuerier;
 pub use query::{
-    ArithmeticTwapResponse, ArithmeticTwapToNowResponse, FullDenomResponse, OsmosisQuery,
-    PoolStateResponse, SpotPriceResponse, SwapResponse,
+    AccountLockedCoinsResponse, AccountLocksResponse, ArithmeticTwapResponse,
+    ArithmeticTwapToNowResponse, BeforeSendHookResponse, CalcExitResponse,
+    CalcJoinPoolSharesResponse, CollectIncentivesResponse, CollectSpreadRewardsResponse,
+    CreateGaugeResponse, CreatePoolResponse, CreatePositionResponse, DenomAdminResponse,
+    DenomSupplyResponse, DenomsByCreatorResponse, EpochInfoResponse, EpochProvisionsResponse,
+    EstimateSwapInResponse, EstimateSwapOutResponse, ExitPoolResponse, FullDenomResponse,
+    GaugeResponse, GeometricTwapResponse, IsFactoryDenomResponse, JoinPoolResponse, LockedResponse,
+    LpSpotPriceResponse, OsmosisQuery, PoolInfo, PoolLiquidityResponse, PoolParamsResponse,
+    PoolStateResponse, PoolTypeResponse, PoolsResponse, PositionResponse, PriceImpactResponse,
+    SpotPriceBothResponse, SpotPriceResponse, SpotPriceWithFeeResponse,
+    SuperfluidDelegationResponse, SwapResponse, TotalLiquidityResponse, TotalPoolLiquidityResponse,
+    WithdrawPositionResponse,
+};
+pub use types::{
+    build_route, is_factory_denom, max_in_after_slippage, min_out_after_slippage,
+    pool_id_from_lp_denom, split_factory_denom, DenomUnit, Metadata, PoolAsset, PoolParams,
+    SpotPriceKind, Step, Swap, SwapAmount, SwapAmountWithLimit, SwapRoute, MAX_TICK, MIN_TICK,
 };
-pub use types::{Step, Swap, SwapAmount, SwapAmountWithLimit};
 
 // This is a signal, such that any contract that imports these helpers will only run on the
 // osmosis blockchain
@@ -1,8 +1,8 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{CosmosMsg, CustomMsg, Uint128};
+use cosmwasm_std::{Coin, CosmosMsg, CustomMsg, Decimal, StdError, StdResult, Uint128};
 
-use crate::types::SwapAmountWithLimit;
-use crate::{Step, Swap};
+use crate::types::{PoolAsset, PoolParams, SwapAmountWithLimit};
+use crate::{is_factory_denom, min_out_after_slippage, Step, Swap, SwapRoute};
 
 /// A number of Custom messages that can call into the Osmosis bindings
 #[cw_serde]
@@ -29,6 +29,12 @@ pub enum OsmosisMsg {
         amount: Uint128,
         mint_to_address: String,
     },
+    /// Like `MintTokens`, but mints to many recipients at once, given as
+    /// (address, amount) pairs. The batch must not be empty.
+    MintTokensBatch {
+        denom: String,
+        mints: Vec<(String, Uint128)>,
+    },
     /// Contracts can burn native tokens for an existing factory denom
     /// that they are the admin of.
     /// Currently, the burn from address must be the admin contract.
@@ -37,13 +43,133 @@ pub enum OsmosisMsg {
         amount: Uint128,
         burn_from_address: String,
     },
+    /// Caps the total supply of a factory denom the sender is the admin of. Mints that would take
+    /// the supply above `max_supply` fail, tokens already in circulation are left untouched.
+    SetDenomMaxSupply { denom: String, max_supply: Uint128 },
+    /// Sets the contract called before every transfer of a factory denom the sender is the admin
+    /// of. An empty address removes the hook.
+    SetBeforeSendHook {
+        denom: String,
+        cosmwasm_address: String,
+    },
+    /// Contracts can move tokens of a factory denom they are the admin of between any two
+    /// accounts, without the consent of the holder.
+    ForceTransfer {
+        denom: String,
+        amount: Uint128,
+        from_address: String,
+        to_address: String,
+    },
     /// Swap over one or more pools
+    /// `first` is the first hop, each `Step` of `route` another one starting from the previous
+    /// `denom_out`. An empty route means a single-pool swap over `first`.
+    /// The route must not end on the denom it starts with.
     /// Returns SwapResponse in the data field of the Response
     Swap {
         first: Swap,
         route: Vec<Step>,
         amount: SwapAmountWithLimit,
     },
+    /// Like `Swap`, but fails if executed after `deadline` (Unix time millisecond).
+    /// No deadline means it behaves exactly like `Swap`.
+    /// Returns SwapResponse in the data field of the Response
+    SwapWithDeadline {
+        first: Swap,
+        route: Vec<Step>,
+        amount: SwapAmountWithLimit,
+        deadline: Option<i64>,
+    },
+    /// Swap an exact input split across several routes, to reduce price impact.
+    /// Every route starts from `token_in_denom` and must end on the same denom. The sum of all
+    /// outputs must be at least `token_out_min_amount`.
+    /// Returns SwapResponse in the data field of the Response
+    SplitRouteSwapExactAmountIn {
+        routes: Vec<SwapRoute>,
+        token_in_denom: String,
+        token_out_min_amount: Uint128,
+    },
+    /// Add liquidity to a pool in exchange for LP shares, without swapping.
+    /// Assets are pulled in proportion to the current pool reserves. With `token_in_maxs` this
+    /// joins at the limiting ratio, minting as many shares as the scarcest asset allows (at least
+    /// `share_out_amount`) and leaving the excess with the sender. An empty list mints exactly
+    /// `share_out_amount` with no limit on the inputs.
+    /// Returns JoinPoolResponse in the data field of the Response
+    JoinPool {
+        pool_id: u64,
+        share_out_amount: Uint128,
+        token_in_maxs: Vec<Coin>,
+    },
+    /// Remove liquidity from a pool by returning `share_in_amount` LP shares.
+    /// Assets are paid out in proportion to the pool reserves, minus the pool's exit fee.
+    /// No single asset may be below the matching amount in `token_out_mins` (an empty list means
+    /// no limit).
+    /// Returns ExitPoolResponse in the data field of the Response
+    ExitPool {
+        pool_id: u64,
+        share_in_amount: Uint128,
+        token_out_mins: Vec<Coin>,
+    },
+    /// Create a new balancer pool holding `pool_assets`, which are taken from the sender.
+    /// The sender receives the initial LP shares.
+    /// Returns CreatePoolResponse in the data field of the Response
+    CreateBalancerPool {
+        pool_params: PoolParams,
+        pool_assets: Vec<PoolAsset>,
+    },
+    /// Create a new stableswap pool holding `initial_pool_liquidity`, which is taken from the sender.
+    /// Each reserve is divided by the matching entry of `scaling_factors` before applying the curve,
+    /// so that assets that should trade at a fixed ratio other than 1:1 (e.g. different decimals)
+    /// can be pegged. An empty list means a factor of 1 for every asset.
+    /// The sender receives the initial LP shares.
+    /// Returns CreatePoolResponse in the data field of the Response
+    CreateStableswapPool {
+        initial_pool_liquidity: Vec<Coin>,
+        scaling_factors: Vec<u64>,
+        swap_fee: Decimal,
+    },
+    /// Create an x/incentives gauge paying out `coins`, which are taken from the sender, to
+    /// everyone locking `denom`.
+    /// A perpetual gauge pays out everything it holds each epoch, otherwise the coins are spread
+    /// over `num_epochs_paid_over` epochs.
+    /// `start_time` (Unix time millisecond) must not be in the past.
+    /// Returns CreateGaugeResponse in the data field of the Response
+    CreateGauge {
+        is_perpetual: bool,
+        denom: String,
+        coins: Vec<Coin>,
+        start_time: i64,
+        num_epochs_paid_over: u64,
+    },
+    /// Provide liquidity to a concentrated liquidity pool between `lower_tick` and `upper_tick`.
+    /// Takes up to `tokens_provided` from the sender, failing if less than the minimum amount of
+    /// either pool asset would be used.
+    /// Returns CreatePositionResponse in the data field of the Response
+    CreatePosition {
+        pool_id: u64,
+        lower_tick: i64,
+        upper_tick: i64,
+        tokens_provided: Vec<Coin>,
+        token_min_amount0: Uint128,
+        token_min_amount1: Uint128,
+    },
+    /// Withdraw `liquidity_amount` from a concentrated liquidity position owned by the sender,
+    /// paying out the matching share of both pool assets.
+    /// `liquidity_amount` is a decimal string, as the chain uses for liquidity.
+    /// Returns WithdrawPositionResponse in the data field of the Response
+    WithdrawPosition {
+        position_id: u64,
+        liquidity_amount: String,
+    },
+    /// Pay out the swap fees earned by the given concentrated liquidity positions, which must all
+    /// be owned by the sender.
+    /// Returns CollectSpreadRewardsResponse in the data field of the Response
+    CollectSpreadRewards { position_ids: Vec<u64> },
+    /// Pay out the incentives earned by the given concentrated liquidity positions, which must all
+    /// be owned by the sender.
+    /// Returns CollectIncentivesResponse in the data field of the Response
+    CollectIncentives { position_ids: Vec<u64> },
+    /// Top up an existing gauge with `coins`, which are taken from the sender
+    AddToGauge { gauge_id: u64, coins: Vec<Coin> },
 }
 
 impl OsmosisMsg {
@@ -61,6 +187,186 @@ impl OsmosisMsg {
         }
     }
 
+    /// Swaps all of `token_in` for at least `min_output` of `denom_out` on a single pool
+    ///
+    /// ```
+    /// # use cosmwasm_std::{coin, Uint128};
+    /// # use osmo_bindings::{OsmosisMsg, Swap, SwapAmountWithLimit};
+    /// let msg = OsmosisMsg::swap_exact_in(1, coin(1000, "uosmo"), "uatom", Uint128::new(450));
+    /// assert_eq!(
+    ///     msg,
+    ///     OsmosisMsg::Swap {
+    ///         first: Swap::new(1, "uosmo", "uatom"),
+    ///         route: vec![],
+    ///         amount: SwapAmountWithLimit::ExactIn {
+    ///             input: Uint128::new(1000),
+    ///             min_output: Uint128::new(450),
+    ///         },
+    ///     }
+    /// );
+    /// ```
+    pub fn swap_exact_in(
+        pool_id: u64,
+        token_in: Coin,
+        denom_out: &str,
+        min_output: Uint128,
+    ) -> Self {
+        OsmosisMsg::simple_swap(
+            pool_id,
+            token_in.denom,
+            denom_out,
+            SwapAmountWithLimit::ExactIn {
+                input: token_in.amount,
+                min_output,
+            },
+        )
+    }
+
+    /// Swaps exactly `input` of `denom_in` on a single pool, accepting up to `slippage` less
+    /// than `expected_out` (see `min_out_after_slippage`)
+    ///
+    /// ```
+    /// # use cosmwasm_std::{Decimal, Uint128};
+    /// # use osmo_bindings::{OsmosisMsg, SwapAmountWithLimit};
+    /// let msg = OsmosisMsg::simple_swap_exact_in(
+    ///     1,
+    ///     "uosmo",
+    ///     "uatom",
+    ///     Uint128::new(1000),
+    ///     Decimal::percent(1),
+    ///     Uint128::new(500),
+    /// );
+    /// assert_eq!(
+    ///     msg,
+    ///     OsmosisMsg::simple_swap(
+    ///         1,
+    ///         "uosmo",
+    ///         "uatom",
+    ///         SwapAmountWithLimit::ExactIn {
+    ///             input: Uint128::new(1000),
+    ///             min_output: Uint128::new(495),
+    ///         },
+    ///     )
+    /// );
+    /// ```
+    pub fn simple_swap_exact_in(
+        pool_id: u64,
+        denom_in: impl Into<String>,
+        denom_out: impl Into<String>,
+        input: Uint128,
+        slippage: Decimal,
+        expected_out: Uint128,
+    ) -> Self {
+        OsmosisMsg::simple_swap(
+            pool_id,
+            denom_in,
+            denom_out,
+            SwapAmountWithLimit::ExactIn {
+                input,
+                min_output: min_out_after_slippage(expected_out, slippage),
+            },
+        )
+    }
+
+    /// Builds an `OsmosisMsg::Swap`, failing early on what the chain would reject: a zero amount
+    /// or limit, or a route that doesn't chain up. `pool_denoms` returns the assets of a pool, or
+    /// None for an unknown pool, and every hop must trade between two distinct assets of its pool.
+    pub fn try_swap(
+        first: Swap,
+        route: Vec<Step>,
+        amount: SwapAmountWithLimit,
+        pool_denoms: impl Fn(u64) -> Option<Vec<String>>,
+    ) -> StdResult<Self> {
+        match &amount {
+            SwapAmountWithLimit::ExactIn { input, min_output } => {
+                if input.is_zero() {
+                    return Err(StdError::generic_err("Swap input must not be zero"));
+                }
+                if min_output.is_zero() {
+                    return Err(StdError::generic_err("Swap min_output must not be zero"));
+                }
+            }
+            SwapAmountWithLimit::ExactOut { output, max_input } => {
+                if output.is_zero() {
+                    return Err(StdError::generic_err("Swap output must not be zero"));
+                }
+                if max_input.is_zero() {
+                    return Err(StdError::generic_err("Swap max_input must not be zero"));
+                }
+            }
+        }
+
+        let hops = std::iter::once((first.pool_id, &first.denom_out))
+            .chain(route.iter().map(|step| (step.pool_id, &step.denom_out)));
+        let mut denom_in = &first.denom_in;
+        for (pool_id, denom_out) in hops {
+            if denom_in == denom_out {
+                return Err(StdError::generic_err(format!(
+                    "Cannot swap {} for itself in pool {}",
+                    denom_in, pool_id
+                )));
+            }
+            let denoms = pool_denoms(pool_id)
+                .ok_or_else(|| StdError::generic_err(format!("Unknown pool {}", pool_id)))?;
+            for denom in [denom_in, denom_out] {
+                if !denoms.contains(denom) {
+                    return Err(StdError::generic_err(format!(
+                        "Pool {} does not hold {}",
+                        pool_id, denom
+                    )));
+                }
+            }
+            denom_in = denom_out;
+        }
+
+        Ok(OsmosisMsg::Swap {
+            first,
+            route,
+            amount,
+        })
+    }
+
+    /// Creates the factory denom `factory/{sender}/{subdenom}`
+    ///
+    /// ```
+    /// # use osmo_bindings::OsmosisMsg;
+    /// let msg = OsmosisMsg::create_denom("ustake");
+    /// assert_eq!(
+    ///     msg,
+    ///     OsmosisMsg::CreateDenom {
+    ///         subdenom: "ustake".to_string()
+    ///     }
+    /// );
+    /// ```
+    pub fn create_denom(subdenom: impl Into<String>) -> Self {
+        OsmosisMsg::CreateDenom {
+            subdenom: subdenom.into(),
+        }
+    }
+
+    /// Hands the admin rights of `denom` over to `new_admin`, an empty address leaves it without
+    /// an admin
+    ///
+    /// ```
+    /// # use osmo_bindings::OsmosisMsg;
+    /// let msg = OsmosisMsg::change_admin("factory/contract/ustake", "dao");
+    /// assert_eq!(
+    ///     msg,
+    ///     OsmosisMsg::ChangeAdmin {
+    ///         denom: "factory/contract/ustake".to_string(),
+    ///         new_admin_address: "dao".to_string(),
+    ///     }
+    /// );
+    /// ```
+    pub fn change_admin(denom: impl Into<String>, new_admin: impl Into<String>) -> Self {
+        OsmosisMsg::ChangeAdmin {
+            denom: denom.into(),
+            new_admin_address: new_admin.into(),
+        }
+    }
+
+    /// Mints `amount` of the factory denom `denom` to `mint_to_address`.
+    /// This does not validate its input, see `try_mint_contract_tokens` for that.
     pub fn mint_contract_tokens(denom: String, amount: Uint128, mint_to_address: String) -> Self {
         OsmosisMsg::MintTokens {
             denom,
@@ -69,6 +375,23 @@ impl OsmosisMsg {
         }
     }
 
+    /// Like `mint_contract_tokens`, but fails unless `denom` has the shape of a factory denom and
+    /// `amount` is not zero
+    pub fn try_mint_contract_tokens(
+        denom: String,
+        amount: Uint128,
+        mint_to_address: String,
+    ) -> StdResult<Self> {
+        validate_factory_tokens(&denom, amount)?;
+        Ok(OsmosisMsg::mint_contract_tokens(
+            denom,
+            amount,
+            mint_to_address,
+        ))
+    }
+
+    /// Burns `amount` of the factory denom `denom` from the sender.
+    /// This does not validate its input, see `try_burn_contract_tokens` for that.
     pub fn burn_contract_tokens(
         denom: String,
         amount: Uint128,
@@ -80,6 +403,34 @@ impl OsmosisMsg {
             burn_from_address: "".to_string(), // burn_from_address is currently disabled.
         }
     }
+
+    /// Like `burn_contract_tokens`, but fails unless `denom` has the shape of a factory denom and
+    /// `amount` is not zero
+    pub fn try_burn_contract_tokens(
+        denom: String,
+        amount: Uint128,
+        burn_from_address: String,
+    ) -> StdResult<Self> {
+        validate_factory_tokens(&denom, amount)?;
+        Ok(OsmosisMsg::burn_contract_tokens(
+            denom,
+            amount,
+            burn_from_address,
+        ))
+    }
+}
+
+fn validate_factory_tokens(denom: &str, amount: Uint128) -> StdResult<()> {
+    if !is_factory_denom(denom) {
+        return Err(StdError::generic_err(format!(
+            "Not a factory denom: '{}'",
+            denom
+        )));
+    }
+    if amount.is_zero() {
+        return Err(StdError::generic_err("Amount must not be zero"));
+    }
+    Ok(())
 }
 
 impl From<OsmosisMsg> for CosmosMsg<OsmosisMsg> {
@@ -89,3 +440,185 @@ impl From<OsmosisMsg> for CosmosMsg<OsmosisMsg> {
 }
 
 impl CustomMsg for OsmosisMsg {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool_denoms(pool_id: u64) -> Option<Vec<String>> {
+        let denoms: &[&str] = match pool_id {
+            1 => &["uosmo", "uatom"],
+            2 => &["uatom", "ubtc"],
+            _ => return None,
+        };
+        Some(denoms.iter().map(|d| d.to_string()).collect())
+    }
+
+    #[test]
+    fn try_swap_validates() {
+        let exact_in = SwapAmountWithLimit::ExactIn {
+            input: Uint128::new(1000),
+            min_output: Uint128::new(90),
+        };
+        let route = vec![Step::new(2, "ubtc")];
+
+        let msg = OsmosisMsg::try_swap(
+            Swap::new(1, "uosmo", "uatom"),
+            route.clone(),
+            exact_in.clone(),
+            pool_denoms,
+        )
+        .unwrap();
+        assert_eq!(
+            msg,
+            OsmosisMsg::Swap {
+                first: Swap::new(1, "uosmo", "uatom"),
+                route: route.clone(),
+                amount: exact_in.clone(),
+            }
+        );
+
+        let cases = [
+            (
+                SwapAmountWithLimit::ExactIn {
+                    input: Uint128::zero(),
+                    min_output: Uint128::new(90),
+                },
+                "Swap input must not be zero",
+            ),
+            (
+                SwapAmountWithLimit::ExactIn {
+                    input: Uint128::new(1000),
+                    min_output: Uint128::zero(),
+                },
+                "Swap min_output must not be zero",
+            ),
+            (
+                SwapAmountWithLimit::ExactOut {
+                    output: Uint128::zero(),
+                    max_input: Uint128::new(1000),
+                },
+                "Swap output must not be zero",
+            ),
+            (
+                SwapAmountWithLimit::ExactOut {
+                    output: Uint128::new(90),
+                    max_input: Uint128::zero(),
+                },
+                "Swap max_input must not be zero",
+            ),
+        ];
+        for (amount, expected) in cases {
+            let err = OsmosisMsg::try_swap(
+                Swap::new(1, "uosmo", "uatom"),
+                route.clone(),
+                amount,
+                pool_denoms,
+            )
+            .unwrap_err();
+            assert_eq!(err, StdError::generic_err(expected));
+        }
+
+        let cases = [
+            // the second hop would start from uosmo, which pool 2 doesn't hold
+            (
+                Swap::new(1, "uatom", "uosmo"),
+                route.clone(),
+                "Pool 2 does not hold uosmo",
+            ),
+            (
+                Swap::new(1, "uosmo", "ubtc"),
+                vec![],
+                "Pool 1 does not hold ubtc",
+            ),
+            (Swap::new(3, "uosmo", "uatom"), vec![], "Unknown pool 3"),
+            (
+                Swap::new(1, "uosmo", "uatom"),
+                vec![Step::new(2, "uatom")],
+                "Cannot swap uatom for itself in pool 2",
+            ),
+        ];
+        for (first, route, expected) in cases {
+            let err =
+                OsmosisMsg::try_swap(first, route, exact_in.clone(), pool_denoms).unwrap_err();
+            assert_eq!(err, StdError::generic_err(expected));
+        }
+    }
+
+    #[test]
+    fn simple_swap_exact_in_applies_slippage() {
+        // 0.5% of 12345 is 61.725, so the floor leaves 12283 rather than 12284
+        let msg = OsmosisMsg::simple_swap_exact_in(
+            7,
+            "uosmo",
+            "uatom",
+            Uint128::new(20000),
+            Decimal::permille(5),
+            Uint128::new(12345),
+        );
+        match msg {
+            OsmosisMsg::Swap {
+                first,
+                route,
+                amount: SwapAmountWithLimit::ExactIn { input, min_output },
+            } => {
+                assert_eq!(first, Swap::new(7, "uosmo", "uatom"));
+                assert!(route.is_empty());
+                assert_eq!(input, Uint128::new(20000));
+                assert_eq!(min_output, Uint128::new(12283));
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn try_mint_and_burn_validate() {
+        let denom = "factory/creator/fundz".to_string();
+        let msg = OsmosisMsg::try_mint_contract_tokens(
+            denom.clone(),
+            Uint128::new(5),
+            "rcpt".to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            msg,
+            OsmosisMsg::mint_contract_tokens(denom.clone(), Uint128::new(5), "rcpt".to_string())
+        );
+        let msg =
+            OsmosisMsg::try_burn_contract_tokens(denom.clone(), Uint128::new(5), "".to_string())
+                .unwrap();
+        assert_eq!(
+            msg,
+            OsmosisMsg::burn_contract_tokens(denom.clone(), Uint128::new(5), "".to_string())
+        );
+
+        let err = OsmosisMsg::try_mint_contract_tokens(
+            denom.clone(),
+            Uint128::zero(),
+            "rcpt".to_string(),
+        )
+        .unwrap_err();
+        assert_eq!(err, StdError::generic_err("Amount must not be zero"));
+        let err = OsmosisMsg::try_burn_contract_tokens(denom, Uint128::zero(), "".to_string())
+            .unwrap_err();
+        assert_eq!(err, StdError::generic_err("Amount must not be zero"));
+
+        for denom in ["", "uosmo", "factory/creator"] {
+            let expected = StdError::generic_err(format!("Not a factory denom: '{}'", denom));
+            let err = OsmosisMsg::try_mint_contract_tokens(
+                denom.to_string(),
+                Uint128::new(5),
+                "rcpt".to_string(),
+            )
+            .unwrap_err();
+            assert_eq!(err, expected);
+            let err = OsmosisMsg::try_burn_contract_tokens(
+                denom.to_string(),
+                Uint128::new(5),
+                "".to_string(),
+            )
+            .unwrap_err();
+            assert_eq!(err, expected);
+        }
+    }
+}
@@ -1,7 +1,15 @@
-use cosmwasm_std::{QuerierWrapper, QueryRequest, StdResult};
+use cosmwasm_std::{QuerierWrapper, StdError, StdResult, Uint128};
+
+use crate::types::{Step, Swap, SwapAmount};
 
 use crate::query::{
-    ArithmeticTwapResponse, ArithmeticTwapToNowResponse, FullDenomResponse, OsmosisQuery,
+    AccountLockedCoinsResponse, AccountLocksResponse, ArithmeticTwapResponse,
+    ArithmeticTwapToNowResponse, DenomAdminResponse, DenomSupplyResponse, DenomsByCreatorResponse,
+    EpochInfoResponse, EpochProvisionsResponse, FullDenomResponse, GaugeResponse,
+    GeometricTwapResponse, IsFactoryDenomResponse, LockedResponse, OsmosisQuery,
+    PoolLiquidityResponse, PoolStateResponse, PoolTypeResponse, PositionResponse,
+    SpotPriceResponse, SuperfluidDelegationResponse, SwapResponse, TotalLiquidityResponse,
+    TotalPoolLiquidityResponse,
 };
 
 /// This is a helper wrapper to easily use our custom queries
@@ -23,7 +31,111 @@ impl<'a> OsmosisQuerier<'a> {
             creator_addr,
             subdenom,
         };
-        let request: QueryRequest<OsmosisQuery> = OsmosisQuery::into(full_denom_query);
+        let request = full_denom_query.request();
+        self.querier.query(&request)
+    }
+
+    pub fn denom_admin(&self, denom: String) -> StdResult<DenomAdminResponse> {
+        let denom_admin_query = OsmosisQuery::DenomAdmin { denom };
+        let request = denom_admin_query.request();
+        self.querier.query(&request)
+    }
+
+    pub fn is_factory_denom(&self, denom: String) -> StdResult<IsFactoryDenomResponse> {
+        let is_factory_denom_query = OsmosisQuery::IsFactoryDenom { denom };
+        let request = is_factory_denom_query.request();
+        self.querier.query(&request)
+    }
+
+    pub fn denoms_by_creator(&self, creator: String) -> StdResult<DenomsByCreatorResponse> {
+        let denoms_by_creator_query = OsmosisQuery::DenomsByCreator { creator };
+        let request = denoms_by_creator_query.request();
+        self.querier.query(&request)
+    }
+
+    pub fn total_pool_liquidity(&self, pool_id: u64) -> StdResult<TotalPoolLiquidityResponse> {
+        let total_pool_liquidity_query = OsmosisQuery::TotalPoolLiquidity { pool_id };
+        let request = total_pool_liquidity_query.request();
+        self.querier.query(&request)
+    }
+
+    pub fn pool_liquidity_of(
+        &self,
+        pool_id: u64,
+        denom: String,
+    ) -> StdResult<PoolLiquidityResponse> {
+        let pool_liquidity_of_query = OsmosisQuery::PoolLiquidityOf { pool_id, denom };
+        let request = pool_liquidity_of_query.request();
+        self.querier.query(&request)
+    }
+
+    pub fn pool_type(&self, pool_id: u64) -> StdResult<PoolTypeResponse> {
+        let pool_type_query = OsmosisQuery::PoolType { pool_id };
+        let request = pool_type_query.request();
+        self.querier.query(&request)
+    }
+
+    pub fn pool_state(&self, id: u64) -> StdResult<PoolStateResponse> {
+        let pool_state_query = OsmosisQuery::PoolState { id };
+        let request = pool_state_query.request();
+        self.querier.query(&request)
+    }
+
+    /// The reserve of `denom` in the pool, an error if the pool doesn't hold it
+    pub fn pool_state_asset(&self, id: u64, denom: &str) -> StdResult<Uint128> {
+        let state = self.pool_state(id)?;
+        state
+            .assets
+            .into_iter()
+            .find(|asset| asset.denom == denom)
+            .map(|asset| asset.amount)
+            .ok_or_else(|| StdError::generic_err(format!("asset {} not in pool {}", denom, id)))
+    }
+
+    /// How many `denom_out` one `denom_in` buys at the margin, without the swap fee
+    pub fn spot_price(
+        &self,
+        pool_id: u64,
+        denom_in: String,
+        denom_out: String,
+    ) -> StdResult<SpotPriceResponse> {
+        let spot_price_query = OsmosisQuery::SpotPrice {
+            swap: Swap::new(pool_id, denom_in, denom_out),
+            with_swap_fee: false,
+        };
+        let request = spot_price_query.request();
+        self.querier.query(&request)
+    }
+
+    /// Like `spot_price`, but net of the pool's swap fee
+    pub fn spot_price_with_fee(
+        &self,
+        pool_id: u64,
+        denom_in: String,
+        denom_out: String,
+    ) -> StdResult<SpotPriceResponse> {
+        let spot_price_query = OsmosisQuery::SpotPrice {
+            swap: Swap::new(pool_id, denom_in, denom_out),
+            with_swap_fee: true,
+        };
+        let request = spot_price_query.request();
+        self.querier.query(&request)
+    }
+
+    pub fn estimate_swap(
+        &self,
+        sender: String,
+        first: Swap,
+        route: Vec<Step>,
+        amount: SwapAmount,
+    ) -> StdResult<SwapResponse> {
+        let estimate_swap_query = OsmosisQuery::EstimateSwap {
+            sender,
+            first,
+            route,
+            amount,
+        };
+        let request = estimate_swap_query.request();
         self.querier.query(&request)
     }
 
@@ -42,7 +154,7 @@ impl<'a> OsmosisQuerier<'a> {
             start_time,
             end_time,
         };
-        let request: QueryRequest<OsmosisQuery> = OsmosisQuery::into(arithmetic_twap_query);
+        let request = arithmetic_twap_query.request();
         self.querier.query(&request)
     }
 
@@ -59,7 +171,95 @@ impl<'a> OsmosisQuerier<'a> {
             base_asset_denom,
             start_time,
         };
-        let request: QueryRequest<OsmosisQuery> = OsmosisQuery::into(arithmetic_twap_to_now_query);
+        let request = arithmetic_twap_to_now_query.request();
+        self.querier.query(&request)
+    }
+
+    pub fn geometric_twap(
+        &self,
+        id: u64,
+        quote_asset_denom: String,
+        base_asset_denom: String,
+        start_time: i64,
+        end_time: i64,
+    ) -> StdResult<GeometricTwapResponse> {
+        let geometric_twap_query = OsmosisQuery::GeometricTwap {
+            id,
+            quote_asset_denom,
+            base_asset_denom,
+            start_time,
+            end_time,
+        };
+        let request = geometric_twap_query.request();
+        self.querier.query(&request)
+    }
+
+    pub fn locked_by_id(&self, lock_id: u64) -> StdResult<LockedResponse> {
+        let locked_by_id_query = OsmosisQuery::LockedById { lock_id };
+        let request = locked_by_id_query.request();
+        self.querier.query(&request)
+    }
+
+    pub fn account_locked_coins(&self, address: String) -> StdResult<AccountLockedCoinsResponse> {
+        let account_locked_coins_query = OsmosisQuery::AccountLockedCoins { address };
+        let request = account_locked_coins_query.request();
+        self.querier.query(&request)
+    }
+
+    pub fn account_locks(
+        &self,
+        address: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> StdResult<AccountLocksResponse> {
+        let account_locks_query = OsmosisQuery::AccountLocks {
+            address,
+            start_after,
+            limit,
+        };
+        let request = account_locks_query.request();
+        self.querier.query(&request)
+    }
+
+    pub fn superfluid_delegation(&self, lock_id: u64) -> StdResult<SuperfluidDelegationResponse> {
+        let superfluid_delegation_query = OsmosisQuery::SuperfluidDelegation { lock_id };
+        let request = superfluid_delegation_query.request();
+        self.querier.query(&request)
+    }
+
+    pub fn total_liquidity(&self) -> StdResult<TotalLiquidityResponse> {
+        let total_liquidity_query = OsmosisQuery::TotalLiquidity {};
+        let request = total_liquidity_query.request();
+        self.querier.query(&request)
+    }
+
+    pub fn denom_supply(&self, denom: String) -> StdResult<DenomSupplyResponse> {
+        let denom_supply_query = OsmosisQuery::DenomSupply { denom };
+        let request = denom_supply_query.request();
+        self.querier.query(&request)
+    }
+
+    pub fn gauge_by_id(&self, id: u64) -> StdResult<GaugeResponse> {
+        let gauge_by_id_query = OsmosisQuery::GaugeById { id };
+        let request = gauge_by_id_query.request();
+        self.querier.query(&request)
+    }
+
+    pub fn epoch_info(&self, identifier: String) -> StdResult<EpochInfoResponse> {
+        let epoch_info_query = OsmosisQuery::EpochInfo { identifier };
+        let request = epoch_info_query.request();
+        self.querier.query(&request)
+    }
+
+    pub fn epoch_provisions(&self) -> StdResult<EpochProvisionsResponse> {
+        let epoch_provisions_query = OsmosisQuery::EpochProvisions {};
+        let request = epoch_provisions_query.request();
+        self.querier.query(&request)
+    }
+
+    pub fn position_by_id(&self, position_id: u64) -> StdResult<PositionResponse> {
+        let position_by_id_query = OsmosisQuery::PositionById { position_id };
+        let request = position_by_id_query.request();
         self.querier.query(&request)
     }
 }
@@ -1,7 +1,9 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Coin, CustomQuery, Decimal, Uint128};
+use cosmwasm_std::{
+    from_binary, Binary, Coin, CustomQuery, Decimal, QueryRequest, StdError, StdResult, Uint128,
+};
 
-use crate::types::{Step, Swap, SwapAmount};
+use crate::types::{PoolParams, SpotPriceKind, Step, Swap, SwapAmount};
 
 #[cw_serde]
 #[derive(QueryResponses)]
@@ -15,15 +17,97 @@ pub enum OsmosisQuery {
         creator_addr: String,
         subdenom: String,
     },
+    /// Returns the admin of a factory denom, which is the only address allowed to mint it.
+    /// The admin is empty if it was cleared via `OsmosisMsg::ChangeAdmin`.
+    #[returns(DenomAdminResponse)]
+    DenomAdmin { denom: String },
+    /// Returns whether `denom` is a factory denom that has actually been created.
+    /// Use `osmo_bindings::is_factory_denom` to only check the shape without a query.
+    #[returns(IsFactoryDenomResponse)]
+    IsFactoryDenom { denom: String },
+    /// Returns the contract called before every transfer of a factory denom
+    #[returns(BeforeSendHookResponse)]
+    BeforeSendHook { denom: String },
+    /// Lists all factory denoms created by `creator`, whoever their admin is now
+    #[returns(DenomsByCreatorResponse)]
+    DenomsByCreator { creator: String },
     /// For a given pool ID, list all tokens traded on it with current liquidity (spot).
     /// As well as the total number of LP shares and their denom
     #[returns(PoolStateResponse)]
     PoolState { id: u64 },
+    /// Like `PoolState`, for the pool whose LP shares are `lp_denom`, e.g. "gamm/pool/1"
+    #[returns(PoolStateResponse)]
+    PoolStateByLpDenom { lp_denom: String },
+    /// Lists all pools by ascending ID, `limit` defaults to and is capped at 100
+    #[returns(PoolsResponse)]
+    Pools {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Returns the assets held by a pool, without its shares
+    #[returns(TotalPoolLiquidityResponse)]
+    TotalPoolLiquidity { pool_id: u64 },
+    /// Returns the reserve of a single asset of a pool, for when the full `PoolState` isn't needed
+    #[returns(PoolLiquidityResponse)]
+    PoolLiquidityOf { pool_id: u64, denom: String },
+    /// Returns which kind of pool the given pool ID is
+    #[returns(PoolTypeResponse)]
+    PoolType { pool_id: u64 },
+    /// Returns the swap and exit fee of the given pool ID
+    #[returns(PoolParamsResponse)]
+    PoolParams { id: u64 },
+    /// Returns how many LP shares joining `pool_id` with at most `tokens_in` would mint, and
+    /// which of those tokens it would take. Like `JoinPool`, this joins at the limiting ratio
+    #[returns(CalcJoinPoolSharesResponse)]
+    CalcJoinPoolShares { pool_id: u64, tokens_in: Vec<Coin> },
+    /// Returns the assets exiting `pool_id` with `share_in_amount` LP shares would pay out,
+    /// after the pool's exit fee. Fails if `share_in_amount` is more than the pool's total shares.
+    #[returns(CalcExitResponse)]
+    CalcExitPoolCoinsFromShares {
+        pool_id: u64,
+        share_in_amount: Uint128,
+    },
     /// Return current spot price swapping In for Out on given pool ID.
+    /// `with_swap_fee: false` returns the marginal price and `true` the marginal price net of the
+    /// swap fee, see `SpotPriceKind` for the exact semantics.
     /// Warning: this can easily be manipulated via sandwich attacks, do not use as price oracle.
     /// We will add TWAP for more robust price feed.
     #[returns(SpotPriceResponse)]
     SpotPrice { swap: Swap, with_swap_fee: bool },
+    /// Like `SpotPrice`, but for both directions of a pair at once
+    #[returns(SpotPriceBothResponse)]
+    SpotPriceBoth {
+        pool_id: u64,
+        denom_a: String,
+        denom_b: String,
+        with_swap_fee: bool,
+    },
+    /// Like `SpotPrice` with the fee applied, but also reports which fee that was.
+    /// By default that is the pool's own swap fee, `swap_fee` prices the swap at another rate
+    /// instead, e.g. to simulate a different fee tier.
+    #[returns(SpotPriceWithFeeResponse)]
+    SpotPriceVerbose {
+        swap: Swap,
+        swap_fee: Option<Decimal>,
+    },
+    /// Returns the current marginal price of `base_asset_denom` measured in `quote_asset_denom`,
+    /// that is, how many quote assets one base asset is worth. The swap fee is not applied.
+    ///
+    /// Example: on a pool holding 6M osmo and 3M atom, asking for base `atom` and quote `osmo`
+    /// returns 2 (one atom is worth two osmo), while base `osmo` and quote `atom` returns 0.5.
+    ///
+    /// Warning: this can easily be manipulated via sandwich attacks, do not use as price oracle.
+    #[returns(SpotPriceResponse)]
+    SpotPriceV2 {
+        pool_id: u64,
+        base_asset_denom: String,
+        quote_asset_denom: String,
+    },
+    /// Returns the value of one LP share of the pool measured in `quote_denom`, which must be one
+    /// of the pool assets. The other assets are valued at their marginal spot price, so the same
+    /// sandwich attack warning as for `SpotPrice` applies.
+    #[returns(LpSpotPriceResponse)]
+    LpSpotPrice { pool_id: u64, quote_denom: String },
     /// Return current spot price swapping In for Out on given pool ID.
     /// You can call `EstimateSwap { contract: env.contract.address, ... }` to set sender to the
     /// current contract.
@@ -36,6 +120,24 @@ pub enum OsmosisQuery {
         route: Vec<Step>,
         amount: SwapAmount,
     },
+    /// Like `EstimateSwap` with `SwapAmount::In`, but returns the output as a plain amount.
+    /// Returns how many `denom_out` of the last step we get for `token_in` of `first.denom_in`.
+    #[returns(EstimateSwapInResponse)]
+    EstimateSwapExactAmountIn {
+        sender: String,
+        first: Swap,
+        route: Vec<Step>,
+        token_in: Uint128,
+    },
+    /// Like `EstimateSwap` with `SwapAmount::Out`, but returns the input as a plain amount.
+    /// Returns how many `first.denom_in` we must pay to get `token_out` of the last step.
+    #[returns(EstimateSwapOutResponse)]
+    EstimateSwapExactAmountOut {
+        sender: String,
+        first: Swap,
+        route: Vec<Step>,
+        token_out: Uint128,
+    },
     // Returns the Arithmetic TWAP given base asset and quote asset.
     // CONTRACT: start_time and end_time should be based on Unix time millisecond.
     #[returns(ArithmeticTwapResponse)]
@@ -55,11 +157,77 @@ pub enum OsmosisQuery {
         base_asset_denom: String,
         start_time: i64,
     },
+    /// Returns the geometric TWAP of the base asset in the quote asset, the time-weighted average
+    /// of the log-prices, exponentiated. It is never above the arithmetic TWAP of the same window.
+    /// Times are Unix time millisecond.
+    #[returns(GeometricTwapResponse)]
+    GeometricTwap {
+        id: u64,
+        quote_asset_denom: String,
+        base_asset_denom: String,
+        start_time: i64,
+        end_time: i64,
+    },
+    /// Estimates how much worse than the current spot price a swap over the route would execute.
+    /// Prices are in units of the final `denom_out` per first `denom_in`.
+    #[returns(PriceImpactResponse)]
+    EstimatePriceImpact {
+        first: Swap,
+        route: Vec<Step>,
+        amount: SwapAmount,
+    },
+    /// Returns the x/lockup lock with the given ID
+    #[returns(LockedResponse)]
+    LockedById { lock_id: u64 },
+    /// Returns the total of all coins locked by `address`, including those currently unlocking
+    #[returns(AccountLockedCoinsResponse)]
+    AccountLockedCoins { address: String },
+    /// Lists the locks of `address` by ascending lock ID, including those currently unlocking.
+    /// Pass the `lock_id` of the last lock returned as `start_after` to get the next page.
+    #[returns(AccountLocksResponse)]
+    AccountLocks {
+        address: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Returns the validator the lock with the given ID is superfluid delegated to, if any
+    #[returns(SuperfluidDelegationResponse)]
+    SuperfluidDelegation { lock_id: u64 },
+    /// Returns the liquidity of all pools summed by denom
+    #[returns(TotalLiquidityResponse)]
+    TotalLiquidity {},
+    /// Returns the total supply of any bank denom
+    #[returns(DenomSupplyResponse)]
+    DenomSupply { denom: String },
+    /// Returns the x/incentives gauge with the given ID
+    #[returns(GaugeResponse)]
+    GaugeById { id: u64 },
+    /// Returns the current state of the epoch with the given identifier, e.g. "day" or "week"
+    #[returns(EpochInfoResponse)]
+    EpochInfo { identifier: String },
+    /// Returns how many OSMO x/mint currently mints per epoch
+    #[returns(EpochProvisionsResponse)]
+    EpochProvisions {},
+    /// Returns the concentrated liquidity position with the given ID
+    #[returns(PositionResponse)]
+    PositionById { position_id: u64 },
 }
 
 impl CustomQuery for OsmosisQuery {}
 
 impl OsmosisQuery {
+    /// Wraps the query in a `QueryRequest`, ready for `QuerierWrapper::query`
+    ///
+    /// ```
+    /// # use cosmwasm_std::QueryRequest;
+    /// # use osmo_bindings::OsmosisQuery;
+    /// let query = OsmosisQuery::spot_price(1, "uosmo", "uatom");
+    /// assert_eq!(query.clone().request(), QueryRequest::Custom(query));
+    /// ```
+    pub fn request(self) -> QueryRequest<OsmosisQuery> {
+        QueryRequest::Custom(self)
+    }
+
     /// Calculate spot price without swap fee
     pub fn spot_price(pool_id: u64, denom_in: &str, denom_out: &str) -> Self {
         OsmosisQuery::SpotPrice {
@@ -68,6 +236,32 @@ impl OsmosisQuery {
         }
     }
 
+    /// Price of `base_asset_denom` measured in `quote_asset_denom`, see `OsmosisQuery::SpotPriceV2`
+    pub fn spot_price_v2(
+        pool_id: u64,
+        base_asset_denom: impl Into<String>,
+        quote_asset_denom: impl Into<String>,
+    ) -> Self {
+        OsmosisQuery::SpotPriceV2 {
+            pool_id,
+            base_asset_denom: base_asset_denom.into(),
+            quote_asset_denom: quote_asset_denom.into(),
+        }
+    }
+
+    /// Calculate spot price of the given kind
+    pub fn spot_price_of_kind(
+        pool_id: u64,
+        denom_in: &str,
+        denom_out: &str,
+        kind: SpotPriceKind,
+    ) -> Self {
+        OsmosisQuery::SpotPrice {
+            swap: Swap::new(pool_id, denom_in, denom_out),
+            with_swap_fee: kind.with_swap_fee(),
+        }
+    }
+
     /// Basic helper to estimate price of a swap on one pool
     pub fn estimate_swap(
         contract: impl Into<String>,
@@ -75,12 +269,80 @@ impl OsmosisQuery {
         denom_in: impl Into<String>,
         denom_out: impl Into<String>,
         amount: SwapAmount,
+    ) -> Self {
+        Self::estimate_route_swap(
+            contract,
+            Swap::new(pool_id, denom_in, denom_out),
+            vec![],
+            amount,
+        )
+    }
+
+    /// Helper to estimate price of a swap over one or more pools
+    ///
+    /// ```
+    /// # use cosmwasm_std::Uint128;
+    /// # use osmo_bindings::{OsmosisQuery, Step, Swap, SwapAmount};
+    /// // sell 1000 osmo for atom on pool 1, then the atom for btc on pool 2
+    /// let query = OsmosisQuery::estimate_route_swap(
+    ///     "contract",
+    ///     Swap::new(1, "osmo", "atom"),
+    ///     vec![Step::new(2, "btc")],
+    ///     SwapAmount::In(Uint128::new(1000)),
+    /// );
+    /// assert_eq!(
+    ///     query,
+    ///     OsmosisQuery::EstimateSwap {
+    ///         sender: "contract".to_string(),
+    ///         first: Swap::new(1, "osmo", "atom"),
+    ///         route: vec![Step::new(2, "btc")],
+    ///         amount: SwapAmount::In(Uint128::new(1000)),
+    ///     }
+    /// );
+    /// ```
+    pub fn estimate_route_swap(
+        sender: impl Into<String>,
+        first: Swap,
+        route: Vec<Step>,
+        amount: SwapAmount,
     ) -> Self {
         OsmosisQuery::EstimateSwap {
+            sender: sender.into(),
+            first,
+            route,
+            amount,
+        }
+    }
+
+    /// Basic helper to estimate the output of swapping an exact input on one pool
+    pub fn estimate_swap_exact_amount_in(
+        contract: impl Into<String>,
+        pool_id: u64,
+        denom_in: impl Into<String>,
+        denom_out: impl Into<String>,
+        token_in: impl Into<Uint128>,
+    ) -> Self {
+        OsmosisQuery::EstimateSwapExactAmountIn {
+            sender: contract.into(),
+            first: Swap::new(pool_id, denom_in, denom_out),
+            route: vec![],
+            token_in: token_in.into(),
+        }
+    }
+
+    /// Basic helper to estimate the input needed for an exact output on one pool
+    pub fn estimate_swap_exact_amount_out(
+        contract: impl Into<String>,
+        pool_id: u64,
+        denom_in: impl Into<String>,
+        denom_out: impl Into<String>,
+        token_out: impl Into<Uint128>,
+    ) -> Self {
+        OsmosisQuery::EstimateSwapExactAmountOut {
             sender: contract.into(),
             first: Swap::new(pool_id, denom_in, denom_out),
-            amount,
             route: vec![],
+            token_out: token_out.into(),
         }
     }
 
@@ -100,6 +362,52 @@ impl OsmosisQuery {
         }
     }
 
+    /// Like `arithmetic_twap`, but rejects a window the chain would refuse: both times
+    /// (Unix time millisecond) must be positive and `start_time` must be before `end_time`.
+    pub fn arithmetic_twap_checked(
+        pool_id: u64,
+        quote_asset_denom: impl Into<String>,
+        base_asset_denom: impl Into<String>,
+        start_time: i64,
+        end_time: i64,
+    ) -> StdResult<Self> {
+        if start_time <= 0 {
+            return Err(StdError::generic_err(format!(
+                "TWAP start_time must be positive, got {}",
+                start_time
+            )));
+        }
+        if start_time >= end_time {
+            return Err(StdError::generic_err(format!(
+                "TWAP start_time {} must be before end_time {}",
+                start_time, end_time
+            )));
+        }
+        Ok(OsmosisQuery::arithmetic_twap(
+            pool_id,
+            quote_asset_denom,
+            base_asset_denom,
+            start_time,
+            end_time,
+        ))
+    }
+
+    pub fn geometric_twap(
+        pool_id: u64,
+        quote_asset_denom: impl Into<String>,
+        base_asset_denom: impl Into<String>,
+        start_time: i64,
+        end_time: i64,
+    ) -> Self {
+        OsmosisQuery::GeometricTwap {
+            id: pool_id,
+            quote_asset_denom: quote_asset_denom.into(),
+            base_asset_denom: base_asset_denom.into(),
+            start_time,
+            end_time,
+        }
+    }
+
     pub fn arithmetic_twap_to_now(
         pool_id: u64,
         quote_asset_denom: impl Into<String>,
@@ -120,15 +428,60 @@ pub struct FullDenomResponse {
     pub denom: String,
 }
 
+impl FullDenomResponse {
+    /// Decodes the data returned by executing `OsmosisMsg::CreateDenom` or one of the mint
+    /// messages, as found in a reply or multitest `AppResponse`
+    pub fn from_data(data: &Option<Binary>) -> StdResult<Self> {
+        match data {
+            Some(data) => from_binary(data),
+            None => Err(StdError::generic_err(
+                "Denom message returned no data to parse",
+            )),
+        }
+    }
+}
+
+#[cw_serde]
+pub struct DenomAdminResponse {
+    /// Empty if the denom has no admin
+    pub admin: String,
+}
+
+#[cw_serde]
+pub struct IsFactoryDenomResponse {
+    pub is_factory: bool,
+}
+
+#[cw_serde]
+pub struct BeforeSendHookResponse {
+    /// Empty if the denom has no hook
+    pub cosmwasm_address: String,
+}
+
+#[cw_serde]
+pub struct DenomsByCreatorResponse {
+    /// Full denoms, sorted
+    pub denoms: Vec<String>,
+}
+
 #[cw_serde]
 pub struct PoolStateResponse {
     /// The various assets that be swapped. Including current liquidity.
+    /// They come in the order the pool holds them, which is not necessarily sorted, so compare
+    /// responses via `sorted` if the order doesn't matter.
     pub assets: Vec<Coin>,
     /// The number of lp shares and their amount
     pub shares: Coin,
 }
 
 impl PoolStateResponse {
+    /// The same state with the assets sorted by denom, so that equivalent pools compare equal
+    /// regardless of the order their assets were added in
+    pub fn sorted(mut self) -> Self {
+        self.assets.sort_by(|a, b| a.denom.cmp(&b.denom));
+        self
+    }
+
     pub fn has_denom(&self, denom: &str) -> bool {
         self.assets.iter().any(|c| c.denom == denom)
     }
@@ -137,17 +490,91 @@ impl PoolStateResponse {
         &self.shares.denom
     }
 
+    /// The number of lp shares issued
+    pub fn total_shares(&self) -> Uint128 {
+        self.shares.amount
+    }
+
     /// If I hold num_shares of the lp_denom, how many assets does that equate to?
+    /// Each amount is rounded down, so this never overstates a holder's claim.
     pub fn shares_value(&self, num_shares: impl Into<Uint128>) -> Vec<Coin> {
         let num_shares = num_shares.into();
         self.assets
             .iter()
             .map(|c| Coin {
                 denom: c.denom.clone(),
-                amount: c.amount * num_shares / self.shares.amount,
+                amount: c.amount.multiply_ratio(num_shares, self.shares.amount),
             })
             .collect()
     }
+
+    /// How many `denom_b` one `denom_a` is worth at the ratio of the reserves.
+    /// This ignores pool weights and fees, and is None if either denom is missing or
+    /// `denom_a` has no liquidity.
+    pub fn price_of(&self, denom_a: &str, denom_b: &str) -> Option<Decimal> {
+        self.reserve_ratio(denom_b, denom_a)
+    }
+
+    /// The reserve of `denom_num` divided by the reserve of `denom_den`, without any fee.
+    /// None if either denom is missing or `denom_den` has no liquidity.
+    ///
+    /// This is instantaneous, do not use as oracle: like the spot price, it moves with every
+    /// trade and can be manipulated within a single block.
+    pub fn reserve_ratio(&self, denom_num: &str, denom_den: &str) -> Option<Decimal> {
+        let amount_of = |denom: &str| {
+            self.assets
+                .iter()
+                .find(|c| c.denom == denom)
+                .map(|c| c.amount)
+        };
+        Decimal::checked_from_ratio(amount_of(denom_num)?, amount_of(denom_den)?).ok()
+    }
+}
+
+#[cw_serde]
+pub struct PoolInfo {
+    pub id: u64,
+    pub state: PoolStateResponse,
+}
+
+#[cw_serde]
+pub struct PoolsResponse {
+    pub pools: Vec<PoolInfo>,
+}
+
+#[cw_serde]
+pub struct TotalPoolLiquidityResponse {
+    pub liquidity: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct PoolLiquidityResponse {
+    pub amount: Uint128,
+}
+
+#[cw_serde]
+pub struct PoolTypeResponse {
+    /// One of "balancer", "stableswap" or "concentrated"
+    pub pool_type: String,
+}
+
+#[cw_serde]
+pub struct PoolParamsResponse {
+    pub params: PoolParams,
+}
+
+#[cw_serde]
+pub struct CalcJoinPoolSharesResponse {
+    /// The number of LP shares the join would mint
+    pub share_out_amount: Uint128,
+    /// The part of `tokens_in` the join would take, in pool order
+    pub tokens_used: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct CalcExitResponse {
+    /// The assets the exit would pay out, in pool order
+    pub tokens_out: Vec<Coin>,
 }
 
 #[cw_serde]
@@ -156,6 +583,33 @@ pub struct SpotPriceResponse {
     pub price: Decimal,
 }
 
+#[cw_serde]
+pub struct SpotPriceBothResponse {
+    /// How many `denom_a` we would get for 1 `denom_b`
+    pub a_per_b: Decimal,
+    /// How many `denom_b` we would get for 1 `denom_a`
+    pub b_per_a: Decimal,
+}
+
+#[cw_serde]
+pub struct LpSpotPriceResponse {
+    /// How many `quote_denom` one LP share is worth
+    pub price: Decimal,
+}
+
+#[cw_serde]
+pub struct SpotPriceWithFeeResponse {
+    /// How many output we would get for 1 input, net of `swap_fee`
+    pub price: Decimal,
+    /// The swap fee the price was computed with
+    pub swap_fee: Decimal,
+}
+
+/// The result of a swap, holding the side of the trade that was not fixed by the request.
+///
+/// `amount` is externally tagged with its direction in snake case, and the amount is a string:
+/// swapping an exact input yields `{"amount":{"out":"123"}}`, while swapping for an exact output
+/// yields `{"amount":{"in":"123"}}`.
 #[cw_serde]
 pub struct SwapResponse {
     // If you query with SwapAmount::Input, this is SwapAmount::Output
@@ -163,6 +617,129 @@ pub struct SwapResponse {
     pub amount: SwapAmount,
 }
 
+impl SwapResponse {
+    /// The JSON tag of `amount`, either `"in"` or `"out"`
+    pub fn direction(&self) -> &'static str {
+        match self.amount {
+            SwapAmount::In(_) => "in",
+            SwapAmount::Out(_) => "out",
+        }
+    }
+
+    /// Decodes the data returned by executing `OsmosisMsg::Swap`, as found in a reply or
+    /// multitest `AppResponse`
+    pub fn from_data(data: &Option<Binary>) -> StdResult<Self> {
+        match data {
+            Some(data) => from_binary(data),
+            None => Err(StdError::generic_err("Swap returned no data to parse")),
+        }
+    }
+
+    /// The price the swap executed at, output divided by input, like
+    /// `PriceImpactResponse::effective_price`.
+    /// `counterpart` is the amount this response doesn't hold: the input of an exact-in swap
+    /// (when `amount` is `Out`), or the output of an exact-out swap (when `amount` is `In`).
+    /// None if the input is zero.
+    pub fn effective_price(&self, counterpart: Uint128) -> Option<Decimal> {
+        let (input, output) = match self.amount {
+            SwapAmount::Out(output) => (counterpart, output),
+            SwapAmount::In(input) => (input, counterpart),
+        };
+        Decimal::checked_from_ratio(output, input).ok()
+    }
+}
+
+#[cw_serde]
+pub struct PriceImpactResponse {
+    /// The marginal price over the route before swapping, without fees
+    pub spot_price_before: Decimal,
+    /// The price the swap would actually execute at, output divided by input
+    pub effective_price: Decimal,
+    /// The relative shortfall of the effective price, `1 - effective / spot` (zero if none)
+    pub impact: Decimal,
+}
+
+#[cw_serde]
+pub struct EstimateSwapInResponse {
+    /// The amount of the final `denom_out` we would receive
+    pub token_out: Uint128,
+}
+
+#[cw_serde]
+pub struct EstimateSwapOutResponse {
+    /// The amount of `first.denom_in` we would have to pay
+    pub token_in: Uint128,
+}
+
+#[cw_serde]
+pub struct CreatePoolResponse {
+    /// The id assigned to the new pool
+    pub pool_id: u64,
+}
+
+#[cw_serde]
+pub struct PositionResponse {
+    pub pool_id: u64,
+    pub owner: String,
+    pub lower_tick: i64,
+    pub upper_tick: i64,
+    /// The liquidity of the position as a decimal string, like the chain reports it
+    pub liquidity: String,
+    /// The pool assets the position's liquidity can currently be withdrawn for
+    pub coins: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct CreateGaugeResponse {
+    /// The id assigned to the new gauge
+    pub gauge_id: u64,
+}
+
+#[cw_serde]
+pub struct CreatePositionResponse {
+    /// The id assigned to the new position
+    pub position_id: u64,
+    /// The amounts of the pool's first and second asset taken from the sender
+    pub amount0: Uint128,
+    pub amount1: Uint128,
+    /// The liquidity of the new position as a decimal string, like the chain reports it
+    pub liquidity_created: String,
+}
+
+#[cw_serde]
+pub struct WithdrawPositionResponse {
+    /// The amounts of the pool's first and second asset paid out to the sender
+    pub amount0: Uint128,
+    pub amount1: Uint128,
+}
+
+#[cw_serde]
+pub struct CollectSpreadRewardsResponse {
+    /// The spread rewards of all positions together, paid out to the sender
+    pub collected_spread_rewards: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct CollectIncentivesResponse {
+    /// The incentives of all positions together, paid out to the sender
+    pub collected_incentives: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct JoinPoolResponse {
+    /// The assets actually taken from the sender, in pool order.
+    /// Anything offered in `token_in_maxs` above this is left with the sender.
+    pub tokens_consumed: Vec<Coin>,
+    /// The number of LP shares minted to the sender
+    pub shares_out: Uint128,
+}
+
+#[cw_serde]
+pub struct ExitPoolResponse {
+    /// The assets paid out to the sender, in pool order
+    pub tokens_out: Vec<Coin>,
+}
+
 #[cw_serde]
 pub struct ArithmeticTwapResponse {
     pub twap: Decimal,
@@ -172,3 +749,269 @@ pub struct ArithmeticTwapResponse {
 pub struct ArithmeticTwapToNowResponse {
     pub twap: Decimal,
 }
+
+#[cw_serde]
+pub struct GeometricTwapResponse {
+    pub twap: Decimal,
+}
+
+#[cw_serde]
+pub struct LockedResponse {
+    pub lock_id: u64,
+    pub owner: String,
+    /// How long the coins stay locked once unlocking begins, in seconds
+    pub duration: u64,
+    /// When unlocking completes (Unix time millisecond), or None while still locked
+    pub end_time: Option<i64>,
+    pub coins: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct AccountLockedCoinsResponse {
+    /// Sum of all locks by denom, sorted by denom
+    pub coins: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct AccountLocksResponse {
+    pub locks: Vec<LockedResponse>,
+}
+
+#[cw_serde]
+pub struct SuperfluidDelegationResponse {
+    /// None if the lock is not superfluid delegated
+    pub validator: Option<String>,
+    /// The locked coins backing the delegation, empty if not delegated
+    pub delegated_coins: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct TotalLiquidityResponse {
+    /// Sum of the assets of every pool by denom, sorted by denom
+    pub coins: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct DenomSupplyResponse {
+    pub amount: Uint128,
+}
+
+#[cw_serde]
+pub struct GaugeResponse {
+    pub id: u64,
+    pub is_perpetual: bool,
+    /// The locked denom that qualifies for rewards
+    pub denom: String,
+    /// All coins ever put into the gauge
+    pub coins: Vec<Coin>,
+    /// When the gauge starts paying out (Unix time millisecond)
+    pub start_time: i64,
+    pub num_epochs_paid_over: u64,
+    /// Number of epochs the gauge has paid out in so far
+    pub filled_epochs: u64,
+    pub distributed_coins: Vec<Coin>,
+}
+
+/// All times are unix timestamps in seconds
+#[cw_serde]
+pub struct EpochInfoResponse {
+    /// Number of the running epoch, the first one is 1
+    pub current_epoch: i64,
+    /// When the first epoch started
+    pub start_time: i64,
+    /// Length of each epoch in seconds
+    pub duration: u64,
+    pub current_epoch_start_time: i64,
+}
+
+#[cw_serde]
+pub struct EpochProvisionsResponse {
+    /// Amount of the mint denom (uosmo) minted each epoch
+    pub epoch_provisions: Decimal,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::{coin, from_slice, to_binary, to_vec};
+
+    #[test]
+    fn swap_response_from_data() {
+        let res = SwapResponse {
+            amount: SwapAmount::Out(Uint128::new(1234)),
+        };
+        let data = Some(to_binary(&res).unwrap());
+        assert_eq!(SwapResponse::from_data(&data).unwrap(), res);
+
+        let err = SwapResponse::from_data(&None).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Swap returned no data to parse"));
+    }
+
+    #[test]
+    fn full_denom_response_from_data() {
+        let res = FullDenomResponse {
+            denom: "factory/creator/fundz".to_string(),
+        };
+        let data = Some(to_binary(&res).unwrap());
+        assert_eq!(FullDenomResponse::from_data(&data).unwrap(), res);
+
+        let err = FullDenomResponse::from_data(&None).unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Denom message returned no data to parse")
+        );
+    }
+
+    #[test]
+    fn arithmetic_twap_checked_window() {
+        let query = OsmosisQuery::arithmetic_twap_checked(1, "uosmo", "uatom", 1000, 2000).unwrap();
+        assert_eq!(
+            query,
+            OsmosisQuery::arithmetic_twap(1, "uosmo", "uatom", 1000, 2000)
+        );
+
+        let err =
+            OsmosisQuery::arithmetic_twap_checked(1, "uosmo", "uatom", 2000, 1000).unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("TWAP start_time 2000 must be before end_time 1000")
+        );
+        let err =
+            OsmosisQuery::arithmetic_twap_checked(1, "uosmo", "uatom", 1000, 1000).unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("TWAP start_time 1000 must be before end_time 1000")
+        );
+        for start_time in [0, -1000] {
+            let err = OsmosisQuery::arithmetic_twap_checked(1, "uosmo", "uatom", start_time, 2000)
+                .unwrap_err();
+            assert_eq!(
+                err,
+                StdError::generic_err(format!(
+                    "TWAP start_time must be positive, got {}",
+                    start_time
+                ))
+            );
+        }
+        // a negative end time is caught by the ordering
+        OsmosisQuery::arithmetic_twap_checked(1, "uosmo", "uatom", 1000, -1).unwrap_err();
+    }
+
+    #[test]
+    fn swap_effective_price() {
+        // exact in: paid 1000, got 450
+        let res = SwapResponse {
+            amount: SwapAmount::Out(Uint128::new(450)),
+        };
+        assert_eq!(
+            res.effective_price(Uint128::new(1000)),
+            Some(Decimal::percent(45))
+        );
+        assert_eq!(res.effective_price(Uint128::zero()), None);
+
+        // exact out: got 450, paid 1000
+        let res = SwapResponse {
+            amount: SwapAmount::In(Uint128::new(1000)),
+        };
+        assert_eq!(
+            res.effective_price(Uint128::new(450)),
+            Some(Decimal::percent(45))
+        );
+        let res = SwapResponse {
+            amount: SwapAmount::In(Uint128::zero()),
+        };
+        assert_eq!(res.effective_price(Uint128::new(450)), None);
+    }
+
+    #[test]
+    fn swap_response_json() {
+        for (amount, direction, json) in [
+            (
+                SwapAmount::Out(Uint128::new(123)),
+                "out",
+                r#"{"amount":{"out":"123"}}"#,
+            ),
+            (
+                SwapAmount::In(Uint128::new(123)),
+                "in",
+                r#"{"amount":{"in":"123"}}"#,
+            ),
+        ] {
+            let res = SwapResponse { amount };
+            assert_eq!(res.direction(), direction);
+            assert_eq!(to_vec(&res).unwrap(), json.as_bytes());
+            assert_eq!(from_slice::<SwapResponse>(json.as_bytes()).unwrap(), res);
+        }
+    }
+
+    fn pool_state() -> PoolStateResponse {
+        PoolStateResponse {
+            assets: vec![coin(6_000_000, "osmo"), coin(1_500_000, "atom")],
+            shares: coin(3_000_000, "gamm/pool/1"),
+        }
+    }
+
+    #[test]
+    fn total_shares() {
+        assert_eq!(pool_state().total_shares(), Uint128::new(3_000_000));
+    }
+
+    #[test]
+    fn shares_value() {
+        let state = pool_state();
+        assert_eq!(
+            state.shares_value(30_000u128),
+            vec![coin(60_000, "osmo"), coin(15_000, "atom")]
+        );
+        // 7 * 6M / 3M = 14 exactly, but 7 * 1.5M / 3M = 3.5 is rounded down
+        assert_eq!(
+            state.shares_value(7u128),
+            vec![coin(14, "osmo"), coin(3, "atom")]
+        );
+        // too little to claim anything
+        assert_eq!(
+            state.shares_value(1u128),
+            vec![coin(2, "osmo"), coin(0, "atom")]
+        );
+    }
+
+    #[test]
+    fn price_of() {
+        let state = pool_state();
+        assert_eq!(state.price_of("atom", "osmo"), Some(Decimal::percent(400)));
+        assert_eq!(state.price_of("osmo", "atom"), Some(Decimal::percent(25)));
+        assert_eq!(state.price_of("osmo", "btc"), None);
+
+        let drained = PoolStateResponse {
+            assets: vec![coin(6_000_000, "osmo"), coin(0, "atom")],
+            shares: coin(3_000_000, "gamm/pool/1"),
+        };
+        assert_eq!(drained.price_of("atom", "osmo"), None);
+        assert_eq!(drained.price_of("osmo", "atom"), Some(Decimal::zero()));
+    }
+
+    #[test]
+    fn reserve_ratio() {
+        let state = pool_state();
+        assert_eq!(
+            state.reserve_ratio("osmo", "atom"),
+            Some(Decimal::percent(400))
+        );
+        assert_eq!(
+            state.reserve_ratio("atom", "osmo"),
+            Some(Decimal::percent(25))
+        );
+        assert_eq!(state.reserve_ratio("osmo", "osmo"), Some(Decimal::one()));
+
+        // missing on either side
+        assert_eq!(state.reserve_ratio("btc", "atom"), None);
+        assert_eq!(state.reserve_ratio("osmo", "btc"), None);
+
+        let drained = PoolStateResponse {
+            assets: vec![coin(6_000_000, "osmo"), coin(0, "atom")],
+            shares: coin(3_000_000, "gamm/pool/1"),
+        };
+        assert_eq!(drained.reserve_ratio("osmo", "atom"), None);
+        assert_eq!(drained.reserve_ratio("atom", "osmo"), Some(Decimal::zero()));
+    }
+}
@@ -1,7 +1,14 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
 
-use cosmwasm_std::Uint128;
+use cosmwasm_std::{Coin, Decimal, Fraction, StdError, StdResult, Uint128, Uint256};
+
+/// The lowest tick a concentrated liquidity position may span, as on Osmosis
+pub const MIN_TICK: i64 = -108_000_000;
+/// The highest tick a concentrated liquidity position may span, as on Osmosis
+pub const MAX_TICK: i64 = 342_000_000;
 
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema, Debug)]
 pub struct Swap {
@@ -18,6 +25,42 @@ impl Swap {
             denom_out: denom_out.into(),
         }
     }
+
+    /// Like `new`, but rejects empty denoms and swapping a denom for itself
+    pub fn try_new(
+        pool_id: u64,
+        denom_in: impl Into<String>,
+        denom_out: impl Into<String>,
+    ) -> StdResult<Self> {
+        let swap = Swap::new(pool_id, denom_in, denom_out);
+        if swap.denom_in.is_empty() || swap.denom_out.is_empty() {
+            return Err(StdError::generic_err("Swap denoms must not be empty"));
+        }
+        if swap.denom_in == swap.denom_out {
+            return Err(StdError::generic_err(format!(
+                "Cannot swap {} for itself",
+                swap.denom_in
+            )));
+        }
+        Ok(swap)
+    }
+}
+
+/// One leg of `OsmosisMsg::SplitRouteSwapExactAmountIn`: swaps `token_in_amount` along `pools`,
+/// starting from the message's `token_in_denom`
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema, Debug)]
+pub struct SwapRoute {
+    pub pools: Vec<Step>,
+    pub token_in_amount: Uint128,
+}
+
+impl SwapRoute {
+    pub fn new(pools: Vec<Step>, token_in_amount: impl Into<Uint128>) -> Self {
+        SwapRoute {
+            pools,
+            token_in_amount: token_in_amount.into(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema, Debug)]
@@ -35,6 +78,123 @@ impl Step {
     }
 }
 
+/// Splits a route given as `(pool_id, denom_out)` hops, starting from `first_denom_in`, into the
+/// `first` and `route` of `OsmosisMsg::Swap`.
+///
+/// ```
+/// # use osmo_bindings::{build_route, Step, Swap};
+/// let (first, route) = build_route(&[(1, "uatom"), (7, "ujuno")], "uosmo");
+/// assert_eq!(first, Swap::new(1, "uosmo", "uatom"));
+/// assert_eq!(route, vec![Step::new(7, "ujuno")]);
+/// ```
+///
+/// # Panics
+///
+/// If `hops` is empty, as a swap needs at least one pool.
+pub fn build_route(hops: &[(u64, &str)], first_denom_in: &str) -> (Swap, Vec<Step>) {
+    let ((pool_id, denom_out), rest) = hops
+        .split_first()
+        .expect("route must have at least one hop");
+    let first = Swap::new(*pool_id, first_denom_in, *denom_out);
+    let route = rest
+        .iter()
+        .map(|(pool_id, denom_out)| Step::new(*pool_id, *denom_out))
+        .collect();
+    (first, route)
+}
+
+/// Parameters of a new balancer pool
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema, Debug)]
+pub struct PoolParams {
+    /// Fraction of every swap input kept by the pool, e.g. 0.003 for 0.3%
+    pub swap_fee: Decimal,
+    /// Fraction of the shares of every exit kept by the pool
+    pub exit_fee: Decimal,
+}
+
+/// One of the assets of a new balancer pool.
+/// The initial liquidity `token` is taken from the creator.
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema, Debug)]
+pub struct PoolAsset {
+    pub token: Coin,
+    /// Relative weight of this asset in the pool. Only the ratio between weights matters.
+    pub weight: Uint128,
+}
+
+impl PoolAsset {
+    pub fn new(token: Coin, weight: impl Into<Uint128>) -> Self {
+        PoolAsset {
+            token,
+            weight: weight.into(),
+        }
+    }
+}
+
+/// Bank metadata of a denom, as the x/bank `Metadata` type
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema, Debug)]
+pub struct Metadata {
+    pub description: String,
+    /// All units of the denom, the base unit has exponent 0
+    pub denom_units: Vec<DenomUnit>,
+    /// The denom of the base unit, which balances are kept in
+    pub base: String,
+    /// The denom of the unit amounts are usually displayed in
+    pub display: String,
+    pub name: String,
+    pub symbol: String,
+}
+
+impl Metadata {
+    /// The exponent of the unit `denom`, looking at both its denom and aliases.
+    /// One `denom` is `10^exponent` base units, e.g. 6 for OSMO in uosmo.
+    pub fn exponent_of(&self, denom: &str) -> Option<u32> {
+        self.denom_units
+            .iter()
+            .find(|unit| unit.denom == denom || unit.aliases.iter().any(|alias| alias == denom))
+            .map(|unit| unit.exponent)
+    }
+}
+
+/// One unit of a denom, see `Metadata`
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema, Debug)]
+pub struct DenomUnit {
+    pub denom: String,
+    pub exponent: u32,
+    pub aliases: Vec<String>,
+}
+
+/// Which spot price to report for a pool.
+/// On the wire this is the `with_swap_fee` flag of `OsmosisQuery::SpotPrice`,
+/// so the kind itself is never serialized.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum SpotPriceKind {
+    /// The marginal price of the pool's curve at its current reserves, i.e. how much output
+    /// one unit of input buys in an infinitesimally small trade before any fee is charged.
+    /// For an equally weighted balancer pool this is just the ratio of the reserves.
+    /// This is what Osmosis reports as the spot price.
+    Marginal,
+    /// The marginal price scaled by `1 - swap_fee`: how much output one unit of input actually
+    /// yields in an infinitesimally small trade once the pool took its fee.
+    /// Larger trades get less than this due to price impact.
+    EffectiveWithFee,
+}
+
+impl SpotPriceKind {
+    pub fn with_swap_fee(self) -> bool {
+        matches!(self, SpotPriceKind::EffectiveWithFee)
+    }
+}
+
+impl From<bool> for SpotPriceKind {
+    fn from(with_swap_fee: bool) -> Self {
+        if with_swap_fee {
+            SpotPriceKind::EffectiveWithFee
+        } else {
+            SpotPriceKind::Marginal
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum SwapAmount {
@@ -56,6 +216,56 @@ impl SwapAmount {
             _ => panic!("was input"),
         }
     }
+
+    /// The amount, whichever direction it is
+    pub fn amount(&self) -> Uint128 {
+        match self {
+            SwapAmount::In(x) | SwapAmount::Out(x) => *x,
+        }
+    }
+
+    /// Turns an exact input into `SwapAmountWithLimit::ExactIn`, the inverse of
+    /// `SwapAmountWithLimit::discard_limit`. Panics on an output, like `as_in`.
+    pub fn with_min_output(self, min_output: Uint128) -> SwapAmountWithLimit {
+        SwapAmountWithLimit::ExactIn {
+            input: self.as_in(),
+            min_output,
+        }
+    }
+
+    /// Turns an exact output into `SwapAmountWithLimit::ExactOut`, the inverse of
+    /// `SwapAmountWithLimit::discard_limit`. Panics on an input, like `as_out`.
+    pub fn with_max_input(self, max_input: Uint128) -> SwapAmountWithLimit {
+        SwapAmountWithLimit::ExactOut {
+            output: self.as_out(),
+            max_input,
+        }
+    }
+}
+
+impl fmt::Display for SwapAmount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SwapAmount::In(x) => write!(f, "in:{}", x),
+            SwapAmount::Out(x) => write!(f, "out:{}", x),
+        }
+    }
+}
+
+/// Builds a `SwapAmount` from `'i'` (in) or `'o'` (out) and the amount
+impl TryFrom<(char, Uint128)> for SwapAmount {
+    type Error = StdError;
+
+    fn try_from((direction, amount): (char, Uint128)) -> StdResult<Self> {
+        match direction {
+            'i' => Ok(SwapAmount::In(amount)),
+            'o' => Ok(SwapAmount::Out(amount)),
+            _ => Err(StdError::generic_err(format!(
+                "invalid swap direction '{}', expected 'i' or 'o'",
+                direction
+            ))),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema, Debug)]
@@ -66,6 +276,28 @@ pub enum SwapAmountWithLimit {
 }
 
 impl SwapAmountWithLimit {
+    /// Sells exactly `input`, accepting at most `slippage` (e.g. 1% as `Decimal::percent(1)`)
+    /// less than the quoted `expected_out`. The limit rounds down, so it never asks for more.
+    pub fn exact_in_with_slippage(
+        input: Uint128,
+        expected_out: Uint128,
+        slippage: Decimal,
+    ) -> Self {
+        let min_output = min_out_after_slippage(expected_out, slippage);
+        SwapAmountWithLimit::ExactIn { input, min_output }
+    }
+
+    /// Buys exactly `output`, paying at most `slippage` more than the quoted `expected_in`.
+    /// The limit rounds up, so it never allows less than the tolerance.
+    pub fn exact_out_with_slippage(
+        output: Uint128,
+        expected_in: Uint128,
+        slippage: Decimal,
+    ) -> Self {
+        let max_input = max_in_after_slippage(expected_in, slippage);
+        SwapAmountWithLimit::ExactOut { output, max_input }
+    }
+
     pub fn discard_limit(self) -> SwapAmount {
         match self {
             SwapAmountWithLimit::ExactIn { input, .. } => SwapAmount::In(input),
@@ -73,3 +305,324 @@ impl SwapAmountWithLimit {
         }
     }
 }
+
+/// Parses the pool ID out of an LP share denom of the form `gamm/pool/{id}`.
+/// Returns None for anything else, including IDs with leading zeros.
+pub fn pool_id_from_lp_denom(denom: &str) -> Option<u64> {
+    let id = denom.strip_prefix("gamm/pool/")?;
+    if id.starts_with('0') || id.starts_with('+') {
+        return None;
+    }
+    id.parse().ok()
+}
+
+/// Splits a factory denom, `factory/{creator}/{subdenom}`, into its creator and subdenom.
+/// Like the chain, this takes everything after the creator as the subdenom, so it may be
+/// empty or contain further `/`. Returns None for anything else.
+pub fn split_factory_denom(denom: &str) -> Option<(&str, &str)> {
+    let mut parts = denom.splitn(3, '/');
+    if parts.next() != Some("factory") {
+        return None;
+    }
+    let creator = parts.next().filter(|creator| !creator.is_empty())?;
+    let subdenom = parts.next()?;
+    Some((creator, subdenom))
+}
+
+/// Checks whether `denom` has the shape of a factory denom, see `split_factory_denom`.
+/// This does not check that the denom exists, see `OsmosisQuery::IsFactoryDenom` for that.
+pub fn is_factory_denom(denom: &str) -> bool {
+    split_factory_denom(denom).is_some()
+}
+
+/// The least output to accept for a swap quoted at `estimated_out` (e.g. by
+/// `OsmosisQuery::EstimateSwap`), allowing `slippage` (e.g. 1% as `Decimal::percent(1)`).
+/// Rounds down, so it never asks for more than the tolerance allows.
+pub fn min_out_after_slippage(estimated_out: Uint128, slippage: Decimal) -> Uint128 {
+    if slippage >= Decimal::one() {
+        Uint128::zero()
+    } else {
+        estimated_out * (Decimal::one() - slippage)
+    }
+}
+
+/// The most input to pay for a swap quoted at `estimated_in`, allowing `slippage`.
+/// Rounds up, so it never allows less than the tolerance.
+pub fn max_in_after_slippage(estimated_in: Uint128, slippage: Decimal) -> Uint128 {
+    let factor = Decimal::one() + slippage;
+    let numerator = Uint256::from(estimated_in) * Uint256::from(factor.numerator());
+    let denominator = Uint256::from(factor.denominator());
+    let rounded_up = (numerator + denominator - Uint256::one()) / denominator;
+    // saturate rather than fail for absurdly large quotes
+    Uint128::try_from(rounded_up).unwrap_or(Uint128::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swap_amount_display() {
+        assert_eq!(SwapAmount::In(Uint128::new(1234)).to_string(), "in:1234");
+        assert_eq!(SwapAmount::Out(Uint128::new(1234)).to_string(), "out:1234");
+    }
+
+    #[test]
+    fn swap_amount_accessor() {
+        assert_eq!(SwapAmount::In(Uint128::new(5)).amount(), Uint128::new(5));
+        assert_eq!(SwapAmount::Out(Uint128::new(7)).amount(), Uint128::new(7));
+    }
+
+    #[test]
+    fn swap_amount_try_from() {
+        let amount = Uint128::new(42);
+        assert_eq!(
+            SwapAmount::try_from(('i', amount)).unwrap(),
+            SwapAmount::In(amount)
+        );
+        assert_eq!(
+            SwapAmount::try_from(('o', amount)).unwrap(),
+            SwapAmount::Out(amount)
+        );
+        SwapAmount::try_from(('x', amount)).unwrap_err();
+    }
+
+    #[test]
+    fn swap_limit_with_slippage() {
+        let one_percent = Decimal::percent(1);
+
+        // 99% of 1999 is 1979.01
+        let amount = SwapAmountWithLimit::exact_in_with_slippage(
+            Uint128::new(1000),
+            Uint128::new(1999),
+            one_percent,
+        );
+        assert_eq!(
+            amount,
+            SwapAmountWithLimit::ExactIn {
+                input: Uint128::new(1000),
+                min_output: Uint128::new(1979),
+            }
+        );
+
+        // 101% of 1999 is 2018.99
+        let amount = SwapAmountWithLimit::exact_out_with_slippage(
+            Uint128::new(1000),
+            Uint128::new(1999),
+            one_percent,
+        );
+        assert_eq!(
+            amount,
+            SwapAmountWithLimit::ExactOut {
+                output: Uint128::new(1000),
+                max_input: Uint128::new(2019),
+            }
+        );
+
+        // exact results are not rounded any further
+        let amount = SwapAmountWithLimit::exact_out_with_slippage(
+            Uint128::new(1000),
+            Uint128::new(2000),
+            one_percent,
+        );
+        assert_eq!(
+            amount,
+            SwapAmountWithLimit::ExactOut {
+                output: Uint128::new(1000),
+                max_input: Uint128::new(2020),
+            }
+        );
+    }
+
+    #[test]
+    fn metadata_exponent_of() {
+        let metadata = Metadata {
+            description: "The native token of Osmosis".to_string(),
+            denom_units: vec![
+                DenomUnit {
+                    denom: "uosmo".to_string(),
+                    exponent: 0,
+                    aliases: vec!["microosmo".to_string()],
+                },
+                DenomUnit {
+                    denom: "osmo".to_string(),
+                    exponent: 6,
+                    aliases: vec![],
+                },
+            ],
+            base: "uosmo".to_string(),
+            display: "osmo".to_string(),
+            name: "Osmosis".to_string(),
+            symbol: "OSMO".to_string(),
+        };
+
+        assert_eq!(metadata.exponent_of(&metadata.base), Some(0));
+        assert_eq!(metadata.exponent_of(&metadata.display), Some(6));
+        assert_eq!(metadata.exponent_of("microosmo"), Some(0));
+        assert_eq!(metadata.exponent_of("mosmo"), None);
+    }
+
+    #[test]
+    fn swap_try_new() {
+        let swap = Swap::try_new(1, "uosmo", "uatom").unwrap();
+        assert_eq!(swap, Swap::new(1, "uosmo", "uatom"));
+
+        let err = Swap::try_new(1, "uosmo", "uosmo").unwrap_err();
+        assert_eq!(err, StdError::generic_err("Cannot swap uosmo for itself"));
+
+        for (denom_in, denom_out) in [("", "uatom"), ("uosmo", ""), ("", "")] {
+            let err = Swap::try_new(1, denom_in, denom_out).unwrap_err();
+            assert_eq!(err, StdError::generic_err("Swap denoms must not be empty"));
+        }
+    }
+
+    #[test]
+    fn swap_amount_with_limit() {
+        let amount = SwapAmount::In(Uint128::new(1000)).with_min_output(Uint128::new(450));
+        assert_eq!(
+            amount,
+            SwapAmountWithLimit::ExactIn {
+                input: Uint128::new(1000),
+                min_output: Uint128::new(450),
+            }
+        );
+        assert_eq!(amount.discard_limit(), SwapAmount::In(Uint128::new(1000)));
+
+        let amount = SwapAmount::Out(Uint128::new(450)).with_max_input(Uint128::new(1000));
+        assert_eq!(
+            amount,
+            SwapAmountWithLimit::ExactOut {
+                output: Uint128::new(450),
+                max_input: Uint128::new(1000),
+            }
+        );
+        assert_eq!(amount.discard_limit(), SwapAmount::Out(Uint128::new(450)));
+    }
+
+    #[test]
+    #[should_panic(expected = "was output")]
+    fn min_output_on_output() {
+        SwapAmount::Out(Uint128::new(450)).with_min_output(Uint128::new(450));
+    }
+
+    #[test]
+    #[should_panic(expected = "was input")]
+    fn max_input_on_input() {
+        SwapAmount::In(Uint128::new(1000)).with_max_input(Uint128::new(1000));
+    }
+
+    #[test]
+    fn build_route_hops() {
+        let (first, route) = build_route(&[(1, "atom")], "osmo");
+        assert_eq!(first, Swap::new(1, "osmo", "atom"));
+        assert_eq!(route, vec![]);
+
+        let (first, route) = build_route(&[(1, "atom"), (2, "btc")], "osmo");
+        assert_eq!(first, Swap::new(1, "osmo", "atom"));
+        assert_eq!(route, vec![Step::new(2, "btc")]);
+
+        let (first, route) = build_route(&[(3, "atom"), (2, "btc"), (1, "eth")], "osmo");
+        assert_eq!(first, Swap::new(3, "osmo", "atom"));
+        assert_eq!(route, vec![Step::new(2, "btc"), Step::new(1, "eth")]);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one hop")]
+    fn build_route_empty() {
+        build_route(&[], "osmo");
+    }
+
+    #[test]
+    fn factory_denom_shape() {
+        for denom in [
+            "factory/creator/fundz",
+            "factory/creator/",
+            "factory/creator/nested/path",
+        ] {
+            assert!(is_factory_denom(denom), "{}", denom);
+        }
+        for denom in [
+            "factory",
+            "factory/",
+            "factory/creator",
+            "factory//fundz",
+            "factories/creator/fundz",
+            "ibc/factory/creator/fundz",
+            "uosmo",
+            "",
+        ] {
+            assert!(!is_factory_denom(denom), "{}", denom);
+        }
+
+        assert_eq!(
+            split_factory_denom("factory/creator/nested/path"),
+            Some(("creator", "nested/path"))
+        );
+        assert_eq!(
+            split_factory_denom("factory/creator/"),
+            Some(("creator", ""))
+        );
+    }
+
+    #[test]
+    fn parse_lp_denom() {
+        assert_eq!(pool_id_from_lp_denom("gamm/pool/1"), Some(1));
+        assert_eq!(pool_id_from_lp_denom("gamm/pool/1234"), Some(1234));
+
+        for denom in [
+            "gamm/pool/",
+            "gamm/pool/0",
+            "gamm/pool/01",
+            "gamm/pool/+1",
+            "gamm/pool/-1",
+            "gamm/pool/1a",
+            "gamm/pool/1/2",
+            "gamm/pool/18446744073709551616",
+            "gamm/pools/1",
+            "factory/creator/gamm/pool/1",
+            "uosmo",
+        ] {
+            assert_eq!(pool_id_from_lp_denom(denom), None, "{}", denom);
+        }
+    }
+
+    #[test]
+    fn slippage_rounding_at_small_amounts() {
+        let one_percent = Decimal::percent(1);
+
+        // 99% of 1 is 0.99, which rounds down to nothing
+        assert_eq!(
+            min_out_after_slippage(Uint128::new(1), one_percent),
+            Uint128::zero()
+        );
+        // 99% of 100 is exactly 99
+        assert_eq!(
+            min_out_after_slippage(Uint128::new(100), one_percent),
+            Uint128::new(99)
+        );
+        // all of it may slip
+        assert_eq!(
+            min_out_after_slippage(Uint128::new(100), Decimal::percent(150)),
+            Uint128::zero()
+        );
+
+        // 101% of 1 is 1.01, which rounds up to 2
+        assert_eq!(
+            max_in_after_slippage(Uint128::new(1), one_percent),
+            Uint128::new(2)
+        );
+        assert_eq!(
+            max_in_after_slippage(Uint128::zero(), one_percent),
+            Uint128::zero()
+        );
+        // no slippage leaves the quote untouched
+        assert_eq!(
+            max_in_after_slippage(Uint128::new(7), Decimal::zero()),
+            Uint128::new(7)
+        );
+        assert_eq!(
+            max_in_after_slippage(Uint128::MAX, one_percent),
+            Uint128::MAX
+        );
+    }
+}